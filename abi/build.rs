@@ -1,12 +1,52 @@
 use std::io::Result;
 use std::process::Command;
 
+// json中时间使用RFC 3339字符串，枚举使用小写的名字(而不是seconds/nanos和数字)
+const TIMESTAMP_FIELDS: [&str; 7] = [
+    "Reservation.start",
+    "Reservation.end",
+    "ReservationQuery.start",
+    "ReservationQuery.end",
+    "ReservationFilter.start",
+    "ReservationFilter.end",
+    "ReservationChange.changed_at",
+];
+const STATUS_FIELDS: [&str; 3] = [
+    "Reservation.status",
+    "ReservationQuery.status",
+    "ReservationFilter.status",
+];
+const UPDATE_TYPE_FIELDS: [&str; 2] = ["ReservationChange.op", "ListenResponse.op"];
+
 fn main() -> Result<()> {
-    tonic_build::configure()
+    // 生成的类型支持serde，供rest网关使用，缺少的字段使用默认值
+    let mut builder = tonic_build::configure()
+        .type_attribute(
+            ".reservation",
+            "#[derive(serde::Serialize, serde::Deserialize)]",
+        )
+        .message_attribute(".reservation", "#[serde(default)]")
+        .enum_attribute(".reservation", r#"#[serde(rename_all = "lowercase")]"#);
+    for (fields, with) in [
+        (TIMESTAMP_FIELDS.as_slice(), "timestamp"),
+        (STATUS_FIELDS.as_slice(), "status"),
+        (UPDATE_TYPE_FIELDS.as_slice(), "update_type"),
+    ] {
+        for field in fields {
+            builder = builder.field_attribute(
+                format!(".reservation.{}", field),
+                format!(r#"#[serde(with = "crate::pb::json::{}")]"#, with),
+            );
+        }
+    }
+
+    // 描述符集合供grpc反射服务使用
+    builder
         .out_dir("src/pb")
+        .file_descriptor_set_path("src/pb/reservation_descriptor.bin")
         .compile(&["protos/reservation.proto"], &["protos"])?;
 
-    Command::new("cargo").args(&["fmt"]).output().unwrap();
+    Command::new("cargo").args(["fmt"]).output().unwrap();
 
     println!("cargo:rerun-if-changed=protos/reservation.proto");
     Ok(())
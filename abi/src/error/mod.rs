@@ -1,15 +1,25 @@
-use sqlx::{error, postgres::PgDatabaseError};
+use sqlx::{migrate::MigrateError, postgres::PgDatabaseError};
+use std::path::PathBuf;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("数据库错误: {0}")]
     DbError(sqlx::Error),
 
-    #[error("配置文件读取错误")]
-    ConfigReadError,
+    #[error("配置文件读取错误: {}: {source}", .path.display())]
+    ConfigReadError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
 
-    #[error("配置文件解析错误")]
-    ConfigParseError,
+    #[error("配置文件解析错误: {}: {source}", .path.display())]
+    ConfigParseError {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+
+    #[error("非法的配置: {0}")]
+    InvalidConfig(String),
 
     #[error("非法的开始或结束预定时间")]
     InvalidTime,
@@ -17,15 +27,27 @@ pub enum Error {
     #[error("未找到预定记录")]
     NotFound,
 
+    #[error("预定时间冲突: {0}")]
+    ConflictReservation(String),
+
     #[error("非法的预定ID: {0}")]
     InvalidReservationId(i64),
 
+    #[error("非法的预定公开ID: {0}")]
+    InvalidPublicId(String),
+
     #[error("非法的用户ID: {0}")]
     InvalidUserId(String),
 
+    #[error("非法的操作者: {0}")]
+    InvalidActor(String),
+
     #[error("非法的资源ID: {0}")]
     InvalidResourceId(String),
 
+    #[error("非法的租户ID: {0}")]
+    InvalidTenant(String),
+
     #[error("非法的页大小: {0}")]
     InvalidPageSize(i64),
 
@@ -35,6 +57,24 @@ pub enum Error {
     #[error("非法的状态: {0}")]
     InvalidStatus(i32),
 
+    #[error("非法的幂等键: {0}")]
+    InvalidIdempotencyKey(String),
+
+    #[error("无效的日历: {0}")]
+    InvalidCalendar(String),
+
+    #[error("幂等键已被不同的请求使用: {0}")]
+    IdempotencyKeyReused(String),
+
+    #[error("相同幂等键的请求正在处理中: {0}")]
+    IdempotencyKeyInFlight(String),
+
+    #[error("没有权限: {0}")]
+    PermissionDenied(String),
+
+    #[error("预定版本不匹配: 期望 {expected}, 实际 {actual}")]
+    VersionMismatch { expected: i64, actual: i64 },
+
     #[error("未知错误")]
     Unknown,
 }
@@ -45,6 +85,26 @@ impl PartialEq for Error {
         match (self, other) {
             (Self::DbError(_), Self::DbError(_)) => true,
             (Self::Unknown, Self::Unknown) => true,
+            (Self::NotFound, Self::NotFound) => true,
+            (Self::ConflictReservation(_), Self::ConflictReservation(_)) => true,
+            (Self::PermissionDenied(_), Self::PermissionDenied(_)) => true,
+            (Self::InvalidPublicId(a), Self::InvalidPublicId(b)) => a == b,
+            (Self::InvalidActor(a), Self::InvalidActor(b)) => a == b,
+            (Self::InvalidTenant(a), Self::InvalidTenant(b)) => a == b,
+            (Self::InvalidPageSize(a), Self::InvalidPageSize(b)) => a == b,
+            (Self::InvalidCursor(a), Self::InvalidCursor(b)) => a == b,
+            (Self::InvalidStatus(a), Self::InvalidStatus(b)) => a == b,
+            (Self::InvalidIdempotencyKey(a), Self::InvalidIdempotencyKey(b)) => a == b,
+            (Self::InvalidCalendar(a), Self::InvalidCalendar(b)) => a == b,
+            (Self::IdempotencyKeyReused(a), Self::IdempotencyKeyReused(b)) => a == b,
+            (Self::IdempotencyKeyInFlight(a), Self::IdempotencyKeyInFlight(b)) => a == b,
+            (
+                Self::VersionMismatch { expected, actual },
+                Self::VersionMismatch {
+                    expected: e,
+                    actual: a,
+                },
+            ) => expected == e && actual == a,
             _ => false,
         }
     }
@@ -55,13 +115,61 @@ impl From<sqlx::Error> for Error {
     fn from(e: sqlx::Error) -> Self {
         match e {
             sqlx::Error::Database(e) => {
-                let err: &PgDatabaseError = e.downcast_ref();
-                match (err.code(), err.schema(), err.table()) {
-                    _ => Error::DbError(sqlx::Error::Database(e)),
+                // 违反postgres的排他约束，说明与已有的预定时间冲突
+                if let Some(err) = e.try_downcast_ref::<PgDatabaseError>() {
+                    if let ("23P01", Some("rsvp"), Some("reservations")) =
+                        (err.code(), err.schema(), err.table())
+                    {
+                        return Error::ConflictReservation(
+                            err.detail().unwrap_or_default().to_string(),
+                        );
+                    }
                 }
+                Error::DbError(sqlx::Error::Database(e))
             }
             sqlx::Error::RowNotFound => Error::NotFound,
             _ => Error::DbError(e),
         }
     }
 }
+
+// 为abi::Error实现从迁移错误转换接口
+impl From<MigrateError> for Error {
+    fn from(e: MigrateError) -> Self {
+        Error::DbError(e.into())
+    }
+}
+
+// 为tonic::Status实现从abi::Error转换接口，用于grpc服务返回错误
+impl From<Error> for tonic::Status {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::DbError(_)
+            | Error::ConfigReadError { .. }
+            | Error::ConfigParseError { .. }
+            | Error::InvalidConfig(_)
+            | Error::Unknown => tonic::Status::internal(e.to_string()),
+            Error::NotFound => tonic::Status::not_found(e.to_string()),
+            Error::PermissionDenied(_) => tonic::Status::permission_denied(e.to_string()),
+            Error::ConflictReservation(_) | Error::IdempotencyKeyReused(_) => {
+                tonic::Status::failed_precondition(e.to_string())
+            }
+            // 客户端可以重新读取最新版本或稍后重试
+            Error::VersionMismatch { .. } | Error::IdempotencyKeyInFlight(_) => {
+                tonic::Status::aborted(e.to_string())
+            }
+            Error::InvalidTime
+            | Error::InvalidReservationId(_)
+            | Error::InvalidPublicId(_)
+            | Error::InvalidUserId(_)
+            | Error::InvalidActor(_)
+            | Error::InvalidResourceId(_)
+            | Error::InvalidTenant(_)
+            | Error::InvalidPageSize(_)
+            | Error::InvalidCursor(_)
+            | Error::InvalidStatus(_)
+            | Error::InvalidIdempotencyKey(_)
+            | Error::InvalidCalendar(_) => tonic::Status::invalid_argument(e.to_string()),
+        }
+    }
+}
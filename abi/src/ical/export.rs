@@ -0,0 +1,134 @@
+use super::{escape_text, fold_line, format_time};
+use crate::{convert_to_utc_time, Reservation, ReservationStatus};
+use chrono::{DateTime, Utc};
+
+const PRODID: &str = "-//rsvp//reservation//EN";
+
+// RFC 5545日历，每个预定对应一个VEVENT，可以被Outlook、Google日历等订阅或导入
+// stamp为生成日历的时间(DTSTAMP)
+#[derive(Debug, Clone)]
+pub struct ICalendar {
+    stamp: DateTime<Utc>,
+    body: String,
+}
+
+impl ICalendar {
+    pub fn new(name: &str, stamp: DateTime<Utc>) -> Self {
+        let mut calendar = Self {
+            stamp,
+            body: String::new(),
+        };
+        calendar.line("BEGIN:VCALENDAR");
+        calendar.line("VERSION:2.0");
+        calendar.line(&format!("PRODID:{}", PRODID));
+        calendar.line("CALSCALE:GREGORIAN");
+        calendar.line("METHOD:PUBLISH");
+        calendar.line(&format!("X-WR-CALNAME:{}", escape_text(name)));
+        calendar
+    }
+
+    // UID由预定ID生成，预定修改后SEQUENCE(版本)增加，日历客户端据此更新已有的事件
+    pub fn push(&mut self, rsvp: &Reservation) -> &mut Self {
+        self.line("BEGIN:VEVENT");
+        self.line(&format!("UID:reservation-{}@rsvp", rsvp.id));
+        self.line(&format!("DTSTAMP:{}", format_time(&self.stamp)));
+        if let Some(start) = &rsvp.start {
+            self.line(&format!(
+                "DTSTART:{}",
+                format_time(&convert_to_utc_time(start))
+            ));
+        }
+        if let Some(end) = &rsvp.end {
+            self.line(&format!("DTEND:{}", format_time(&convert_to_utc_time(end))));
+        }
+        self.line(&format!("SEQUENCE:{}", rsvp.version.max(0)));
+        self.line(&format!("SUMMARY:{}", escape_text(&rsvp.resource_id)));
+        if !rsvp.note.is_empty() {
+            self.line(&format!("DESCRIPTION:{}", escape_text(&rsvp.note)));
+        }
+        if let Some(status) = event_status(rsvp.status) {
+            self.line(&format!("STATUS:{}", status));
+        }
+        self.line("END:VEVENT");
+        self
+    }
+
+    pub fn extend<'a>(&mut self, rsvps: impl IntoIterator<Item = &'a Reservation>) -> &mut Self {
+        for rsvp in rsvps {
+            self.push(rsvp);
+        }
+        self
+    }
+
+    pub fn finish(mut self) -> String {
+        self.line("END:VCALENDAR");
+        self.body
+    }
+
+    fn line(&mut self, line: &str) {
+        fold_line(&mut self.body, line);
+    }
+}
+
+// 待确认的预定显示为暂定，被占用的时段与已确认的预定一样显示为确定
+fn event_status(status: i32) -> Option<&'static str> {
+    match ReservationStatus::try_from(status).ok()? {
+        ReservationStatus::Pending => Some("TENTATIVE"),
+        ReservationStatus::Confirmed | ReservationStatus::Blocked => Some("CONFIRMED"),
+        ReservationStatus::Unknown => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert_to_timestamp;
+
+    fn rsvp(status: ReservationStatus, note: &str) -> Reservation {
+        Reservation {
+            id: 42,
+            user_id: "alice".to_string(),
+            resource_id: "room-101".to_string(),
+            status: status as i32,
+            start: Some(convert_to_timestamp(
+                &"2024-01-05T07:00:00Z".parse().unwrap(),
+            )),
+            end: Some(convert_to_timestamp(
+                &"2024-01-05T08:30:00Z".parse().unwrap(),
+            )),
+            note: note.to_string(),
+            version: 3,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn reservations_should_be_exported_as_vevents() {
+        let stamp = "2024-01-01T00:00:00Z".parse().unwrap();
+        let mut calendar = ICalendar::new("alice", stamp);
+        calendar.extend(&[
+            rsvp(
+                ReservationStatus::Confirmed,
+                "board meeting; bring slides, laptop",
+            ),
+            rsvp(ReservationStatus::Pending, ""),
+        ]);
+        let ics = calendar.finish();
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+        let event = "BEGIN:VEVENT\r\n\
+            UID:reservation-42@rsvp\r\n\
+            DTSTAMP:20240101T000000Z\r\n\
+            DTSTART:20240105T070000Z\r\n\
+            DTEND:20240105T083000Z\r\n\
+            SEQUENCE:3\r\n\
+            SUMMARY:room-101\r\n\
+            DESCRIPTION:board meeting\\; bring slides\\, laptop\r\n\
+            STATUS:CONFIRMED\r\n\
+            END:VEVENT\r\n";
+        assert!(ics.contains(event));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+        assert!(ics.contains("SUMMARY:room-101\r\nSTATUS:TENTATIVE\r\n"));
+    }
+}
@@ -0,0 +1,668 @@
+use super::{unescape_text, unfold_lines};
+use crate::{convert_to_timestamp, Error, Reservation, ReservationStatus};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use rrule::{RRule, RRuleSet, Unvalidated};
+use std::collections::{HashMap, HashSet};
+
+// 重复事件默认最多展开的次数
+const DEFAULT_MAX_OCCURRENCES: u16 = 500;
+
+// Outlook导出的日历使用Windows时区名，常见的时区映射为IANA时区
+const WINDOWS_ZONES: [(&str, Tz); 24] = [
+    ("UTC", Tz::UTC),
+    ("GMT Standard Time", Tz::Europe__London),
+    ("Greenwich Standard Time", Tz::Atlantic__Reykjavik),
+    ("W. Europe Standard Time", Tz::Europe__Berlin),
+    ("Romance Standard Time", Tz::Europe__Paris),
+    ("Central Europe Standard Time", Tz::Europe__Budapest),
+    ("Central European Standard Time", Tz::Europe__Warsaw),
+    ("E. Europe Standard Time", Tz::Europe__Chisinau),
+    ("Russian Standard Time", Tz::Europe__Moscow),
+    ("India Standard Time", Tz::Asia__Kolkata),
+    ("China Standard Time", Tz::Asia__Shanghai),
+    ("Taipei Standard Time", Tz::Asia__Taipei),
+    ("Singapore Standard Time", Tz::Asia__Singapore),
+    ("Tokyo Standard Time", Tz::Asia__Tokyo),
+    ("Korea Standard Time", Tz::Asia__Seoul),
+    ("AUS Eastern Standard Time", Tz::Australia__Sydney),
+    ("Eastern Standard Time", Tz::America__New_York),
+    ("Central Standard Time", Tz::America__Chicago),
+    ("Mountain Standard Time", Tz::America__Denver),
+    ("US Mountain Standard Time", Tz::America__Phoenix),
+    ("Pacific Standard Time", Tz::America__Los_Angeles),
+    ("Alaskan Standard Time", Tz::America__Anchorage),
+    ("Hawaiian Standard Time", Tz::Pacific__Honolulu),
+    ("E. South America Standard Time", Tz::America__Sao_Paulo),
+];
+
+// 导入日历的选项
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ICalImportOptions {
+    // 没有时区的时间(浮动时间)和全天事件按此时区解释
+    pub tz: Tz,
+    // 每个重复事件最多展开的次数，超过时该事件导入失败
+    pub max_occurrences: u16,
+    // 重复事件只展开到此时间之前，没有COUNT或UNTIL的重复事件需要指定
+    pub until: Option<DateTime<Utc>>,
+}
+
+// 日历中的一个事件，重复事件的每次发生对应一个
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ICalEvent {
+    pub uid: String,
+    pub summary: String,
+    pub description: String,
+    pub status: ReservationStatus,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+// 无法导入的事件，例如时区未知或者缺少开始时间
+#[derive(Debug)]
+pub struct ICalEventError {
+    pub uid: String,
+    pub error: Error,
+}
+
+// 内容行，名称和参数名统一为大写
+#[derive(Debug)]
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+// 一个VEVENT中的属性(不包括其中VALARM等子组件的属性)
+#[derive(Debug, Default)]
+struct Component {
+    props: Vec<Property>,
+}
+
+// 事件中的时间，全天事件只有日期
+#[derive(Debug, Clone, Copy)]
+enum Time {
+    Date(NaiveDate),
+    DateTime(DateTime<Tz>),
+}
+
+// 事件的时长，全天事件按天计算，跨越夏令时切换时仍然在午夜结束
+#[derive(Debug, Clone, Copy)]
+enum Length {
+    Days(i64),
+    Exact(Duration),
+}
+
+// 一次发生的开始和结束时间
+type Occurrence = (DateTime<Utc>, DateTime<Utc>);
+
+struct Context<'a> {
+    options: &'a ICalImportOptions,
+    // VTIMEZONE中定义的时区
+    timezones: HashMap<String, Tz>,
+}
+
+impl Default for ICalImportOptions {
+    fn default() -> Self {
+        Self {
+            tz: Tz::UTC,
+            max_occurrences: DEFAULT_MAX_OCCURRENCES,
+            until: None,
+        }
+    }
+}
+
+impl ICalEvent {
+    // 转换为指定用户和资源的预定，备注为事件的标题和描述
+    pub fn to_reservation(&self, user_id: &str, resource_id: &str) -> Reservation {
+        let note = [self.summary.as_str(), self.description.as_str()]
+            .into_iter()
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        Reservation {
+            user_id: user_id.to_string(),
+            resource_id: resource_id.to_string(),
+            status: self.status as i32,
+            start: Some(convert_to_timestamp(&self.start)),
+            end: Some(convert_to_timestamp(&self.end)),
+            note,
+            ..Default::default()
+        }
+    }
+}
+
+// 解析日历中的VEVENT，重复事件(RRULE、RDATE、EXDATE)按事件所在的时区展开，
+// 修改过的某一次重复(RECURRENCE-ID)替换原来的那一次，已取消的事件会被忽略
+// 单个事件无法导入时只影响该事件，日历本身格式错误时返回错误
+pub fn parse_ical(
+    input: &str,
+    options: &ICalImportOptions,
+) -> Result<Vec<Result<ICalEvent, ICalEventError>>, Error> {
+    let (events, timezones) = parse_components(input)?;
+    let ctx = Context { options, timezones };
+
+    let mut overridden: HashMap<&str, HashSet<DateTime<Utc>>> = HashMap::new();
+    for event in &events {
+        if let Some(prop) = event.get("RECURRENCE-ID") {
+            if let Ok(time) = ctx.time(prop, &prop.value).and_then(|t| ctx.datetime(t)) {
+                overridden
+                    .entry(event.value("UID"))
+                    .or_default()
+                    .insert(time.with_timezone(&Utc));
+            }
+        }
+    }
+
+    let mut results = Vec::new();
+    for (i, event) in events.iter().enumerate() {
+        let uid = match event.value("UID") {
+            "" => format!("#{}", i + 1),
+            uid => uid.to_string(),
+        };
+        let status = match event.value("STATUS").to_ascii_uppercase().as_str() {
+            "CANCELLED" => continue,
+            "CONFIRMED" => ReservationStatus::Confirmed,
+            _ => ReservationStatus::Pending,
+        };
+        let skip = match event.get("RECURRENCE-ID") {
+            Some(_) => None,
+            None => overridden.get(event.value("UID")),
+        };
+        match ctx.expand(event, skip) {
+            Ok(spans) => results.extend(spans.into_iter().map(|(start, end)| {
+                Ok(ICalEvent {
+                    uid: uid.clone(),
+                    summary: unescape_text(event.value("SUMMARY")),
+                    description: unescape_text(event.value("DESCRIPTION")),
+                    status,
+                    start,
+                    end,
+                })
+            })),
+            Err(error) => results.push(Err(ICalEventError { uid, error })),
+        }
+    }
+    Ok(results)
+}
+
+// 取出日历中的VEVENT，以及VTIMEZONE中TZID对应的IANA时区(X-LIC-LOCATION)
+fn parse_components(input: &str) -> Result<(Vec<Component>, HashMap<String, Tz>), Error> {
+    let mut events = Vec::new();
+    let mut timezones = HashMap::new();
+    let mut stack: Vec<String> = Vec::new();
+    let mut event = Component::default();
+    let (mut tzid, mut location) = (None, None);
+    let mut calendar = false;
+
+    for line in unfold_lines(input) {
+        let prop =
+            parse_property(&line).ok_or_else(|| invalid(format!("无法解析的内容行: {}", line)))?;
+        let parent = stack.last().map(String::as_str);
+        match (prop.name.as_str(), parent) {
+            ("BEGIN", _) => {
+                let name = prop.value.to_ascii_uppercase();
+                calendar |= name == "VCALENDAR";
+                stack.push(name);
+            }
+            ("END", _) => {
+                let name = prop.value.to_ascii_uppercase();
+                if stack.pop().as_deref() != Some(name.as_str()) {
+                    return Err(invalid(format!("END:{}与BEGIN不匹配", name)));
+                }
+                match name.as_str() {
+                    "VEVENT" => events.push(std::mem::take(&mut event)),
+                    "VTIMEZONE" => {
+                        if let (Some(id), Some(tz)) = (tzid.take(), location.take()) {
+                            timezones.insert(id, tz);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            (_, Some("VEVENT")) => event.props.push(prop),
+            ("TZID", Some("VTIMEZONE")) => tzid = Some(prop.value),
+            ("X-LIC-LOCATION", Some("VTIMEZONE")) => location = prop.value.parse::<Tz>().ok(),
+            _ => {}
+        }
+    }
+    if !calendar {
+        return Err(invalid("缺少BEGIN:VCALENDAR"));
+    }
+    if let Some(name) = stack.pop() {
+        return Err(invalid(format!("缺少END:{}", name)));
+    }
+    Ok((events, timezones))
+}
+
+// 内容行的格式为 名称;参数=值:值，参数值可以用双引号括起来，其中的冒号和分号不是分隔符
+fn parse_property(line: &str) -> Option<Property> {
+    let parts = split_unquoted(line, ':', Some(1));
+    let (head, value) = match parts.as_slice() {
+        [head, value] => (*head, *value),
+        _ => return None,
+    };
+    let mut head = split_unquoted(head, ';', None).into_iter();
+    let name = head.next()?.trim().to_ascii_uppercase();
+    if name.is_empty() {
+        return None;
+    }
+    let params = head
+        .map(|param| {
+            let (key, value) = param.split_once('=')?;
+            Some((
+                key.trim().to_ascii_uppercase(),
+                value.trim_matches('"').to_string(),
+            ))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(Property {
+        name,
+        params,
+        value: value.to_string(),
+    })
+}
+
+// 按不在引号中的分隔符拆分，最多拆分limit次
+fn split_unquoted(s: &str, sep: char, limit: Option<usize>) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut quoted, mut start) = (false, 0);
+    for (i, c) in s.char_indices() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if c == sep && !quoted && limit.is_none_or(|n| parts.len() < n) {
+            parts.push(&s[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl Component {
+    fn get(&self, name: &str) -> Option<&Property> {
+        self.props.iter().find(|p| p.name == name)
+    }
+
+    fn value(&self, name: &str) -> &str {
+        self.get(name).map_or("", |p| p.value.as_str())
+    }
+}
+
+impl Context<'_> {
+    // 展开事件，返回每次发生的开始和结束时间，skip中的开始时间已被单独修改过
+    fn expand(
+        &self,
+        event: &Component,
+        skip: Option<&HashSet<DateTime<Utc>>>,
+    ) -> Result<Vec<Occurrence>, Error> {
+        let dtstart = event.get("DTSTART").ok_or_else(|| invalid("缺少DTSTART"))?;
+        let start = self.time(dtstart, &dtstart.value)?;
+        let length = match (event.get("DTEND"), event.get("DURATION")) {
+            (Some(end), _) => length_between(start, self.time(end, &end.value)?)?,
+            (None, Some(duration)) => parse_duration(&duration.value, start)?,
+            (None, None) if matches!(start, Time::Date(_)) => Length::Days(1),
+            (None, None) => return Err(invalid("缺少DTEND或DURATION")),
+        };
+        let start = self.datetime(start)?;
+
+        let starts = if event.get("RRULE").is_some() || event.get("RDATE").is_some() {
+            self.recurrences(event, start)?
+        } else {
+            vec![start]
+        };
+        starts
+            .into_iter()
+            .filter(|s| skip.is_none_or(|skip| !skip.contains(&s.with_timezone(&Utc))))
+            .map(|s| {
+                Ok((
+                    s.with_timezone(&Utc),
+                    self.end(s, length)?.with_timezone(&Utc),
+                ))
+            })
+            .collect()
+    }
+
+    // 在开始时间所在的时区中展开重复规则，夏令时切换前后保持相同的本地时间
+    fn recurrences(
+        &self,
+        event: &Component,
+        start: DateTime<Tz>,
+    ) -> Result<Vec<DateTime<Tz>>, Error> {
+        let tz = rrule::Tz::Tz(start.timezone());
+        let dt_start = start.with_timezone(&tz);
+        let mut set = match event.get("RRULE") {
+            Some(rule) => rule
+                .value
+                .parse::<RRule<Unvalidated>>()
+                .and_then(|rule| rule.build(dt_start))
+                .map_err(|e| invalid(format!("无效的RRULE: {}", e)))?,
+            None => RRuleSet::new(dt_start).rdate(dt_start),
+        };
+        for prop in event.props.iter() {
+            let exclude = match prop.name.as_str() {
+                "RDATE" => false,
+                "EXDATE" => true,
+                _ => continue,
+            };
+            for value in prop.value.split(',') {
+                let time = self.datetime(self.time(prop, value)?)?.with_timezone(&tz);
+                set = if exclude {
+                    set.exdate(time)
+                } else {
+                    set.rdate(time)
+                };
+            }
+        }
+        // before包含截止时间本身，展开后再去掉从截止时间开始的那一次
+        let until = self.options.until;
+        if let Some(until) = until {
+            set = set.before(until.with_timezone(&tz));
+        }
+
+        let max = self.options.max_occurrences;
+        let result = set.all(max);
+        if result.limited {
+            return Err(invalid(format!("重复事件超过{}次，需要指定截止时间", max)));
+        }
+        Ok(result
+            .dates
+            .into_iter()
+            .filter(|d| until.is_none_or(|until| *d < until))
+            .map(|d| d.with_timezone(&start.timezone()))
+            .collect())
+    }
+
+    // 解析属性中的时间，带TZID参数时使用对应的时区，UTC时间以Z结尾，其它为浮动时间
+    fn time(&self, prop: &Property, value: &str) -> Result<Time, Error> {
+        let value = value.trim();
+        let date = prop
+            .param("VALUE")
+            .is_some_and(|v| v.eq_ignore_ascii_case("DATE"));
+        if date || value.len() == 8 {
+            return NaiveDate::parse_from_str(value, "%Y%m%d")
+                .map(Time::Date)
+                .map_err(|_| invalid(format!("无效的日期{}", value)));
+        }
+        let (local, utc) = match value.strip_suffix('Z') {
+            Some(local) => (local, true),
+            None => (value, false),
+        };
+        let naive = NaiveDateTime::parse_from_str(local, "%Y%m%dT%H%M%S")
+            .map_err(|_| invalid(format!("无效的时间{}", value)))?;
+        let tz = match (utc, prop.param("TZID")) {
+            (true, _) => Tz::UTC,
+            (false, Some(tzid)) => self.resolve_tz(tzid)?,
+            (false, None) => self.options.tz,
+        };
+        to_local(tz, naive).map(Time::DateTime)
+    }
+
+    // 全天事件从默认时区的午夜开始
+    fn datetime(&self, time: Time) -> Result<DateTime<Tz>, Error> {
+        match time {
+            Time::Date(date) => to_local(self.options.tz, date.and_time(NaiveTime::MIN)),
+            Time::DateTime(dt) => Ok(dt),
+        }
+    }
+
+    fn end(&self, start: DateTime<Tz>, length: Length) -> Result<DateTime<Tz>, Error> {
+        match length {
+            Length::Days(days) => {
+                let date = start.date_naive() + Duration::days(days);
+                to_local(start.timezone(), date.and_time(start.time()))
+            }
+            Length::Exact(duration) => Ok(start + duration),
+        }
+    }
+
+    // 依次尝试IANA时区名、VTIMEZONE中的X-LIC-LOCATION和Windows时区名
+    fn resolve_tz(&self, tzid: &str) -> Result<Tz, Error> {
+        tzid.trim_start_matches('/')
+            .parse::<Tz>()
+            .ok()
+            .or_else(|| self.timezones.get(tzid).copied())
+            .or_else(|| {
+                WINDOWS_ZONES
+                    .iter()
+                    .find(|(name, _)| *name == tzid)
+                    .map(|(_, tz)| *tz)
+            })
+            .ok_or_else(|| invalid(format!("未知的时区{}", tzid)))
+    }
+}
+
+// 夏令时切换时重复的时间取较早的一个，被跳过的时间不存在
+fn to_local(tz: Tz, naive: NaiveDateTime) -> Result<DateTime<Tz>, Error> {
+    tz.from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| invalid(format!("时间{}在时区{}中不存在", naive, tz)))
+}
+
+fn length_between(start: Time, end: Time) -> Result<Length, Error> {
+    let length = match (start, end) {
+        (Time::Date(start), Time::Date(end)) => Length::Days((end - start).num_days()),
+        (Time::DateTime(start), Time::DateTime(end)) => Length::Exact(end - start),
+        _ => return Err(invalid("DTSTART和DTEND的类型不一致")),
+    };
+    Ok(length)
+}
+
+// 时长的格式为 P1W、P1D、PT1H30M、P1DT12H，全天事件只能使用整天
+fn parse_duration(value: &str, start: Time) -> Result<Length, Error> {
+    let error = || invalid(format!("无效的DURATION{}", value));
+    let rest = value.trim().strip_prefix('+').unwrap_or(value.trim());
+    let rest = rest.strip_prefix('P').ok_or_else(error)?;
+    let (mut days, mut seconds, mut number) = (0i64, 0i64, String::new());
+    let mut time = false;
+    for c in rest.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        if c == 'T' && number.is_empty() {
+            time = true;
+            continue;
+        }
+        let n: i64 = std::mem::take(&mut number).parse().map_err(|_| error())?;
+        match (c, time) {
+            ('W', false) => days += n * 7,
+            ('D', false) => days += n,
+            ('H', true) => seconds += n * 3600,
+            ('M', true) => seconds += n * 60,
+            ('S', true) => seconds += n,
+            _ => return Err(error()),
+        }
+    }
+    if !number.is_empty() {
+        return Err(error());
+    }
+    match start {
+        Time::Date(_) if seconds != 0 => Err(error()),
+        Time::Date(_) => Ok(Length::Days(days)),
+        Time::DateTime(_) => Ok(Length::Exact(
+            Duration::days(days) + Duration::seconds(seconds),
+        )),
+    }
+}
+
+fn invalid(msg: impl Into<String>) -> Error {
+    Error::InvalidCalendar(msg.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calendar(body: &str) -> String {
+        format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\n{}END:VCALENDAR\r\n",
+            body
+        )
+    }
+
+    fn parse(body: &str, options: &ICalImportOptions) -> Vec<Result<ICalEvent, ICalEventError>> {
+        parse_ical(&calendar(body), options).unwrap()
+    }
+
+    fn spans(results: &[Result<ICalEvent, ICalEventError>]) -> Vec<String> {
+        results
+            .iter()
+            .map(|r| {
+                let event = r.as_ref().unwrap();
+                format!("{} {}", event.start.to_rfc3339(), event.end.to_rfc3339())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn single_events_should_be_parsed_with_time_zones() {
+        let body = "BEGIN:VEVENT\r\n\
+            UID:a@example.com\r\n\
+            DTSTART;TZID=Asia/Shanghai:20240105T150000\r\n\
+            DTEND;TZID=Asia/Shanghai:20240105T163000\r\n\
+            SUMMARY:Board meeting\\, Q1\r\n\
+            DESCRIPTION:bring slides\\nand laptop\r\n\
+            STATUS:CONFIRMED\r\n\
+            BEGIN:VALARM\r\n\
+            DESCRIPTION:reminder\r\n\
+            END:VALARM\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:b@example.com\r\n\
+            DTSTART:20240106T090000\r\n\
+            DURATION:PT45M\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:c@example.com\r\n\
+            DTSTART;VALUE=DATE:20240107\r\n\
+            DTEND;VALUE=DATE:20240109\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:d@example.com\r\n\
+            DTSTART:20240108T090000Z\r\n\
+            DTEND:20240108T100000Z\r\n\
+            STATUS:CANCELLED\r\n\
+            END:VEVENT\r\n";
+        let options = ICalImportOptions {
+            tz: Tz::Europe__London,
+            ..Default::default()
+        };
+        let results = parse(body, &options);
+        assert_eq!(
+            spans(&results),
+            [
+                "2024-01-05T07:00:00+00:00 2024-01-05T08:30:00+00:00",
+                "2024-01-06T09:00:00+00:00 2024-01-06T09:45:00+00:00",
+                "2024-01-07T00:00:00+00:00 2024-01-09T00:00:00+00:00",
+            ]
+        );
+
+        let event = results[0].as_ref().unwrap();
+        assert_eq!(event.uid, "a@example.com");
+        assert_eq!(event.status, ReservationStatus::Confirmed);
+        let rsvp = event.to_reservation("alice", "room-101");
+        assert_eq!(rsvp.note, "Board meeting, Q1\nbring slides\nand laptop");
+        assert_eq!(rsvp.status, ReservationStatus::Confirmed as i32);
+        assert_eq!(
+            results[1].as_ref().unwrap().status,
+            ReservationStatus::Pending
+        );
+    }
+
+    #[test]
+    fn recurring_events_should_keep_local_time_across_dst() {
+        // 2024-03-10 美国开始夏令时，每周二上午9点的会议在UTC中提前一小时
+        let body = "BEGIN:VTIMEZONE\r\n\
+            TZID:Custom Eastern\r\n\
+            X-LIC-LOCATION:America/New_York\r\n\
+            END:VTIMEZONE\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:weekly\r\n\
+            DTSTART;TZID=Custom Eastern:20240305T090000\r\n\
+            DTEND;TZID=Custom Eastern:20240305T100000\r\n\
+            RRULE:FREQ=WEEKLY;BYDAY=TU;COUNT=4\r\n\
+            EXDATE;TZID=Custom Eastern:20240319T090000\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:weekly\r\n\
+            RECURRENCE-ID;TZID=Custom Eastern:20240326T090000\r\n\
+            DTSTART;TZID=\"Eastern Standard Time\":20240326T140000\r\n\
+            DTEND;TZID=\"Eastern Standard Time\":20240326T150000\r\n\
+            END:VEVENT\r\n";
+        let results = parse(body, &ICalImportOptions::default());
+        assert_eq!(
+            spans(&results),
+            [
+                "2024-03-05T14:00:00+00:00 2024-03-05T15:00:00+00:00",
+                "2024-03-12T13:00:00+00:00 2024-03-12T14:00:00+00:00",
+                "2024-03-26T18:00:00+00:00 2024-03-26T19:00:00+00:00",
+            ]
+        );
+    }
+
+    #[test]
+    fn unbounded_recurrence_should_need_until() {
+        let body = "BEGIN:VEVENT\r\n\
+            UID:daily\r\n\
+            DTSTART;VALUE=DATE:20240101\r\n\
+            RRULE:FREQ=DAILY\r\n\
+            END:VEVENT\r\n";
+        let results = parse(body, &ICalImportOptions::default());
+        let err = results[0].as_ref().unwrap_err();
+        assert_eq!(err.uid, "daily");
+        assert!(err.error.to_string().contains("截止时间"));
+
+        let options = ICalImportOptions {
+            until: Some("2024-01-04T00:00:00Z".parse().unwrap()),
+            ..Default::default()
+        };
+        let results = parse(body, &options);
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            spans(&results)[2],
+            "2024-01-03T00:00:00+00:00 2024-01-04T00:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn invalid_events_should_be_reported_individually() {
+        let body = "BEGIN:VEVENT\r\n\
+            UID:unknown-tz\r\n\
+            DTSTART;TZID=Mars/Olympus:20240105T150000\r\n\
+            DTEND;TZID=Mars/Olympus:20240105T160000\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART:20240105T150000Z\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:ok\r\n\
+            DTSTART:20240105T150000Z\r\n\
+            DURATION:P1DT2H\r\n\
+            END:VEVENT\r\n";
+        let results = parse(body, &ICalImportOptions::default());
+        let err = results[0].as_ref().unwrap_err();
+        assert!(err.error.to_string().contains("Mars/Olympus"));
+        let err = results[1].as_ref().unwrap_err();
+        assert_eq!(err.uid, "#2");
+        assert!(err.error.to_string().contains("DTEND"));
+        assert_eq!(
+            spans(&results[2..]),
+            ["2024-01-05T15:00:00+00:00 2024-01-06T17:00:00+00:00"]
+        );
+
+        let options = ICalImportOptions::default();
+        assert!(parse_ical("BEGIN:VEVENT\r\nEND:VEVENT\r\n", &options).is_err());
+        assert!(parse_ical(&calendar("BEGIN:VEVENT\r\n"), &options).is_err());
+        assert!(parse_ical(&calendar("not a content line\r\n"), &options).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+mod export;
+mod import;
+
+pub use export::*;
+pub use import::*;
+
+use chrono::{DateTime, Utc};
+
+// 一行最多75个字节(不含换行)，超出时折行，续行以空格开头
+const MAX_LINE_OCTETS: usize = 75;
+
+// 时间统一使用UTC格式，例如 20240105T150000Z
+fn format_time(time: &DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+// TEXT类型的值需要转义反斜杠、分号、逗号和换行
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// 按字节折行，不拆开多字节的UTF-8字符
+fn fold_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > MAX_LINE_OCTETS {
+            out.push_str("\r\n ");
+            // 续行开头的空格也计入长度
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+// 将折行的内容行合并为一行，兼容只使用\n换行的文件
+fn unfold_lines(input: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in input.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ if line.is_empty() => {}
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+// escape_text的逆操作
+fn unescape_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => unescaped.push('\n'),
+                Some(c) => unescaped.push(c),
+                None => unescaped.push('\\'),
+            },
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_lines_should_be_folded_on_char_boundaries() {
+        let note = format!("{}\n{}", "a".repeat(80), "预定".repeat(30));
+        let mut out = String::new();
+        fold_line(&mut out, &format!("DESCRIPTION:{}", escape_text(&note)));
+
+        for line in out.split("\r\n").filter(|l| !l.is_empty()) {
+            assert!(line.len() <= MAX_LINE_OCTETS, "{} is too long", line);
+        }
+        let unfolded = out.replace("\r\n ", "");
+        assert_eq!(
+            unfolded,
+            format!("DESCRIPTION:{}\\n{}\r\n", "a".repeat(80), "预定".repeat(30))
+        );
+    }
+
+    #[test]
+    fn text_should_round_trip_through_escape_and_unfold() {
+        let text = "line 1, part; a\\b\nline 2 预定";
+        let mut out = String::new();
+        fold_line(
+            &mut out,
+            &format!("DESCRIPTION:{}{}", "x".repeat(70), escape_text(text)),
+        );
+        let lines = unfold_lines(&out.replace("\r\n", "\n"));
+        assert_eq!(lines.len(), 1);
+        let value = lines[0].strip_prefix("DESCRIPTION:").unwrap();
+        assert_eq!(unescape_text(value), format!("{}{}", "x".repeat(70), text));
+    }
+}
@@ -1,11 +1,13 @@
 mod config;
 mod error;
+mod ical;
 mod pb;
 mod types;
 mod utils;
 
 pub use config::*;
 pub use error::*;
+pub use ical::*;
 pub use pb::*;
 pub use types::*;
 pub use utils::*;
@@ -29,7 +31,8 @@ pub trait Normalizer: Validate {
     }
 }
 
-// 定义Tosql特征
+// 定义Tosql特征，生成的SQL总是按租户过滤，由存储将租户ID绑定到$1
+// 查询和过滤的SQL中用户ID和资源ID不拼接到SQL中，由存储依次绑定到$2和$3
 pub trait ToSql {
     fn to_sql(&self) -> String;
 }
@@ -0,0 +1,107 @@
+// 生成的消息中字段的json表示，通过 #[serde(with = "...")] 在build.rs中指定
+
+// 时间使用RFC 3339字符串(UTC)，解析时接受任意时区
+pub mod timestamp {
+    use crate::{convert_to_timestamp, convert_to_utc_time, validate_timestamp};
+    use chrono::{DateTime, SecondsFormat, Utc};
+    use prost_types::Timestamp;
+    use serde::{ser::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        ts: &Option<Timestamp>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        // 超出范围的时间戳无法表示为rfc3339，返回错误而不是panic
+        ts.as_ref()
+            .map(|ts| {
+                validate_timestamp(ts).map_err(S::Error::custom)?;
+                Ok(convert_to_utc_time(ts).to_rfc3339_opts(SecondsFormat::AutoSi, true))
+            })
+            .transpose()?
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Timestamp>, D::Error> {
+        let dt = Option::<DateTime<Utc>>::deserialize(deserializer)?;
+        Ok(dt.map(|dt| convert_to_timestamp(&dt)))
+    }
+}
+
+// 枚举字段在消息中为i32，json中使用枚举的小写名字，无法识别的值视为unknown
+macro_rules! enumeration {
+    ($name:ident, $ty:ident) => {
+        pub mod $name {
+            use crate::$ty;
+            use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+            pub fn serialize<S: Serializer>(value: &i32, serializer: S) -> Result<S::Ok, S::Error> {
+                $ty::try_from(*value)
+                    .unwrap_or($ty::Unknown)
+                    .serialize(serializer)
+            }
+
+            pub fn deserialize<'de, D: Deserializer<'de>>(
+                deserializer: D,
+            ) -> Result<i32, D::Error> {
+                $ty::deserialize(deserializer).map(|value| value as i32)
+            }
+        }
+    };
+}
+
+enumeration!(status, ReservationStatus);
+enumeration!(update_type, ReservationUpdateType);
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        convert_to_timestamp, ListenResponse, Reservation, ReservationStatus, ReservationUpdateType,
+    };
+    use serde_json::json;
+
+    #[test]
+    fn reservation_should_use_rfc3339_and_status_name() {
+        let rsvp = Reservation {
+            id: 1,
+            user_id: "alice".to_string(),
+            resource_id: "room-101".to_string(),
+            status: ReservationStatus::Confirmed as i32,
+            start: Some(convert_to_timestamp(
+                &"2024-01-05T15:00:00+08:00".parse().unwrap(),
+            )),
+            ..Default::default()
+        };
+        let value = serde_json::to_value(&rsvp).unwrap();
+        assert_eq!(value["status"], "confirmed");
+        assert_eq!(value["start"], "2024-01-05T07:00:00Z");
+        assert_eq!(value["end"], json!(null));
+
+        let event = ListenResponse {
+            op: ReservationUpdateType::Delete as i32,
+            reservation: Some(rsvp.clone()),
+            changed_fields: vec![],
+        };
+        assert_eq!(serde_json::to_value(&event).unwrap()["op"], "delete");
+
+        let parsed: Reservation = serde_json::from_value(value).unwrap();
+        assert_eq!(parsed, rsvp);
+    }
+
+    #[test]
+    fn missing_fields_should_use_default() {
+        let rsvp: Reservation = serde_json::from_value(json!({
+            "user_id": "alice",
+            "start": "2024-01-05T15:00:00+08:00",
+            "status": "pending",
+        }))
+        .unwrap();
+        assert_eq!(rsvp.status, ReservationStatus::Pending as i32);
+        assert_eq!(rsvp.start.unwrap().seconds, 1704438000);
+        assert!(rsvp.resource_id.is_empty() && rsvp.end.is_none());
+
+        let err = serde_json::from_value::<Reservation>(json!({ "status": "done" })).unwrap_err();
+        assert!(err.to_string().contains("unknown variant"));
+    }
+}
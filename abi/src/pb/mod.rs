@@ -1,4 +1,34 @@
+mod json;
 #[allow(clippy::all, non_camel_case_types)]
 mod reservation;
 
 pub use reservation::*;
+
+// 编码后的proto描述符集合，用于grpc反射
+pub const FILE_DESCRIPTOR_SET: &[u8] = include_bytes!("reservation_descriptor.bin");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+
+    // 旧版本的UpdateRequest，id为字符串
+    #[derive(Clone, PartialEq, Message)]
+    struct LegacyUpdateRequest {
+        #[prost(string, tag = "1")]
+        id: String,
+        #[prost(string, tag = "2")]
+        note: String,
+    }
+
+    #[test]
+    fn update_request_should_ignore_legacy_string_id() {
+        let legacy = LegacyUpdateRequest {
+            id: "42".into(),
+            note: "hello".into(),
+        };
+        let req = UpdateRequest::decode(legacy.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(req.id, 0);
+        assert_eq!(req.note, "hello");
+    }
+}
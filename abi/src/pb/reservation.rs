@@ -1,5 +1,7 @@
 /// Core reservation object. Contains all the information for a reservation
 /// if ListenResponse op is DELETE, only id will be populated
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Reservation {
@@ -11,28 +13,50 @@ pub struct Reservation {
     pub user_id: ::prost::alloc::string::String,
     /// reservation status, used for differentating purpose
     #[prost(enumeration = "ReservationStatus", tag = "3")]
+    #[serde(with = "crate::pb::json::status")]
     pub status: i32,
     /// resource id for the reservation
     #[prost(string, tag = "4")]
     pub resource_id: ::prost::alloc::string::String,
     /// start time for the reservation
     #[prost(message, optional, tag = "5")]
+    #[serde(with = "crate::pb::json::timestamp")]
     pub start: ::core::option::Option<::prost_types::Timestamp>,
     /// end time for the reservation
     #[prost(message, optional, tag = "6")]
+    #[serde(with = "crate::pb::json::timestamp")]
     pub end: ::core::option::Option<::prost_types::Timestamp>,
     /// extra note
     #[prost(string, tag = "7")]
     pub note: ::prost::alloc::string::String,
+    /// version of the reservation, increased by one on every change
+    #[prost(int64, tag = "8")]
+    pub version: i64,
+    /// opaque and time-sortable public id (uuid v7), clients should prefer it over id
+    #[prost(string, tag = "9")]
+    pub public_id: ::prost::alloc::string::String,
+    /// tenant that owns the reservation, always set by the server
+    #[prost(string, tag = "10")]
+    pub tenant_id: ::prost::alloc::string::String,
 }
 /// To make a reservatino, send a ReservationRequest with Resvation object (id should be empty)
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ReserveRequest {
     #[prost(message, optional, tag = "1")]
     pub reservation: ::core::option::Option<Reservation>,
+    /// optional idempotency key, a retry with the same key and payload returns the original response
+    #[prost(string, tag = "2")]
+    pub idempotency_key: ::prost::alloc::string::String,
+    /// optional reason for the change, persisted in the audit trail
+    #[prost(string, tag = "3")]
+    pub reason: ::prost::alloc::string::String,
 }
 /// Created reservation will be returned in ReserveResponse
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ReserveResponse {
@@ -40,15 +64,32 @@ pub struct ReserveResponse {
     pub reservation: ::core::option::Option<Reservation>,
 }
 /// To update a reservation, send an UpdateRequest. Only note is updatable.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct UpdateRequest {
-    #[prost(string, tag = "1")]
-    pub id: ::prost::alloc::string::String,
     #[prost(string, tag = "2")]
     pub note: ::prost::alloc::string::String,
+    /// optional idempotency key, a retry with the same key and payload returns the original response
+    #[prost(string, tag = "3")]
+    pub idempotency_key: ::prost::alloc::string::String,
+    /// if not 0, the update only succeeds when the reservation is still at this version
+    #[prost(int64, tag = "4")]
+    pub expected_version: i64,
+    /// optional reason for the change, persisted in the audit trail
+    #[prost(string, tag = "5")]
+    pub reason: ::prost::alloc::string::String,
+    /// public id of the reservation, if not empty it is used instead of id
+    #[prost(string, tag = "6")]
+    pub public_id: ::prost::alloc::string::String,
+    /// id of the reservation to update
+    #[prost(int64, tag = "7")]
+    pub id: i64,
 }
 /// Updated reservation will be returned in UpdateResponse
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct UpdateResponse {
@@ -56,13 +97,29 @@ pub struct UpdateResponse {
     pub reservation: ::core::option::Option<Reservation>,
 }
 /// To change a reservation from pending to confirmed, send a ConfirmRequest
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ConfirmRequest {
     #[prost(int64, tag = "1")]
     pub id: i64,
+    /// optional idempotency key, a retry with the same key and payload returns the original response
+    #[prost(string, tag = "2")]
+    pub idempotency_key: ::prost::alloc::string::String,
+    /// if not 0, the change only succeeds when the reservation is still at this version
+    #[prost(int64, tag = "3")]
+    pub expected_version: i64,
+    /// optional reason for the change, persisted in the audit trail
+    #[prost(string, tag = "4")]
+    pub reason: ::prost::alloc::string::String,
+    /// public id of the reservation, if not empty it is used instead of id
+    #[prost(string, tag = "5")]
+    pub public_id: ::prost::alloc::string::String,
 }
 /// Confirmed reservation will be returned in ConfirmResponse
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ConfirmResponse {
@@ -70,13 +127,29 @@ pub struct ConfirmResponse {
     pub reservation: ::core::option::Option<Reservation>,
 }
 /// To cancel a reservation, send a CancelRequest
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CancelRequest {
     #[prost(int64, tag = "1")]
     pub id: i64,
+    /// optional idempotency key, a retry with the same key and payload returns the original response
+    #[prost(string, tag = "2")]
+    pub idempotency_key: ::prost::alloc::string::String,
+    /// if not 0, the change only succeeds when the reservation is still at this version
+    #[prost(int64, tag = "3")]
+    pub expected_version: i64,
+    /// optional reason for the change, persisted in the audit trail
+    #[prost(string, tag = "4")]
+    pub reason: ::prost::alloc::string::String,
+    /// public id of the reservation, if not empty it is used instead of id
+    #[prost(string, tag = "5")]
+    pub public_id: ::prost::alloc::string::String,
 }
 /// Canceled reservation will be returned in CancelResponse
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CancelResponse {
@@ -84,13 +157,20 @@ pub struct CancelResponse {
     pub reservation: ::core::option::Option<Reservation>,
 }
 /// To get a reservation, send a GetRequest
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct GetRequest {
     #[prost(int64, tag = "1")]
     pub id: i64,
+    /// public id of the reservation, if not empty it is used instead of id
+    #[prost(string, tag = "2")]
+    pub public_id: ::prost::alloc::string::String,
 }
 /// Reservation will be returned in GetResponse
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct GetResponse {
@@ -98,6 +178,8 @@ pub struct GetResponse {
     pub reservation: ::core::option::Option<Reservation>,
 }
 /// query reservations with user id, resource id, start time, end time, and status
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ReservationQuery {
@@ -109,18 +191,23 @@ pub struct ReservationQuery {
     pub user_id: ::prost::alloc::string::String,
     /// use status to filter result. If UNKNOWN, return all reservations
     #[prost(enumeration = "ReservationStatus", tag = "3")]
+    #[serde(with = "crate::pb::json::status")]
     pub status: i32,
     /// start time for the reservation query, if 0, use Infinity for start time
     #[prost(message, optional, tag = "4")]
+    #[serde(with = "crate::pb::json::timestamp")]
     pub start: ::core::option::Option<::prost_types::Timestamp>,
     /// end time for the reservation query, if 0, use Infinity for end time
     #[prost(message, optional, tag = "5")]
+    #[serde(with = "crate::pb::json::timestamp")]
     pub end: ::core::option::Option<::prost_types::Timestamp>,
     /// sort direction
     #[prost(bool, tag = "6")]
     pub desc: bool,
 }
 /// To query reservations, send a QueryRequest
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct QueryRequest {
@@ -128,6 +215,8 @@ pub struct QueryRequest {
     pub query: ::core::option::Option<ReservationQuery>,
 }
 /// query reservations, order by reservation id
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ReservationFilter {
@@ -139,7 +228,9 @@ pub struct ReservationFilter {
     pub user_id: ::prost::alloc::string::String,
     /// use status to filter result. If UNKNOWN, return all reservations
     #[prost(enumeration = "ReservationStatus", tag = "3")]
+    #[serde(with = "crate::pb::json::status")]
     pub status: i32,
+    /// only return reservations after this id (before it if desc), 0 for the first page
     #[prost(int64, tag = "4")]
     pub cursor: i64,
     /// page size for the query
@@ -148,8 +239,18 @@ pub struct ReservationFilter {
     /// sort direction
     #[prost(bool, tag = "6")]
     pub desc: bool,
+    /// only return reservations starting at or after this time, if not set, no lower bound
+    #[prost(message, optional, tag = "7")]
+    #[serde(with = "crate::pb::json::timestamp")]
+    pub start: ::core::option::Option<::prost_types::Timestamp>,
+    /// only return reservations ending at or before this time, if not set, no upper bound
+    #[prost(message, optional, tag = "8")]
+    #[serde(with = "crate::pb::json::timestamp")]
+    pub end: ::core::option::Option<::prost_types::Timestamp>,
 }
 /// To query reservations, send a QueryRequest
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct FilterRequest {
@@ -157,16 +258,23 @@ pub struct FilterRequest {
     pub filter: ::core::option::Option<ReservationFilter>,
 }
 /// filter pager info
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct FilterPager {
+    /// if not 0, use it as cursor with reversed sort direction to get the previous page
     #[prost(int64, tag = "1")]
     pub prev: i64,
+    /// if not 0, use it as cursor to get the next page
     #[prost(int64, tag = "2")]
     pub next: i64,
+    /// total number of reservations matching the filter
     #[prost(int64, tag = "3")]
     pub total: i64,
 }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct FilterResponse {
@@ -175,22 +283,97 @@ pub struct FilterResponse {
     #[prost(message, optional, tag = "2")]
     pub pager: ::core::option::Option<FilterPager>,
 }
+/// To get the change history of a reservation, send a HistoryRequest
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HistoryRequest {
+    #[prost(int64, tag = "1")]
+    pub id: i64,
+    /// public id of the reservation, if not empty it is used instead of id
+    #[prost(string, tag = "2")]
+    pub public_id: ::prost::alloc::string::String,
+}
+/// a single changed field, values are json encoded
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FieldChange {
+    #[prost(string, tag = "1")]
+    pub field: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub old: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub new: ::prost::alloc::string::String,
+}
+/// a recorded change of a reservation
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReservationChange {
+    /// id of the change, increasing in the order changes happened
+    #[prost(int64, tag = "1")]
+    pub id: i64,
+    #[prost(int64, tag = "2")]
+    pub reservation_id: i64,
+    #[prost(enumeration = "ReservationUpdateType", tag = "3")]
+    #[serde(with = "crate::pb::json::update_type")]
+    pub op: i32,
+    /// user or service principal who made the change
+    #[prost(string, tag = "4")]
+    pub actor: ::prost::alloc::string::String,
+    /// reason given for the change, could be empty
+    #[prost(string, tag = "5")]
+    pub reason: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "6")]
+    #[serde(with = "crate::pb::json::timestamp")]
+    pub changed_at: ::core::option::Option<::prost_types::Timestamp>,
+    /// changed fields of the reservation
+    #[prost(message, repeated, tag = "7")]
+    pub fields: ::prost::alloc::vec::Vec<FieldChange>,
+}
+/// Changes will be returned in HistoryResponse, ordered from the oldest to the newest
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HistoryResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub changes: ::prost::alloc::vec::Vec<ReservationChange>,
+}
 /// Client can listen to reservation updates by sending a ListenRequest
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct ListenRequest {}
+pub struct ListenRequest {
+    /// only send changes touching any of these fields (e.g. "status"). If empty, send all changes
+    #[prost(string, repeated, tag = "1")]
+    pub changed_fields: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
 /// Server will send ListenResponse to client in streaming response
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ListenResponse {
     /// update type
     #[prost(enumeration = "ReservationUpdateType", tag = "1")]
+    #[serde(with = "crate::pb::json::update_type")]
     pub op: i32,
-    /// id for updated reservation
+    /// updated reservation, for DELETE it is the reservation before deletion
     #[prost(message, optional, tag = "2")]
     pub reservation: ::core::option::Option<Reservation>,
+    /// fields touched by the change, create and delete touch all fields
+    #[prost(string, repeated, tag = "3")]
+    pub changed_fields: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 /// reservation status for a given time period
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum ReservationStatus {
@@ -224,6 +407,8 @@ impl ReservationStatus {
     }
 }
 /// when reservation is updated, record the update type
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum ReservationUpdateType {
@@ -476,12 +661,31 @@ pub mod reservation_service_client {
                 .insert(GrpcMethod::new("reservation.ReservationService", "filter"));
             self.inner.unary(req, path, codec).await
         }
+        /// get the change history of a reservation
+        pub async fn history(
+            &mut self,
+            request: impl tonic::IntoRequest<super::HistoryRequest>,
+        ) -> std::result::Result<tonic::Response<super::HistoryResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/reservation.ReservationService/history");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("reservation.ReservationService", "history"));
+            self.inner.unary(req, path, codec).await
+        }
         /// another system could monitor newly added/confirmed/cancelled reservations
         pub async fn listen(
             &mut self,
             request: impl tonic::IntoRequest<super::ListenRequest>,
         ) -> std::result::Result<
-            tonic::Response<tonic::codec::Streaming<super::Reservation>>,
+            tonic::Response<tonic::codec::Streaming<super::ListenResponse>>,
             tonic::Status,
         > {
             self.inner.ready().await.map_err(|e| {
@@ -547,9 +751,14 @@ pub mod reservation_service_server {
             &self,
             request: tonic::Request<super::FilterRequest>,
         ) -> std::result::Result<tonic::Response<super::FilterResponse>, tonic::Status>;
+        /// get the change history of a reservation
+        async fn history(
+            &self,
+            request: tonic::Request<super::HistoryRequest>,
+        ) -> std::result::Result<tonic::Response<super::HistoryResponse>, tonic::Status>;
         /// Server streaming response type for the listen method.
         type listenStream: tonic::codegen::tokio_stream::Stream<
-                Item = std::result::Result<super::Reservation, tonic::Status>,
+                Item = std::result::Result<super::ListenResponse, tonic::Status>,
             > + Send
             + 'static;
         /// another system could monitor newly added/confirmed/cancelled reservations
@@ -919,6 +1128,46 @@ pub mod reservation_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/reservation.ReservationService/history" => {
+                    #[allow(non_camel_case_types)]
+                    struct historySvc<T: ReservationService>(pub Arc<T>);
+                    impl<T: ReservationService> tonic::server::UnaryService<super::HistoryRequest> for historySvc<T> {
+                        type Response = super::HistoryResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::HistoryRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ReservationService>::history(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = historySvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/reservation.ReservationService/listen" => {
                     #[allow(non_camel_case_types)]
                     struct listenSvc<T: ReservationService>(pub Arc<T>);
@@ -926,7 +1175,7 @@ pub mod reservation_service_server {
                         tonic::server::ServerStreamingService<super::ListenRequest>
                         for listenSvc<T>
                     {
-                        type Response = super::Reservation;
+                        type Response = super::ListenResponse;
                         type ResponseStream = T::listenStream;
                         type Future =
                             BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
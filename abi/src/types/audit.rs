@@ -0,0 +1,108 @@
+use std::{fmt, str::FromStr};
+
+use crate::{Error, Validate};
+
+// 定义变更的操作者(用户或者服务主体)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Actor {
+    User(String),
+    Service(String),
+}
+
+// 定义变更的审计信息，会随变更记录一起持久化
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Audit {
+    pub actor: Actor,
+    pub reason: Option<String>,
+}
+
+impl Actor {
+    pub fn user(id: impl Into<String>) -> Self {
+        Self::User(id.into())
+    }
+
+    pub fn service(name: impl Into<String>) -> Self {
+        Self::Service(name.into())
+    }
+
+    // 获取操作者的标识
+    pub fn id(&self) -> &str {
+        match self {
+            Actor::User(id) => id,
+            Actor::Service(name) => name,
+        }
+    }
+}
+
+impl Audit {
+    pub fn new(actor: Actor, reason: impl Into<String>) -> Self {
+        let reason = reason.into();
+        Self {
+            actor,
+            reason: (!reason.is_empty()).then_some(reason),
+        }
+    }
+}
+
+impl From<Actor> for Audit {
+    fn from(actor: Actor) -> Self {
+        Self {
+            actor,
+            reason: None,
+        }
+    }
+}
+
+impl fmt::Display for Actor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Actor::User(id) => write!(f, "user:{}", id),
+            Actor::Service(name) => write!(f, "service:{}", name),
+        }
+    }
+}
+
+impl FromStr for Actor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("user", id)) if !id.is_empty() => Ok(Actor::user(id)),
+            Some(("service", name)) if !name.is_empty() => Ok(Actor::service(name)),
+            _ => Err(Error::InvalidActor(s.to_string())),
+        }
+    }
+}
+
+impl Validate for Audit {
+    fn validate(&self) -> Result<(), Error> {
+        // 持久化格式为 "类型:标识"，长度不能超过数据库字段长度
+        let actor = self.actor.to_string();
+        if self.actor.id().is_empty() || actor.len() > 64 {
+            return Err(Error::InvalidActor(actor));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn actor_should_round_trip_through_string() {
+        let actor = Actor::service("billing");
+        assert_eq!(actor.to_string(), "service:billing");
+        assert_eq!("service:billing".parse::<Actor>().unwrap(), actor);
+        assert_eq!("user:tyr".parse::<Actor>().unwrap(), Actor::user("tyr"));
+        assert!("tyr".parse::<Actor>().is_err());
+        assert!("user:".parse::<Actor>().is_err());
+    }
+
+    #[test]
+    fn audit_should_reject_empty_actor() {
+        assert!(Audit::from(Actor::user("")).validate().is_err());
+        assert!(Audit::new(Actor::user("tyr"), "").validate().is_ok());
+        assert_eq!(Audit::new(Actor::user("tyr"), "").reason, None);
+    }
+}
@@ -1,25 +1,42 @@
+mod audit;
 mod reservation;
+mod reservation_change;
 mod reservation_filter;
 mod reservation_query;
 mod reservation_status;
+mod tenant;
 
 use chrono::{DateTime, Utc};
 use prost_types::Timestamp;
 use sqlx::postgres::types::PgRange;
 use std::ops::Bound;
 
-use crate::{convert_to_utc_time, Error};
+use crate::{convert_to_utc_time, validate_timestamp, Error};
+
+pub use audit::*;
+pub use reservation_change::{diff_fields, TRACKED_FIELDS};
+pub use tenant::*;
 
 pub fn validate_range(start: Option<&Timestamp>, end: Option<&Timestamp>) -> Result<(), Error> {
     if start.is_none() || end.is_none() {
         return Err(Error::InvalidTime);
     }
 
-    let start = start.unwrap();
-    let end = end.unwrap();
+    validate_bounds(start, end)
+}
 
-    if start.seconds >= end.seconds {
-        return Err(Error::InvalidTime);
+// 校验查询的时间段，未指定的一端不限制，两端都指定时开始时间需要早于结束时间
+pub(crate) fn validate_bounds(
+    start: Option<&Timestamp>,
+    end: Option<&Timestamp>,
+) -> Result<(), Error> {
+    for ts in start.into_iter().chain(end) {
+        validate_timestamp(ts)?;
+    }
+    if let (Some(start), Some(end)) = (start, end) {
+        if start.seconds >= end.seconds {
+            return Err(Error::InvalidTime);
+        }
     }
     Ok(())
 }
@@ -34,6 +51,23 @@ pub fn get_timespan(start: Option<&Timestamp>, end: Option<&Timestamp>) -> PgRan
     }
 }
 
+// 生成预定完全落在[start, end)内的SQL条件，未指定的一端不限制
+pub(crate) fn timespan_condition(start: Option<&Timestamp>, end: Option<&Timestamp>) -> String {
+    let time = |ts: Option<&Timestamp>, unbounded: &str| match ts {
+        Some(ts) => convert_to_utc_time(ts).to_rfc3339(),
+        None => unbounded.to_string(),
+    };
+    format!(
+        "tstzrange('{}', '{}') @> timespan",
+        time(start, "-infinity"),
+        time(end, "infinity")
+    )
+}
+
+// 按用户和资源过滤的SQL条件，用户ID和资源ID由存储绑定到$2和$3，为空时忽略
+pub(crate) const USER_RESOURCE_CONDITION: &str =
+    "($2 = '' OR user_id = $2) AND ($3 = '' OR resource_id = $3)";
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -66,6 +100,26 @@ mod tests {
         assert!(validate_range(Some(&start), Some(&end)).is_err());
     }
 
+    #[test]
+    fn validate_range_should_reject_out_of_range_timestamp() {
+        let start = Timestamp {
+            seconds: 1,
+            nanos: -1,
+        };
+        let end = Timestamp {
+            seconds: i64::MAX,
+            nanos: 0,
+        };
+        let valid = Timestamp {
+            seconds: 2,
+            nanos: 0,
+        };
+
+        assert!(validate_range(Some(&start), Some(&valid)).is_err());
+        assert!(validate_range(Some(&valid), Some(&end)).is_err());
+        assert!(validate_bounds(None, Some(&end)).is_err());
+    }
+
     #[test]
     fn get_timestamp_should_work_for_valid_start_end() {
         let start = Timestamp {
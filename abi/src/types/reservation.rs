@@ -9,6 +9,7 @@ use sqlx::{
     postgres::{types::PgRange, PgRow},
     FromRow, Row,
 };
+use uuid::Uuid;
 
 impl Reservation {
     // 创建一个预定类
@@ -27,6 +28,9 @@ impl Reservation {
             end: Some(convert_to_timestamp(&end.with_timezone(&Utc))),
             note: note.into(),
             status: ReservationStatus::Pending as i32,
+            version: 0,
+            public_id: String::new(),
+            tenant_id: String::new(),
         }
     }
 
@@ -67,6 +71,7 @@ impl FromRow<'_, PgRow> for Reservation {
         let end = range.end.unwrap();
 
         let status: RsvpStatus = row.get("status");
+        let public_id: Option<Uuid> = row.get("public_id");
 
         Ok(Self {
             id,
@@ -76,6 +81,9 @@ impl FromRow<'_, PgRow> for Reservation {
             end: Some(convert_to_timestamp(&end)),
             note: row.get("note"),
             status: ReservationStatus::from(status) as i32,
+            version: row.get("version"),
+            public_id: public_id.map(|id| id.to_string()).unwrap_or_default(),
+            tenant_id: row.get("tenant_id"),
         })
     }
 }
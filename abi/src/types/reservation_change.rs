@@ -0,0 +1,168 @@
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+use sqlx::{postgres::PgRow, FromRow, Row};
+
+use crate::{
+    convert_to_timestamp, FieldChange, ListenRequest, ListenResponse, Reservation,
+    ReservationChange, ReservationUpdateType,
+};
+
+// 实现从sqlx::Row转换为abi::ReservationChange的方法
+impl FromRow<'_, PgRow> for ReservationChange {
+    fn from_row(row: &'_ PgRow) -> Result<Self, sqlx::Error> {
+        let op: String = row.get("op");
+        let op = ReservationUpdateType::from_op(&op);
+        let old: Option<Value> = row.get("old");
+        let new: Option<Value> = row.get("new");
+        let changed_at: DateTime<Utc> = row.get("created_at");
+        let actor: Option<String> = row.get("actor");
+        let reason: Option<String> = row.get("reason");
+
+        Ok(Self {
+            id: row.get("id"),
+            reservation_id: row.get("reservation_id"),
+            op: op as i32,
+            actor: actor.unwrap_or_default(),
+            reason: reason.unwrap_or_default(),
+            changed_at: Some(convert_to_timestamp(&changed_at)),
+            fields: diff_fields(old.as_ref(), new.as_ref()),
+        })
+    }
+}
+
+// 实现从变更记录(包含变更后的预定字段)转换为abi::ListenResponse的方法
+impl FromRow<'_, PgRow> for ListenResponse {
+    fn from_row(row: &'_ PgRow) -> Result<Self, sqlx::Error> {
+        let op: String = row.get("op");
+        Ok(Self {
+            op: ReservationUpdateType::from_op(&op) as i32,
+            reservation: Some(Reservation::from_row(row)?),
+            changed_fields: row.get("changed_fields"),
+        })
+    }
+}
+
+impl ReservationUpdateType {
+    // 从数据库中的rsvp.reservation_update_type转换
+    pub fn from_op(op: &str) -> Self {
+        match op {
+            "create" => ReservationUpdateType::Create,
+            "update" => ReservationUpdateType::Update,
+            "delete" => ReservationUpdateType::Delete,
+            _ => ReservationUpdateType::Unknown,
+        }
+    }
+
+    // 转换为数据库中的rsvp.reservation_update_type
+    pub fn as_op(&self) -> &'static str {
+        match self {
+            ReservationUpdateType::Create => "create",
+            ReservationUpdateType::Update => "update",
+            ReservationUpdateType::Delete => "delete",
+            ReservationUpdateType::Unknown => "unknown",
+        }
+    }
+}
+
+impl ListenRequest {
+    // 判断变更是否是订阅者关心的，未指定字段时订阅所有变更
+    pub fn matches(&self, changed_fields: &[String]) -> bool {
+        self.changed_fields.is_empty()
+            || self
+                .changed_fields
+                .iter()
+                .any(|field| changed_fields.contains(field))
+    }
+}
+
+// 记录变更的字段，与数据库触发器中比较的字段保持一致。
+// id、version、public_id、tenant_id等记账字段在每次更新时都可能变化，不算作变更
+pub const TRACKED_FIELDS: [&str; 5] = ["user_id", "status", "resource_id", "timespan", "note"];
+
+// 对比变更前后的记录，返回发生变化的字段(按字段名排序)
+pub fn diff_fields(old: Option<&Value>, new: Option<&Value>) -> Vec<FieldChange> {
+    let old = old.and_then(Value::as_object);
+    let new = new.and_then(Value::as_object);
+
+    let mut fields = TRACKED_FIELDS;
+    fields.sort();
+
+    fields
+        .into_iter()
+        .filter(|field| get(old, field) != get(new, field))
+        .map(|field| FieldChange {
+            field: field.to_string(),
+            old: get(old, field).map(Value::to_string).unwrap_or_default(),
+            new: get(new, field).map(Value::to_string).unwrap_or_default(),
+        })
+        .collect()
+}
+
+fn get<'a>(map: Option<&'a Map<String, Value>>, field: &str) -> Option<&'a Value> {
+    map.and_then(|m| m.get(field))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn diff_fields_should_only_return_changed_fields() {
+        let old = json!({"id": 1, "note": "old note", "status": "pending"});
+        let new = json!({"id": 1, "note": "new note", "status": "pending"});
+
+        let fields = diff_fields(Some(&old), Some(&new));
+
+        assert_eq!(
+            fields,
+            vec![FieldChange {
+                field: "note".into(),
+                old: r#""old note""#.into(),
+                new: r#""new note""#.into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn listen_request_should_filter_by_changed_fields() {
+        let all = ListenRequest::default();
+        let status_only = ListenRequest {
+            changed_fields: vec!["status".into()],
+        };
+        let note_changed = vec!["note".to_string()];
+        let status_changed = vec!["status".to_string(), "note".to_string()];
+
+        assert!(all.matches(&note_changed));
+        assert!(!status_only.matches(&note_changed));
+        assert!(status_only.matches(&status_changed));
+    }
+
+    #[test]
+    fn diff_fields_should_handle_create_and_delete() {
+        let rsvp = json!({"id": 1, "note": "note", "user_id": "tyr"});
+
+        let created = diff_fields(None, Some(&rsvp));
+        assert_eq!(created.len(), 2);
+        assert_eq!(created[0].field, "note");
+        assert_eq!(created[0].old, "");
+        assert_eq!(created[0].new, r#""note""#);
+
+        let deleted = diff_fields(Some(&rsvp), None);
+        assert_eq!(deleted.len(), 2);
+        assert_eq!(deleted[1].field, "user_id");
+        assert_eq!(deleted[1].new, "");
+    }
+
+    #[test]
+    fn diff_fields_should_ignore_bookkeeping_fields() {
+        let old =
+            json!({"id": 1, "note": "old", "version": 1, "public_id": "a", "tenant_id": "default"});
+        let new =
+            json!({"id": 2, "note": "new", "version": 2, "public_id": "b", "tenant_id": "acme"});
+
+        let fields = diff_fields(Some(&old), Some(&new));
+        let names: Vec<_> = fields.iter().map(|f| f.field.as_str()).collect();
+        assert_eq!(names, vec!["note"]);
+    }
+}
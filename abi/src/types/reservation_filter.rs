@@ -1 +1,170 @@
+use super::{timespan_condition, validate_bounds, USER_RESOURCE_CONDITION};
+use crate::{
+    Error, FilterPager, Normalizer, Reservation, ReservationFilter, ReservationStatus, ToSql,
+    Validate,
+};
 
+// 默认的页大小以及允许的最大页大小
+const DEFAULT_PAGE_SIZE: i64 = 10;
+const MAX_PAGE_SIZE: i64 = 100;
+
+impl ReservationFilter {
+    pub fn get_status(&self) -> ReservationStatus {
+        ReservationStatus::try_from(self.status).unwrap_or(ReservationStatus::Unknown)
+    }
+
+    // 根据多查询一条的结果生成分页信息，并去掉多查询的那一条
+    // next不为0时表示还有下一页，prev不为0时表示还有上一页(以prev为游标反向查询即可)
+    pub fn get_pager(&self, data: &mut Vec<Reservation>, total: i64) -> FilterPager {
+        let has_next = data.len() as i64 > self.page_size;
+        data.truncate(self.page_size as usize);
+
+        let prev = if self.cursor > 0 {
+            data.first().map(|r| r.id).unwrap_or_default()
+        } else {
+            0
+        };
+        let next = if has_next {
+            data.last().map(|r| r.id).unwrap_or_default()
+        } else {
+            0
+        };
+
+        FilterPager { prev, next, total }
+    }
+
+    // 生成统计满足条件(不考虑游标)的预定总数的SQL
+    pub fn to_count_sql(&self) -> String {
+        format!(
+            "SELECT COUNT(*) FROM rsvp.reservations WHERE tenant_id = $1 AND status = '{}'::rsvp.reservation_status AND {} AND {}",
+            self.get_status(),
+            USER_RESOURCE_CONDITION,
+            timespan_condition(self.start.as_ref(), self.end.as_ref())
+        )
+    }
+}
+
+// 为预订记录过滤添加校验
+impl Validate for ReservationFilter {
+    fn validate(&self) -> Result<(), Error> {
+        ReservationStatus::try_from(self.status).map_err(|_| Error::InvalidStatus(self.status))?;
+        if self.page_size < 1 || self.page_size > MAX_PAGE_SIZE {
+            return Err(Error::InvalidPageSize(self.page_size));
+        }
+        if self.cursor < 0 {
+            return Err(Error::InvalidCursor(self.cursor));
+        }
+        validate_bounds(self.start.as_ref(), self.end.as_ref())
+    }
+}
+
+// 为预订记录过滤添加规范化特征
+impl Normalizer for ReservationFilter {
+    fn normalize(&mut self) -> Result<(), Error> {
+        // 未指定页大小时使用默认页大小
+        if self.page_size == 0 {
+            self.page_size = DEFAULT_PAGE_SIZE;
+        }
+        self.validate()?;
+        self.do_normalize();
+        Ok(())
+    }
+
+    fn do_normalize(&mut self) {
+        if self.status == ReservationStatus::Unknown as i32 {
+            self.status = ReservationStatus::Pending as i32;
+        }
+    }
+}
+
+impl ToSql for ReservationFilter {
+    fn to_sql(&self) -> String {
+        let (cursor, direction) = match (self.cursor, self.desc) {
+            (0, desc) => ("TRUE".to_string(), if desc { "DESC" } else { "ASC" }),
+            (cursor, false) => (format!("id > {}", cursor), "ASC"),
+            (cursor, true) => (format!("id < {}", cursor), "DESC"),
+        };
+
+        // 多查询一条用于判断是否还有下一页
+        format!(
+            "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND status = '{}'::rsvp.reservation_status AND {} AND {} AND {} ORDER BY id {} LIMIT {}",
+            self.get_status(),
+            USER_RESOURCE_CONDITION,
+            timespan_condition(self.start.as_ref(), self.end.as_ref()),
+            cursor,
+            direction,
+            self.page_size + 1
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_should_generate_valid_sql() {
+        let mut filter = ReservationFilter {
+            user_id: "tyr".into(),
+            ..Default::default()
+        };
+        filter.normalize().unwrap();
+        assert_eq!(filter.to_sql(), "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND status = 'pending'::rsvp.reservation_status AND ($2 = '' OR user_id = $2) AND ($3 = '' OR resource_id = $3) AND tstzrange('-infinity', 'infinity') @> timespan AND TRUE ORDER BY id ASC LIMIT 11");
+
+        filter.cursor = 100;
+        filter.desc = true;
+        assert_eq!(filter.to_sql(), "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND status = 'pending'::rsvp.reservation_status AND ($2 = '' OR user_id = $2) AND ($3 = '' OR resource_id = $3) AND tstzrange('-infinity', 'infinity') @> timespan AND id < 100 ORDER BY id DESC LIMIT 11");
+
+        filter.start = Some("2021-11-01T15:00:00-0700".parse().unwrap());
+        assert_eq!(filter.to_count_sql(), "SELECT COUNT(*) FROM rsvp.reservations WHERE tenant_id = $1 AND status = 'pending'::rsvp.reservation_status AND ($2 = '' OR user_id = $2) AND ($3 = '' OR resource_id = $3) AND tstzrange('2021-11-01T22:00:00+00:00', 'infinity') @> timespan");
+    }
+
+    #[test]
+    fn filter_should_reject_invalid_time_range() {
+        let mut filter = ReservationFilter {
+            start: Some("2021-11-02T00:00:00-0700".parse().unwrap()),
+            end: Some("2021-11-01T00:00:00-0700".parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(matches!(filter.normalize(), Err(Error::InvalidTime)));
+    }
+
+    #[test]
+    fn filter_should_reject_invalid_page_size() {
+        let mut filter = ReservationFilter {
+            page_size: MAX_PAGE_SIZE + 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            filter.normalize(),
+            Err(Error::InvalidPageSize(MAX_PAGE_SIZE + 1))
+        );
+    }
+
+    #[test]
+    fn get_pager_should_drop_extra_row() {
+        let filter = ReservationFilter {
+            cursor: 3,
+            page_size: 2,
+            ..Default::default()
+        };
+        let mut data: Vec<Reservation> = (4..7)
+            .map(|id| Reservation {
+                id,
+                ..Default::default()
+            })
+            .collect();
+
+        let pager = filter.get_pager(&mut data, 10);
+
+        assert_eq!(data.len(), 2);
+        assert_eq!(
+            pager,
+            FilterPager {
+                prev: 4,
+                next: 5,
+                total: 10
+            }
+        );
+    }
+}
@@ -1,12 +1,9 @@
-use prost_types::Timestamp;
-
-use crate::{
-    convert_to_utc_time, Error, Normalizer, ReservationQuery, ReservationStatus, ToSql, Validate,
-};
+use super::{timespan_condition, validate_bounds, USER_RESOURCE_CONDITION};
+use crate::{Error, Normalizer, ReservationQuery, ReservationStatus, ToSql, Validate};
 
 impl ReservationQuery {
     pub fn get_status(&self) -> ReservationStatus {
-        ReservationStatus::from_i32(self.status).unwrap()
+        ReservationStatus::try_from(self.status).unwrap()
     }
 }
 
@@ -14,14 +11,9 @@ impl ReservationQuery {
 impl Validate for ReservationQuery {
     fn validate(&self) -> Result<(), Error> {
         // 检查查询状态是否有效
-        ReservationStatus::from_i32(self.status).ok_or(Error::InvalidStatus(self.status))?;
+        ReservationStatus::try_from(self.status).map_err(|_| Error::InvalidStatus(self.status))?;
         // 检查查询区间是否有效
-        if let (Some(start), Some(end)) = (self.start.as_ref(), self.end.as_ref()) {
-            if start.seconds >= end.seconds {
-                return Err(Error::InvalidTime);
-            }
-        }
-        Ok(())
+        validate_bounds(self.start.as_ref(), self.end.as_ref())
     }
 }
 
@@ -36,78 +28,73 @@ impl Normalizer for ReservationQuery {
 
 impl ToSql for ReservationQuery {
     fn to_sql(&self) -> String {
-        let status = self.get_status();
-
-        let timespan = format!(
-            "tstzrange('{}', '{}')",
-            get_time_string(self.start.as_ref(), true),
-            get_time_string(self.end.as_ref(), false)
-        );
-
-        let condition = match (self.user_id.is_empty(), self.resource_id.is_empty()) {
-            (true, true) => "TRUE".into(),
-            (false, true) => format!("user_id = '{}'", self.user_id),
-            (true, false) => format!("resource_id = '{}'", self.resource_id),
-            (false, false) => format!(
-                "user_id = '{}' AND resource_id = '{}'",
-                self.user_id, self.resource_id
-            ),
+        // 状态为UNKNOWN时(只有导出时不经过规范化)返回所有状态的预定
+        let status = match self.get_status() {
+            ReservationStatus::Unknown => "TRUE".to_string(),
+            status => format!("status = '{}'::rsvp.reservation_status", status),
         };
 
+        let timespan = timespan_condition(self.start.as_ref(), self.end.as_ref());
+
         let direction = if self.desc { "DESC" } else { "ASC" };
 
         format!(
-            "SELECT * FROM rsvp.reservations WHERE {} @> timespan AND 
-        status = '{}'::rsvp.reservation_status AND {} ORDER BY lower(timespan) {}",
-            timespan, status, condition, direction
+            "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND {} AND {} AND {} ORDER BY lower(timespan) {}",
+            timespan, status, USER_RESOURCE_CONDITION, direction
         )
     }
 }
 
-fn get_time_string(ts: Option<&Timestamp>, start: bool) -> String {
-    match ts {
-        Some(ts) => convert_to_utc_time(ts).to_rfc3339(),
-        None => {
-            if start {
-                "-infinity"
-            } else {
-                "infinity"
-            }
-        }
-        .into(),
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use prost_types::Timestamp;
 
     #[test]
     fn query_should_generate_valid_sql() {
-        let query = ReservationQueryBuilder::default()
-            .user_id("tyr")
-            .build()
-            .unwrap();
+        let query = ReservationQuery {
+            user_id: "tyr".into(),
+            status: ReservationStatus::Pending as i32,
+            ..Default::default()
+        };
 
         let sql = query.to_sql();
 
-        assert_eq!(sql, "SELECT * FROM rsvp.reservations WHERE tstzrange('-infinity', 'infinity') @> timespan AND status = 'pending'::rsvp.reservation_status AND user_id = 'tyr' ORDER BY lower(timespan) ASC");
+        assert_eq!(sql, "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND tstzrange('-infinity', 'infinity') @> timespan AND status = 'pending'::rsvp.reservation_status AND ($2 = '' OR user_id = $2) AND ($3 = '' OR resource_id = $3) ORDER BY lower(timespan) ASC");
 
-        let query = ReservationQueryBuilder::default()
-            .resource_id("test")
-            .start("2021-11-01T15:00:00-0700".parse::<Timestamp>().unwrap())
-            .build()
-            .unwrap();
+        let query = ReservationQuery {
+            resource_id: "test".into(),
+            status: ReservationStatus::Pending as i32,
+            start: Some("2021-11-01T15:00:00-0700".parse::<Timestamp>().unwrap()),
+            ..Default::default()
+        };
 
         let sql = query.to_sql();
-        assert_eq!(sql, "SELECT * FROM rsvp.reservations WHERE tstzrange('2021-11-01T22:00:00+00:00', 'infinity') @> timespan AND status = 'pending'::rsvp.reservation_status AND resource_id = 'test' ORDER BY lower(timespan) ASC");
+        assert_eq!(sql, "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND tstzrange('2021-11-01T22:00:00+00:00', 'infinity') @> timespan AND status = 'pending'::rsvp.reservation_status AND ($2 = '' OR user_id = $2) AND ($3 = '' OR resource_id = $3) ORDER BY lower(timespan) ASC");
 
-        let query = ReservationQueryBuilder::default()
-            .end("2021-11-01T16:00:00-0700".parse::<Timestamp>().unwrap())
-            .build()
-            .unwrap();
+        let query = ReservationQuery {
+            status: ReservationStatus::Pending as i32,
+            end: Some("2021-11-01T16:00:00-0700".parse::<Timestamp>().unwrap()),
+            ..Default::default()
+        };
 
         let sql = query.to_sql();
-        assert_eq!(sql, "SELECT * FROM rsvp.reservations WHERE tstzrange('-infinity', '2021-11-01T23:00:00+00:00') @> timespan AND status = 'pending'::rsvp.reservation_status AND TRUE ORDER BY lower(timespan) ASC");
+        assert_eq!(sql, "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND tstzrange('-infinity', '2021-11-01T23:00:00+00:00') @> timespan AND status = 'pending'::rsvp.reservation_status AND ($2 = '' OR user_id = $2) AND ($3 = '' OR resource_id = $3) ORDER BY lower(timespan) ASC");
+    }
+
+    #[test]
+    fn query_with_unknown_status_should_match_all_statuses() {
+        let query = ReservationQuery {
+            user_id: "tyr".into(),
+            ..Default::default()
+        };
+        assert_eq!(query.to_sql(), "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND tstzrange('-infinity', 'infinity') @> timespan AND TRUE AND ($2 = '' OR user_id = $2) AND ($3 = '' OR resource_id = $3) ORDER BY lower(timespan) ASC");
+    }
+
+    #[test]
+    fn query_should_default_unknown_status_to_pending() {
+        let mut query = ReservationQuery::default();
+        query.normalize().unwrap();
+        assert_eq!(query.get_status(), ReservationStatus::Pending);
     }
 }
@@ -0,0 +1,35 @@
+use crate::Error;
+
+// 未指定租户时使用的默认租户，引入多租户之前的预定都属于默认租户
+pub const DEFAULT_TENANT: &str = "default";
+
+// 校验租户ID，租户ID会写入数据库并用于行级安全策略，只允许字母、数字以及 - _ .
+pub fn validate_tenant(tenant: &str) -> Result<(), Error> {
+    let valid = !tenant.is_empty()
+        && tenant.len() <= 64
+        && tenant
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        return Err(Error::InvalidTenant(tenant.to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_tenant_should_reject_malformed_ids() {
+        assert!(validate_tenant(DEFAULT_TENANT).is_ok());
+        assert!(validate_tenant("acme-corp.eu_1").is_ok());
+        assert_eq!(
+            validate_tenant(""),
+            Err(Error::InvalidTenant(String::new()))
+        );
+        assert!(validate_tenant("acme corp").is_err());
+        assert!(validate_tenant("acme'--").is_err());
+        assert!(validate_tenant(&"a".repeat(65)).is_err());
+    }
+}
@@ -1,11 +1,25 @@
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, TimeZone, Utc};
 use prost_types::Timestamp;
+use uuid::Uuid;
 
+use crate::Error;
+
+// 时间戳需要先通过validate_timestamp校验，超出范围时会panic
 pub fn convert_to_utc_time(ts: &Timestamp) -> DateTime<Utc> {
-    DateTime::<Utc>::from_utc(
-        NaiveDateTime::from_timestamp(ts.seconds, ts.nanos as _),
-        Utc,
-    )
+    Utc.timestamp_opt(ts.seconds, ts.nanos as _).unwrap()
+}
+
+// 校验客户端发送的时间戳，秒数或纳秒数超出范围时无法转换为UTC时间
+pub fn validate_timestamp(ts: &Timestamp) -> Result<(), Error> {
+    if !(0..1_000_000_000).contains(&ts.nanos)
+        || Utc
+            .timestamp_opt(ts.seconds, ts.nanos as _)
+            .single()
+            .is_none()
+    {
+        return Err(Error::InvalidTime);
+    }
+    Ok(())
 }
 
 pub fn convert_to_timestamp(dt: &DateTime<Utc>) -> Timestamp {
@@ -14,3 +28,27 @@ pub fn convert_to_timestamp(dt: &DateTime<Utc>) -> Timestamp {
         nanos: dt.timestamp_subsec_nanos() as _,
     }
 }
+
+// 解析对外公开的预定ID(uuid v7)
+pub fn parse_public_id(id: &str) -> Result<Uuid, Error> {
+    Uuid::parse_str(id).map_err(|_| Error::InvalidPublicId(id.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_timestamp_should_reject_out_of_range_values() {
+        let ts = |seconds, nanos| Timestamp { seconds, nanos };
+        assert!(validate_timestamp(&ts(1, 0)).is_ok());
+        assert!(validate_timestamp(&ts(-1, 999_999_999)).is_ok());
+        assert!(matches!(
+            validate_timestamp(&ts(1, -1)),
+            Err(Error::InvalidTime)
+        ));
+        assert!(validate_timestamp(&ts(1, 1_000_000_000)).is_err());
+        assert!(validate_timestamp(&ts(i64::MAX, 0)).is_err());
+        assert!(validate_timestamp(&ts(i64::MIN, 0)).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use abi::{reservation_service_client::ReservationServiceClient, Actor};
+use anyhow::{anyhow, Context, Result};
+use clap::Args;
+use std::{fs, path::PathBuf};
+use tonic::{
+    metadata::{Ascii, MetadataValue},
+    service::{interceptor::InterceptedService, Interceptor},
+    transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity},
+    Request, Status,
+};
+
+// 与服务端约定的metadata
+const AUTHORIZATION_METADATA: &str = "authorization";
+const TENANT_METADATA: &str = "x-tenant";
+const ACTOR_METADATA: &str = "x-actor";
+
+pub type Client = ReservationServiceClient<InterceptedService<Channel, Metadata>>;
+
+#[derive(Debug, Args)]
+#[command(next_help_heading = "连接选项")]
+pub struct ConnectArgs {
+    /// 服务地址，https地址使用TLS连接
+    #[arg(
+        long,
+        global = true,
+        env = "RSVP_SERVER",
+        default_value = "http://127.0.0.1:10020"
+    )]
+    server: String,
+
+    /// 访问令牌(JWT)，以 Bearer 方式发送
+    #[arg(long, global = true, env = "RSVP_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    /// 租户，服务端配置了认证时以令牌中的租户为准
+    #[arg(long, global = true, env = "RSVP_TENANT")]
+    tenant: Option<String>,
+
+    /// 操作人，格式为 user:<id> 或 service:<name>，记录在变更历史中，服务端配置了认证时以令牌中的用户为准
+    #[arg(long, global = true, env = "RSVP_ACTOR")]
+    actor: Option<Actor>,
+
+    /// 校验服务端证书的CA证书(PEM)
+    #[arg(long, global = true, env = "RSVP_CA_CERT")]
+    ca_cert: Option<PathBuf>,
+
+    /// 双向TLS使用的客户端证书(PEM)
+    #[arg(long, global = true, env = "RSVP_CERT", requires = "key")]
+    cert: Option<PathBuf>,
+
+    /// 客户端证书的私钥(PEM)
+    #[arg(long, global = true, env = "RSVP_KEY", requires = "cert")]
+    key: Option<PathBuf>,
+}
+
+impl ConnectArgs {
+    // 第一次请求时才建立连接，参数错误可以在连接之前报告
+    pub fn connect(&self) -> Result<Client> {
+        let metadata = self.metadata()?;
+        let mut endpoint = Endpoint::from_shared(self.server.clone())
+            .with_context(|| format!("无效的服务地址: {}", self.server))?;
+        if self.server.starts_with("https://") || self.ca_cert.is_some() {
+            endpoint = endpoint.tls_config(self.tls_config()?)?;
+        }
+        Ok(ReservationServiceClient::with_interceptor(
+            endpoint.connect_lazy(),
+            metadata,
+        ))
+    }
+
+    fn tls_config(&self) -> Result<ClientTlsConfig> {
+        let mut tls = ClientTlsConfig::new();
+        if let Some(ca) = &self.ca_cert {
+            tls = tls.ca_certificate(Certificate::from_pem(read_file(ca)?));
+        }
+        if let (Some(cert), Some(key)) = (&self.cert, &self.key) {
+            tls = tls.identity(Identity::from_pem(read_file(cert)?, read_file(key)?));
+        }
+        Ok(tls)
+    }
+
+    // 在连接之前检查metadata的取值，避免每个请求都失败
+    fn metadata(&self) -> Result<Metadata> {
+        let values = [
+            (
+                AUTHORIZATION_METADATA,
+                self.token.as_ref().map(|t| format!("Bearer {}", t)),
+            ),
+            (TENANT_METADATA, self.tenant.clone()),
+            (ACTOR_METADATA, self.actor.as_ref().map(|a| a.to_string())),
+        ];
+        let mut metadata = Metadata::default();
+        for (key, value) in values {
+            if let Some(value) = value {
+                let value = value
+                    .parse()
+                    .with_context(|| format!("{}中包含无效字符", key))?;
+                metadata.0.push((key, value));
+            }
+        }
+        Ok(metadata)
+    }
+}
+
+// 为每个请求附加认证、租户和操作人
+#[derive(Debug, Clone, Default)]
+pub struct Metadata(Vec<(&'static str, MetadataValue<Ascii>)>);
+
+impl Interceptor for Metadata {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        for (key, value) in &self.0 {
+            request.metadata_mut().insert(*key, value.clone());
+        }
+        Ok(request)
+    }
+}
+
+// 只输出状态码和错误信息，不输出metadata
+pub fn rpc_error(status: Status) -> anyhow::Error {
+    anyhow!("请求失败({:?}): {}", status.code(), status.message())
+}
+
+fn read_file(path: &PathBuf) -> Result<Vec<u8>> {
+    fs::read(path).with_context(|| format!("无法读取 {}", path.display()))
+}
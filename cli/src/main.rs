@@ -0,0 +1,525 @@
+mod client;
+mod output;
+mod time;
+
+use abi::{
+    convert_to_timestamp, CancelRequest, ConfirmRequest, FilterRequest, GetRequest, ListenRequest,
+    QueryRequest, Reservation, ReservationFilter, ReservationQuery, ReservationStatus,
+    ReserveRequest, UpdateRequest,
+};
+use anyhow::Result;
+use chrono::Utc;
+use chrono_tz::Tz;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use client::{rpc_error, Client, ConnectArgs};
+use output::{Format, Printer};
+use prost_types::Timestamp;
+use std::{
+    io::{self, Write},
+    str::FromStr,
+};
+
+#[derive(Debug, Parser)]
+#[command(name = "rsvp", about = "预定服务的命令行客户端")]
+struct Cli {
+    #[command(flatten)]
+    connect: ConnectArgs,
+
+    /// 解析和显示时间使用的时区，例如 Asia/Shanghai，默认为系统时区
+    #[arg(long, global = true, env = "RSVP_TZ")]
+    tz: Option<Tz>,
+
+    /// 输出格式
+    #[arg(short, long, global = true, value_enum, default_value_t = Format::Table)]
+    format: Format,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+// 时间参数支持RFC 3339，或者 --tz 时区中的 now、+2h、15:00、tomorrow 15:00、friday 9am、2024-01-05 15:00
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// 创建预定，新的预定处于pending状态
+    Reserve {
+        /// 预定的用户
+        #[arg(long)]
+        user: String,
+        /// 预定的资源
+        #[arg(long)]
+        resource: String,
+        /// 开始时间，例如 "tomorrow 15:00" 或 2024-01-05T15:00:00+08:00
+        #[arg(long)]
+        start: String,
+        /// 结束时间，格式同开始时间
+        #[arg(long)]
+        end: String,
+        /// 备注
+        #[arg(long, default_value = "")]
+        note: String,
+        #[command(flatten)]
+        change: ChangeArgs,
+    },
+    /// 确认pending状态的预定
+    Confirm {
+        /// 预定ID或公开ID
+        id: ReservationId,
+        #[command(flatten)]
+        change: VersionedChangeArgs,
+    },
+    /// 取消预定
+    Cancel {
+        /// 预定ID或公开ID
+        id: ReservationId,
+        #[command(flatten)]
+        change: VersionedChangeArgs,
+    },
+    /// 查看预定
+    Get {
+        /// 预定ID或公开ID
+        id: ReservationId,
+    },
+    /// 修改预定的备注
+    UpdateNote {
+        /// 预定ID或公开ID
+        id: ReservationId,
+        /// 新的备注
+        note: String,
+        #[command(flatten)]
+        change: VersionedChangeArgs,
+    },
+    /// 按用户、资源、状态和时间段查询预定，结果边接收边输出
+    Query(QueryArgs),
+    /// 按预定ID排序分页查询预定
+    Filter(FilterArgs),
+    /// 持续输出预定的变更，直到按下Ctrl-C
+    Listen {
+        /// 只输出修改了这些字段的变更，例如 --field status --field note
+        #[arg(long = "field")]
+        fields: Vec<String>,
+    },
+}
+
+#[derive(Debug, Args)]
+struct ChangeArgs {
+    /// 变更原因，记录在变更历史中
+    #[arg(long, default_value = "")]
+    reason: String,
+    /// 幂等键，使用相同的键重试会返回第一次的结果
+    #[arg(long, default_value = "")]
+    idempotency_key: String,
+}
+
+#[derive(Debug, Args)]
+struct VersionedChangeArgs {
+    #[command(flatten)]
+    change: ChangeArgs,
+    /// 只有预定仍处于这个版本时才修改，0表示不检查
+    #[arg(long, default_value_t = 0)]
+    expect_version: i64,
+}
+
+#[derive(Debug, Args)]
+struct QueryArgs {
+    /// 预定的用户
+    #[arg(long, default_value = "")]
+    user: String,
+    /// 预定的资源
+    #[arg(long, default_value = "")]
+    resource: String,
+    /// 预定状态，默认为pending
+    #[arg(long, value_enum)]
+    status: Option<StatusArg>,
+    /// 时间段的开始
+    #[arg(long)]
+    start: Option<String>,
+    /// 时间段的结束
+    #[arg(long)]
+    end: Option<String>,
+    /// 按开始时间倒序输出
+    #[arg(long)]
+    desc: bool,
+}
+
+#[derive(Debug, Args)]
+struct FilterArgs {
+    /// 预定的用户
+    #[arg(long, default_value = "")]
+    user: String,
+    /// 预定的资源
+    #[arg(long, default_value = "")]
+    resource: String,
+    /// 预定状态，默认为pending
+    #[arg(long, value_enum)]
+    status: Option<StatusArg>,
+    /// 从这个预定ID之后开始，使用上一次输出的下一页游标翻页
+    #[arg(long, default_value_t = 0)]
+    cursor: i64,
+    /// 每页的数量
+    #[arg(long, default_value_t = 10)]
+    page_size: i64,
+    /// 按预定ID倒序
+    #[arg(long)]
+    desc: bool,
+    /// 自动翻页，输出所有满足条件的预定
+    #[arg(long)]
+    all: bool,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum StatusArg {
+    Pending,
+    Confirmed,
+    Blocked,
+}
+
+impl From<StatusArg> for ReservationStatus {
+    fn from(status: StatusArg) -> Self {
+        match status {
+            StatusArg::Pending => ReservationStatus::Pending,
+            StatusArg::Confirmed => ReservationStatus::Confirmed,
+            StatusArg::Blocked => ReservationStatus::Blocked,
+        }
+    }
+}
+
+// 数字为预定ID，否则为公开ID(uuid)
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ReservationId {
+    Id(i64),
+    Public(String),
+}
+
+impl FromStr for ReservationId {
+    type Err = abi::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(id) => Ok(Self::Id(id)),
+            Err(_) => Ok(Self::Public(abi::parse_public_id(s)?.to_string())),
+        }
+    }
+}
+
+impl ReservationId {
+    // 转换为请求中的 (id, public_id)
+    fn into_parts(self) -> (i64, String) {
+        match self {
+            Self::Id(id) => (id, String::new()),
+            Self::Public(public_id) => (0, public_id),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let tz = cli.tz.unwrap_or_else(time::local_tz);
+    let client = cli.connect.connect()?;
+    let mut printer = Printer::new(cli.format, tz, io::stdout());
+    run(cli.command, client, &mut printer, tz).await
+}
+
+async fn run<W: Write>(
+    command: Command,
+    mut client: Client,
+    printer: &mut Printer<W>,
+    tz: Tz,
+) -> Result<()> {
+    let parse_time = |s: &str| -> Result<Timestamp> {
+        time::parse_time(s, tz, Utc::now()).map(|dt| convert_to_timestamp(&dt))
+    };
+    let reservation = match command {
+        Command::Reserve {
+            user,
+            resource,
+            start,
+            end,
+            note,
+            change,
+        } => {
+            let reservation = Reservation {
+                user_id: user,
+                resource_id: resource,
+                start: Some(parse_time(&start)?),
+                end: Some(parse_time(&end)?),
+                note,
+                status: ReservationStatus::Pending as i32,
+                ..Default::default()
+            };
+            let request = ReserveRequest {
+                reservation: Some(reservation),
+                idempotency_key: change.idempotency_key,
+                reason: change.reason,
+            };
+            client
+                .reserve(request)
+                .await
+                .map_err(rpc_error)?
+                .into_inner()
+                .reservation
+        }
+        Command::Confirm { id, change } => {
+            let (id, public_id) = id.into_parts();
+            let request = ConfirmRequest {
+                id,
+                public_id,
+                idempotency_key: change.change.idempotency_key,
+                expected_version: change.expect_version,
+                reason: change.change.reason,
+            };
+            client
+                .confirm(request)
+                .await
+                .map_err(rpc_error)?
+                .into_inner()
+                .reservation
+        }
+        Command::Cancel { id, change } => {
+            let (id, public_id) = id.into_parts();
+            let request = CancelRequest {
+                id,
+                public_id,
+                idempotency_key: change.change.idempotency_key,
+                expected_version: change.expect_version,
+                reason: change.change.reason,
+            };
+            client
+                .cancel(request)
+                .await
+                .map_err(rpc_error)?
+                .into_inner()
+                .reservation
+        }
+        Command::Get { id } => {
+            let (id, public_id) = id.into_parts();
+            let request = GetRequest { id, public_id };
+            client
+                .get(request)
+                .await
+                .map_err(rpc_error)?
+                .into_inner()
+                .reservation
+        }
+        Command::UpdateNote { id, note, change } => {
+            let (id, public_id) = id.into_parts();
+            let request = UpdateRequest {
+                id,
+                public_id,
+                note,
+                idempotency_key: change.change.idempotency_key,
+                expected_version: change.expect_version,
+                reason: change.change.reason,
+            };
+            client
+                .update(request)
+                .await
+                .map_err(rpc_error)?
+                .into_inner()
+                .reservation
+        }
+        Command::Query(args) => {
+            let query = ReservationQuery {
+                user_id: args.user,
+                resource_id: args.resource,
+                status: status(args.status),
+                start: args.start.as_deref().map(parse_time).transpose()?,
+                end: args.end.as_deref().map(parse_time).transpose()?,
+                desc: args.desc,
+            };
+            let request = QueryRequest { query: Some(query) };
+            let mut stream = client.query(request).await.map_err(rpc_error)?.into_inner();
+            while let Some(reservation) = stream.message().await.map_err(rpc_error)? {
+                printer.reservation(&reservation)?;
+            }
+            return Ok(());
+        }
+        Command::Filter(args) => {
+            let mut filter = ReservationFilter {
+                user_id: args.user,
+                resource_id: args.resource,
+                status: status(args.status),
+                cursor: args.cursor,
+                page_size: args.page_size,
+                desc: args.desc,
+                ..Default::default()
+            };
+            loop {
+                let request = FilterRequest {
+                    filter: Some(filter.clone()),
+                };
+                let response = client
+                    .filter(request)
+                    .await
+                    .map_err(rpc_error)?
+                    .into_inner();
+                for reservation in &response.reservations {
+                    printer.reservation(reservation)?;
+                }
+                // 分页信息输出到stderr，不影响json和csv的输出
+                let pager = response.pager.unwrap_or_default();
+                if !args.all || pager.next == 0 {
+                    eprintln!(
+                        "共{}条，上一页游标: {}，下一页游标: {}",
+                        pager.total, pager.prev, pager.next
+                    );
+                    return Ok(());
+                }
+                filter.cursor = pager.next;
+            }
+        }
+        Command::Listen { fields } => {
+            let request = ListenRequest {
+                changed_fields: fields,
+            };
+            let mut stream = client
+                .listen(request)
+                .await
+                .map_err(rpc_error)?
+                .into_inner();
+            while let Some(event) = stream.message().await.map_err(rpc_error)? {
+                printer.event(&event)?;
+            }
+            return Ok(());
+        }
+    };
+    if let Some(reservation) = reservation {
+        printer.reservation(&reservation)?;
+    }
+    Ok(())
+}
+
+fn status(status: Option<StatusArg>) -> i32 {
+    status
+        .map(ReservationStatus::from)
+        .unwrap_or(ReservationStatus::Unknown) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abi::reservation_service_server::ReservationServiceServer;
+    use clap::CommandFactory;
+    use reservation::ReservationManager;
+    use service::RsvpService;
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+
+    // 使用内存存储在随机端口上启动服务，返回连接到它的命令行
+    async fn start_test_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let service = RsvpService::new(ReservationManager::in_memory());
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(ReservationServiceServer::new(service))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        format!("http://{}", addr)
+    }
+
+    async fn rsvp(server: &str, args: &[&str]) -> Result<String> {
+        let cli = Cli::try_parse_from(
+            [
+                "rsvp",
+                "--server",
+                server,
+                "--actor",
+                "user:support",
+                "--tz",
+                "UTC",
+            ]
+            .iter()
+            .chain(args),
+        )?;
+        let mut out = Vec::new();
+        let mut printer = Printer::new(cli.format, Tz::UTC, &mut out);
+        run(cli.command, cli.connect.connect()?, &mut printer, Tz::UTC).await?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    #[tokio::test]
+    async fn commands_should_call_service() {
+        let server = start_test_server().await;
+        let reserved = rsvp(
+            &server,
+            &[
+                "reserve",
+                "--user",
+                "alice",
+                "--resource",
+                "room-101",
+                "--start",
+                "2030-01-05 15:00",
+                "--end",
+                "2030-01-05 16:30",
+                "-f",
+                "json",
+            ],
+        )
+        .await
+        .unwrap();
+        let reserved: serde_json::Value = serde_json::from_str(&reserved).unwrap();
+        assert_eq!(reserved["start"], "2030-01-05T15:00:00+00:00");
+        let public_id = reserved["public_id"].as_str().unwrap();
+
+        let confirmed = rsvp(&server, &["confirm", public_id, "-f", "csv"])
+            .await
+            .unwrap();
+        assert!(confirmed.lines().nth(1).unwrap().contains(",confirmed,"));
+
+        let queried = rsvp(
+            &server,
+            &[
+                "query",
+                "--user",
+                "alice",
+                "--status",
+                "confirmed",
+                "-f",
+                "csv",
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(queried.lines().count(), 2);
+
+        let err = rsvp(&server, &["get", "999"]).await.unwrap_err();
+        assert!(err.to_string().contains("NotFound"));
+    }
+
+    #[test]
+    fn cli_should_be_valid() {
+        Cli::command().debug_assert();
+
+        let cli = Cli::try_parse_from([
+            "rsvp",
+            "confirm",
+            "42",
+            "--expect-version",
+            "2",
+            "--tz",
+            "Asia/Shanghai",
+        ])
+        .unwrap();
+        assert_eq!(cli.tz, Some(Tz::Asia__Shanghai));
+        assert!(matches!(
+            cli.command,
+            Command::Confirm { id: ReservationId::Id(42), change } if change.expect_version == 2
+        ));
+    }
+
+    #[test]
+    fn reservation_id_should_accept_id_or_public_id() {
+        let public_id = "018c9d3e-7a1b-7c2d-9e3f-123456789abc";
+        assert_eq!(
+            "42".parse::<ReservationId>().unwrap().into_parts(),
+            (42, String::new())
+        );
+        assert_eq!(
+            public_id.parse::<ReservationId>().unwrap().into_parts(),
+            (0, public_id.to_string())
+        );
+        assert!("room-42".parse::<ReservationId>().is_err());
+    }
+}
@@ -0,0 +1,260 @@
+use abi::{
+    convert_to_utc_time, ListenResponse, Reservation, ReservationStatus, ReservationUpdateType,
+};
+use anyhow::Result;
+use chrono_tz::Tz;
+use clap::ValueEnum;
+use prost_types::Timestamp;
+use serde_json::{json, Value};
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// 对齐的表格，时间按指定时区显示
+    Table,
+    /// 每行一个JSON对象
+    Json,
+    /// 带表头的CSV
+    Csv,
+}
+
+const COLUMNS: [&str; 9] = [
+    "id",
+    "public_id",
+    "user_id",
+    "resource_id",
+    "status",
+    "start",
+    "end",
+    "version",
+    "note",
+];
+const EVENT_COLUMNS: [&str; 2] = ["op", "changed_fields"];
+
+// 表格中各列的宽度，最后一列(note)不补齐
+const WIDTHS: [usize; 10] = [6, 14, 6, 36, 12, 16, 10, 16, 16, 7];
+
+// 逐行输出预定或变更事件，流式结果不需要等全部收到再输出
+pub struct Printer<W: Write> {
+    format: Format,
+    tz: Tz,
+    out: W,
+    header: bool,
+}
+
+impl<W: Write> Printer<W> {
+    pub fn new(format: Format, tz: Tz, out: W) -> Self {
+        Self {
+            format,
+            tz,
+            out,
+            header: false,
+        }
+    }
+
+    pub fn reservation(&mut self, rsvp: &Reservation) -> Result<()> {
+        match self.format {
+            Format::Json => self.json(reservation_json(rsvp, self.tz)),
+            _ => {
+                let row = self.fields(rsvp);
+                self.row(&COLUMNS, row)
+            }
+        }
+    }
+
+    pub fn event(&mut self, event: &ListenResponse) -> Result<()> {
+        let rsvp = event.reservation.clone().unwrap_or_default();
+        let op = op_name(event.op);
+        match self.format {
+            Format::Json => self.json(json!({
+                "op": op,
+                "changed_fields": event.changed_fields,
+                "reservation": reservation_json(&rsvp, self.tz),
+            })),
+            _ => {
+                let columns = [EVENT_COLUMNS.as_slice(), COLUMNS.as_slice()].concat();
+                let mut row = vec![op.to_string(), event.changed_fields.join(",")];
+                row.extend(self.fields(&rsvp));
+                self.row(&columns, row)
+            }
+        }
+    }
+
+    fn json(&mut self, value: Value) -> Result<()> {
+        writeln!(self.out, "{}", value)?;
+        Ok(())
+    }
+
+    // 表格和CSV在第一行之前输出表头
+    fn row(&mut self, columns: &[&str], row: Vec<String>) -> Result<()> {
+        let header = !std::mem::replace(&mut self.header, true);
+        match self.format {
+            Format::Csv => {
+                let mut writer = csv::Writer::from_writer(&mut self.out);
+                if header {
+                    writer.write_record(columns)?;
+                }
+                writer.write_record(&row)?;
+                writer.flush()?;
+            }
+            _ => {
+                // 事件多两列，宽度从对应的位置开始取
+                let widths = &WIDTHS[WIDTHS.len() + 1 - columns.len()..];
+                if header {
+                    let columns = columns.iter().map(|c| c.to_uppercase()).collect::<Vec<_>>();
+                    writeln!(self.out, "{}", align(&columns, widths))?;
+                }
+                writeln!(self.out, "{}", align(&row, widths))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn fields(&self, rsvp: &Reservation) -> Vec<String> {
+        let time = |ts: Option<&Timestamp>| match self.format {
+            Format::Table => format_time(ts, self.tz, "%Y-%m-%d %H:%M"),
+            _ => format_time(ts, self.tz, "%Y-%m-%dT%H:%M:%S%:z"),
+        };
+        vec![
+            rsvp.id.to_string(),
+            rsvp.public_id.clone(),
+            rsvp.user_id.clone(),
+            rsvp.resource_id.clone(),
+            status_name(rsvp.status),
+            time(rsvp.start.as_ref()),
+            time(rsvp.end.as_ref()),
+            rsvp.version.to_string(),
+            rsvp.note.clone(),
+        ]
+    }
+}
+
+fn reservation_json(rsvp: &Reservation, tz: Tz) -> Value {
+    let time = |ts: Option<&Timestamp>| format_time(ts, tz, "%Y-%m-%dT%H:%M:%S%:z");
+    json!({
+        "id": rsvp.id,
+        "public_id": rsvp.public_id,
+        "user_id": rsvp.user_id,
+        "resource_id": rsvp.resource_id,
+        "status": status_name(rsvp.status),
+        "start": time(rsvp.start.as_ref()),
+        "end": time(rsvp.end.as_ref()),
+        "version": rsvp.version,
+        "note": rsvp.note,
+    })
+}
+
+fn align(row: &[String], widths: &[usize]) -> String {
+    row.iter()
+        .zip(widths.iter().chain(std::iter::repeat(&0)))
+        .map(|(value, width)| format!("{:<width$}", value, width = width))
+        .collect::<Vec<_>>()
+        .join("  ")
+        .trim_end()
+        .to_string()
+}
+
+fn format_time(ts: Option<&Timestamp>, tz: Tz, fmt: &str) -> String {
+    ts.map(|ts| {
+        convert_to_utc_time(ts)
+            .with_timezone(&tz)
+            .format(fmt)
+            .to_string()
+    })
+    .unwrap_or_default()
+}
+
+fn status_name(status: i32) -> String {
+    ReservationStatus::try_from(status)
+        .unwrap_or(ReservationStatus::Unknown)
+        .to_string()
+}
+
+fn op_name(op: i32) -> &'static str {
+    match ReservationUpdateType::try_from(op) {
+        Ok(ReservationUpdateType::Create) => "create",
+        Ok(ReservationUpdateType::Update) => "update",
+        Ok(ReservationUpdateType::Delete) => "delete",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abi::convert_to_timestamp;
+
+    fn rsvp() -> Reservation {
+        Reservation {
+            id: 42,
+            public_id: "018c9d3e-7a1b-7c2d-9e3f-123456789abc".to_string(),
+            user_id: "alice".to_string(),
+            resource_id: "room-101".to_string(),
+            status: ReservationStatus::Confirmed as i32,
+            start: Some(convert_to_timestamp(
+                &"2024-01-05T07:00:00Z".parse().unwrap(),
+            )),
+            end: Some(convert_to_timestamp(
+                &"2024-01-05T08:30:00Z".parse().unwrap(),
+            )),
+            note: "board meeting, 2nd floor".to_string(),
+            version: 2,
+            tenant_id: String::new(),
+        }
+    }
+
+    fn print(format: Format, f: impl Fn(&mut Printer<&mut Vec<u8>>)) -> String {
+        let mut out = Vec::new();
+        f(&mut Printer::new(format, Tz::Asia__Shanghai, &mut out));
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn reservations_should_be_printed_in_each_format() {
+        let twice = |p: &mut Printer<&mut Vec<u8>>| {
+            p.reservation(&rsvp()).unwrap();
+            p.reservation(&rsvp()).unwrap();
+        };
+
+        let table = print(Format::Table, twice);
+        let lines = table.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("ID      PUBLIC_ID"));
+        assert!(lines[1].contains("confirmed   2024-01-05 15:00  2024-01-05 16:30  2"));
+
+        let csv = print(Format::Csv, twice);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "id,public_id,user_id,resource_id,status,start,end,version,note"
+        );
+        assert_eq!(
+            lines[1],
+            "42,018c9d3e-7a1b-7c2d-9e3f-123456789abc,alice,room-101,confirmed,2024-01-05T15:00:00+08:00,2024-01-05T16:30:00+08:00,2,\"board meeting, 2nd floor\""
+        );
+        assert_eq!(lines.len(), 3);
+
+        let json = print(Format::Json, twice);
+        let value: Value = serde_json::from_str(json.lines().next().unwrap()).unwrap();
+        assert_eq!(value["id"], 42);
+        assert_eq!(value["start"], "2024-01-05T15:00:00+08:00");
+    }
+
+    #[test]
+    fn events_should_include_op_and_changed_fields() {
+        let event = ListenResponse {
+            op: ReservationUpdateType::Update as i32,
+            reservation: Some(rsvp()),
+            changed_fields: vec!["status".to_string(), "version".to_string()],
+        };
+
+        let table = print(Format::Table, |p| p.event(&event).unwrap());
+        assert!(table.starts_with("OP      CHANGED_FIELDS  ID"));
+        assert!(table.contains("update  status,version  42"));
+
+        let json = print(Format::Json, |p| p.event(&event).unwrap());
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["op"], "update");
+        assert_eq!(value["reservation"]["status"], "confirmed");
+    }
+}
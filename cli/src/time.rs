@@ -0,0 +1,209 @@
+use anyhow::{anyhow, Result};
+use chrono::{
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+
+// 解析命令行中的时间: RFC 3339，或者给定时区中的自然写法，例如
+// now、+2h、-30m、15:00(今天)、tomorrow 15:00、friday 9am、2024-01-05 15:00、2024-01-05
+pub fn parse_time(input: &str, tz: Tz, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let input = input.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(input) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    let lower = input.to_lowercase();
+    if lower == "now" {
+        return Ok(now);
+    }
+    if let Some(offset) = parse_offset(&lower)? {
+        return now
+            .checked_add_signed(offset)
+            .ok_or_else(|| anyhow!("时间超出范围: {}", input));
+    }
+
+    let today = now.with_timezone(&tz).date_naive();
+    let mut parts = lower.split_whitespace();
+    let local = match (parts.next(), parts.next(), parts.next()) {
+        (Some(first), second, None) => match parse_date(first, today) {
+            Some(date) => match second {
+                Some(clock) => parse_clock(clock).map(|time| date.and_time(time)),
+                None => Some(date.and_time(NaiveTime::MIN)),
+            },
+            None if second.is_none() => parse_datetime(first)
+                .or_else(|| parse_clock(first).map(|time| today.and_time(time))),
+            None => None,
+        },
+        _ => None,
+    }
+    .ok_or_else(|| {
+        anyhow!(
+            "无法解析时间: {}，支持RFC 3339或 tomorrow 15:00 这样的写法",
+            input
+        )
+    })?;
+
+    // 夏令时切换时重复的时间取较早的一个，被跳过的时间不存在
+    tz.from_local_datetime(&local)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok_or_else(|| anyhow!("时间{}在时区{}中不存在", input, tz))
+}
+
+// 未指定时区时使用系统时区，无法识别时使用UTC
+pub fn local_tz() -> Tz {
+    iana_time_zone::get_timezone()
+        .ok()
+        .and_then(|name| name.parse().ok())
+        .unwrap_or(Tz::UTC)
+}
+
+// 相对当前时间的偏移，例如 +2h、-30m、+1d、+1w，不是偏移的写法时返回None
+fn parse_offset(s: &str) -> Result<Option<Duration>> {
+    let (negative, rest) = match s.as_bytes().first() {
+        Some(b'+') => (false, &s[1..]),
+        Some(b'-') => (true, &s[1..]),
+        _ => return Ok(None),
+    };
+    let Some(unit) = rest.chars().last() else {
+        return Ok(None);
+    };
+    let unit_secs = match unit {
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => return Ok(None),
+    };
+    let Ok(n) = rest[..rest.len() - unit.len_utf8()].parse::<u64>() else {
+        return Ok(None);
+    };
+    // 很大的偏移会超出Duration的范围，返回错误而不是panic
+    let offset = n
+        .checked_mul(unit_secs)
+        .and_then(|secs| Duration::from_std(std::time::Duration::from_secs(secs)).ok())
+        .ok_or_else(|| anyhow!("时间偏移超出范围: {}", s))?;
+    Ok(Some(if negative { -offset } else { offset }))
+}
+
+fn parse_date(s: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match s {
+        "today" => Some(today),
+        "tomorrow" => today.succ_opt(),
+        "yesterday" => today.pred_opt(),
+        _ => match s.parse::<Weekday>() {
+            // 星期几表示今天之后最近的那一天
+            Ok(weekday) => {
+                let days = (weekday.num_days_from_monday() + 6
+                    - today.weekday().num_days_from_monday())
+                    % 7
+                    + 1;
+                Some(today + Duration::days(days as i64))
+            }
+            Err(_) => NaiveDate::parse_from_str(s, "%Y-%m-%d").ok(),
+        },
+    }
+}
+
+// 不带时区的日期时间，例如 2024-01-05t15:00
+fn parse_datetime(s: &str) -> Option<NaiveDateTime> {
+    ["%Y-%m-%dt%H:%M:%S", "%Y-%m-%dt%H:%M"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(s, fmt).ok())
+}
+
+// 一天中的时间，例如 15:00、15:00:30、9am、3:30pm
+fn parse_clock(s: &str) -> Option<NaiveTime> {
+    let (clock, pm) = match (s.strip_suffix("am"), s.strip_suffix("pm")) {
+        (Some(clock), _) => (clock, Some(false)),
+        (_, Some(clock)) => (clock, Some(true)),
+        _ => (s, None),
+    };
+    // 没有am/pm时必须带分钟，避免把单独的数字当成时间
+    if pm.is_none() && !clock.contains(':') {
+        return None;
+    }
+    let mut parts = clock.split(':').map(|p| p.parse::<u32>().ok());
+    let hour = parts.next()??;
+    let minute = parts.next().unwrap_or(Some(0))?;
+    let second = parts.next().unwrap_or(Some(0))?;
+    if parts.next().is_some() {
+        return None;
+    }
+    let hour = match pm {
+        Some(pm) if (1..=12).contains(&hour) => hour % 12 + if pm { 12 } else { 0 },
+        Some(_) => return None,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str, tz: Tz) -> Result<String> {
+        // 2024-01-05 是星期五
+        let now = "2024-01-05T10:00:00Z".parse().unwrap();
+        parse_time(input, tz, now).map(|dt| dt.to_rfc3339())
+    }
+
+    #[test]
+    fn rfc3339_and_relative_time_should_be_parsed() {
+        let tz = Tz::Asia__Shanghai;
+        assert_eq!(
+            parse("2024-01-05T15:00:00-07:00", tz).unwrap(),
+            "2024-01-05T22:00:00+00:00"
+        );
+        assert_eq!(parse("now", tz).unwrap(), "2024-01-05T10:00:00+00:00");
+        assert_eq!(parse("+2h", tz).unwrap(), "2024-01-05T12:00:00+00:00");
+        assert_eq!(parse("-30m", tz).unwrap(), "2024-01-05T09:30:00+00:00");
+    }
+
+    #[test]
+    fn natural_time_should_be_parsed_in_time_zone() {
+        let tz = Tz::Asia__Shanghai;
+        assert_eq!(
+            parse("tomorrow 15:00", tz).unwrap(),
+            "2024-01-06T07:00:00+00:00"
+        );
+        assert_eq!(parse("Today 9am", tz).unwrap(), "2024-01-05T01:00:00+00:00");
+        assert_eq!(parse("16:30", tz).unwrap(), "2024-01-05T08:30:00+00:00");
+        assert_eq!(
+            parse("friday 3:30pm", tz).unwrap(),
+            "2024-01-12T07:30:00+00:00"
+        );
+        assert_eq!(parse("mon 12am", tz).unwrap(), "2024-01-07T16:00:00+00:00");
+        assert_eq!(
+            parse("2024-02-01", tz).unwrap(),
+            "2024-01-31T16:00:00+00:00"
+        );
+        assert_eq!(
+            parse("2024-02-01T08:00", tz).unwrap(),
+            "2024-02-01T00:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn invalid_time_should_be_rejected() {
+        let tz = Tz::America__New_York;
+        // 2024-03-10 02:30 在夏令时切换时被跳过
+        assert!(parse("2024-03-10 02:30", tz)
+            .unwrap_err()
+            .to_string()
+            .contains("不存在"));
+        for input in ["15", "13pm", "next week", "tomorrow 25:00", "+2y"] {
+            assert!(parse(input, tz).is_err(), "{} should be rejected", input);
+        }
+        // 超出范围的偏移返回错误而不是panic
+        for input in ["+99999999999d", "-99999999999w", "+18446744073709551615m"] {
+            assert!(
+                parse(input, tz)
+                    .unwrap_err()
+                    .to_string()
+                    .contains("超出范围"),
+                "{} should be out of range",
+                input
+            );
+        }
+    }
+}
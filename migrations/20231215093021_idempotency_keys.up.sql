@@ -0,0 +1,11 @@
+-- idempotency keys for mutating requests, a replay within ttl returns the stored response
+CREATE TABLE rsvp.idempotency_keys (
+    key VARCHAR(128) NOT NULL,
+    op VARCHAR(16) NOT NULL,
+    request BYTEA NOT NULL,
+    response BYTEA,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    CONSTRAINT idempotency_keys_pkey PRIMARY KEY (key)
+);
+CREATE INDEX idempotency_keys_expires_at_idx ON rsvp.idempotency_keys (expires_at);
@@ -0,0 +1,3 @@
+DROP TRIGGER reservations_bump_version ON rsvp.reservations;
+DROP FUNCTION rsvp.reservations_bump_version();
+ALTER TABLE rsvp.reservations DROP COLUMN version;
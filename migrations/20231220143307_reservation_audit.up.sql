@@ -0,0 +1,28 @@
+-- who made the change and why, set per transaction via set_config('rsvp.actor'/'rsvp.reason')
+ALTER TABLE rsvp.reservation_changes
+    ADD COLUMN actor VARCHAR(64),
+    ADD COLUMN reason TEXT,
+    ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+CREATE OR REPLACE FUNCTION rsvp.reservations_trigger() RETURNS TRIGGER AS $$
+DECLARE
+    v_actor VARCHAR(64) := NULLIF(current_setting('rsvp.actor', true), '');
+    v_reason TEXT := NULLIF(current_setting('rsvp.reason', true), '');
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        -- update reservation_changes
+        INSERT INTO rsvp.reservation_changes (reservation_id, old, new, op, actor, reason) VALUES (NEW.id, null, to_jsonb(NEW), 'create', v_actor, v_reason);
+    ELSIF TG_OP = 'UPDATE' THEN
+        -- if status changed, update reservation_changes
+        IF OLD.status <> NEW.status THEN
+            INSERT INTO rsvp.reservation_changes (reservation_id, old, new, op, actor, reason) VALUES (NEW.id, to_jsonb(OLD), to_jsonb(NEW), 'update', v_actor, v_reason);
+        END IF;
+    ELSIF TG_OP = 'DELETE' THEN
+        -- update reservation_changes
+        INSERT INTO rsvp.reservation_changes (reservation_id, old, new, op, actor, reason) VALUES (OLD.id, to_jsonb(OLD), null, 'delete', v_actor, v_reason);
+    END IF;
+    -- notify a channel called reservation_update
+    NOTIFY reservation_update;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
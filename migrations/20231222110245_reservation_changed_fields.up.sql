@@ -0,0 +1,45 @@
+-- which fields a change touched, used by listen subscribers to filter changes
+ALTER TABLE rsvp.reservation_changes ADD COLUMN changed_fields TEXT[] NOT NULL DEFAULT '{}';
+
+-- capture every meaningful field change, not just status changes
+CREATE OR REPLACE FUNCTION rsvp.reservations_trigger() RETURNS TRIGGER AS $$
+DECLARE
+    v_actor VARCHAR(64) := NULLIF(current_setting('rsvp.actor', true), '');
+    v_reason TEXT := NULLIF(current_setting('rsvp.reason', true), '');
+    v_all TEXT[] := ARRAY['user_id', 'status', 'resource_id', 'timespan', 'note'];
+    v_changed TEXT[] := '{}';
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        -- update reservation_changes
+        INSERT INTO rsvp.reservation_changes (reservation_id, old, new, op, actor, reason, changed_fields) VALUES (NEW.id, null, to_jsonb(NEW), 'create', v_actor, v_reason, v_all);
+    ELSIF TG_OP = 'UPDATE' THEN
+        -- version is bumped on every update, so it is not a meaningful change by itself
+        IF OLD.user_id IS DISTINCT FROM NEW.user_id THEN
+            v_changed := array_append(v_changed, 'user_id');
+        END IF;
+        IF OLD.status IS DISTINCT FROM NEW.status THEN
+            v_changed := array_append(v_changed, 'status');
+        END IF;
+        IF OLD.resource_id IS DISTINCT FROM NEW.resource_id THEN
+            v_changed := array_append(v_changed, 'resource_id');
+        END IF;
+        IF OLD.timespan IS DISTINCT FROM NEW.timespan THEN
+            v_changed := array_append(v_changed, 'timespan');
+        END IF;
+        IF OLD.note IS DISTINCT FROM NEW.note THEN
+            v_changed := array_append(v_changed, 'note');
+        END IF;
+        -- nothing changed, no record and no notification
+        IF cardinality(v_changed) = 0 THEN
+            RETURN NULL;
+        END IF;
+        INSERT INTO rsvp.reservation_changes (reservation_id, old, new, op, actor, reason, changed_fields) VALUES (NEW.id, to_jsonb(OLD), to_jsonb(NEW), 'update', v_actor, v_reason, v_changed);
+    ELSIF TG_OP = 'DELETE' THEN
+        -- update reservation_changes
+        INSERT INTO rsvp.reservation_changes (reservation_id, old, new, op, actor, reason, changed_fields) VALUES (OLD.id, to_jsonb(OLD), null, 'delete', v_actor, v_reason, v_all);
+    END IF;
+    -- notify a channel called reservation_update
+    NOTIFY reservation_update;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
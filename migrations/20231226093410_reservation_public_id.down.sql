@@ -0,0 +1,3 @@
+DROP INDEX rsvp.reservations_public_id_idx;
+ALTER TABLE rsvp.reservations DROP COLUMN public_id;
+DROP FUNCTION rsvp.uuid_generate_v7();
@@ -0,0 +1,16 @@
+-- time-sortable uuid v7: 48 bits unix epoch milliseconds followed by random bits
+CREATE OR REPLACE FUNCTION rsvp.uuid_generate_v7() RETURNS UUID AS $$
+    SELECT encode(
+        set_bit(
+            set_bit(
+                overlay(uuid_send(gen_random_uuid())
+                    placing substring(int8send(floor(extract(epoch FROM clock_timestamp()) * 1000)::BIGINT) FROM 3)
+                    FROM 1 FOR 6),
+                52, 1),
+            53, 1),
+        'hex')::UUID;
+$$ LANGUAGE sql VOLATILE;
+
+-- opaque public id exposed to clients, the BIGSERIAL id stays internal for joins
+ALTER TABLE rsvp.reservations ADD COLUMN public_id UUID NOT NULL DEFAULT rsvp.uuid_generate_v7();
+CREATE UNIQUE INDEX reservations_public_id_idx ON rsvp.reservations (public_id);
@@ -0,0 +1,66 @@
+ALTER TABLE rsvp.reservations NO FORCE ROW LEVEL SECURITY, DISABLE ROW LEVEL SECURITY;
+ALTER TABLE rsvp.reservation_changes NO FORCE ROW LEVEL SECURITY, DISABLE ROW LEVEL SECURITY;
+ALTER TABLE rsvp.idempotency_keys NO FORCE ROW LEVEL SECURITY, DISABLE ROW LEVEL SECURITY;
+DROP POLICY idempotency_keys_tenant_isolation ON rsvp.idempotency_keys;
+DROP POLICY reservation_changes_tenant_isolation ON rsvp.reservation_changes;
+DROP POLICY reservations_tenant_isolation ON rsvp.reservations;
+
+CREATE OR REPLACE FUNCTION rsvp.reservations_trigger() RETURNS TRIGGER AS $$
+DECLARE
+    v_actor VARCHAR(64) := NULLIF(current_setting('rsvp.actor', true), '');
+    v_reason TEXT := NULLIF(current_setting('rsvp.reason', true), '');
+    v_all TEXT[] := ARRAY['user_id', 'status', 'resource_id', 'timespan', 'note'];
+    v_changed TEXT[] := '{}';
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        -- update reservation_changes
+        INSERT INTO rsvp.reservation_changes (reservation_id, old, new, op, actor, reason, changed_fields) VALUES (NEW.id, null, to_jsonb(NEW), 'create', v_actor, v_reason, v_all);
+    ELSIF TG_OP = 'UPDATE' THEN
+        -- version is bumped on every update, so it is not a meaningful change by itself
+        IF OLD.user_id IS DISTINCT FROM NEW.user_id THEN
+            v_changed := array_append(v_changed, 'user_id');
+        END IF;
+        IF OLD.status IS DISTINCT FROM NEW.status THEN
+            v_changed := array_append(v_changed, 'status');
+        END IF;
+        IF OLD.resource_id IS DISTINCT FROM NEW.resource_id THEN
+            v_changed := array_append(v_changed, 'resource_id');
+        END IF;
+        IF OLD.timespan IS DISTINCT FROM NEW.timespan THEN
+            v_changed := array_append(v_changed, 'timespan');
+        END IF;
+        IF OLD.note IS DISTINCT FROM NEW.note THEN
+            v_changed := array_append(v_changed, 'note');
+        END IF;
+        -- nothing changed, no record and no notification
+        IF cardinality(v_changed) = 0 THEN
+            RETURN NULL;
+        END IF;
+        INSERT INTO rsvp.reservation_changes (reservation_id, old, new, op, actor, reason, changed_fields) VALUES (NEW.id, to_jsonb(OLD), to_jsonb(NEW), 'update', v_actor, v_reason, v_changed);
+    ELSIF TG_OP = 'DELETE' THEN
+        -- update reservation_changes
+        INSERT INTO rsvp.reservation_changes (reservation_id, old, new, op, actor, reason, changed_fields) VALUES (OLD.id, to_jsonb(OLD), null, 'delete', v_actor, v_reason, v_all);
+    END IF;
+    -- notify a channel called reservation_update
+    NOTIFY reservation_update;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- fails if the same resource is booked or the same idempotency key is used by several tenants
+ALTER TABLE rsvp.idempotency_keys
+    DROP CONSTRAINT idempotency_keys_pkey,
+    ADD CONSTRAINT idempotency_keys_pkey PRIMARY KEY (key),
+    DROP COLUMN tenant_id;
+
+DROP INDEX rsvp.reservation_changes_tenant_id_idx;
+ALTER TABLE rsvp.reservation_changes DROP COLUMN tenant_id;
+
+DROP INDEX rsvp.reservations_user_id_idx;
+CREATE INDEX reservations_user_id_idx ON rsvp.reservations (user_id);
+DROP INDEX rsvp.reservations_resource_id_idx;
+CREATE INDEX reservations_resource_id_idx ON rsvp.reservations (resource_id);
+ALTER TABLE rsvp.reservations
+    DROP CONSTRAINT reservations_conflict,
+    ADD CONSTRAINT reservations_conflict EXCLUDE USING gist (resource_id WITH =, timespan WITH &&),
+    DROP COLUMN tenant_id;
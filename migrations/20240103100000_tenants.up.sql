@@ -0,0 +1,74 @@
+-- every reservation belongs to a tenant, existing rows belong to the default tenant
+ALTER TABLE rsvp.reservations ADD COLUMN tenant_id VARCHAR(64) NOT NULL DEFAULT 'default';
+-- the same resource id in two tenants is two different resources
+ALTER TABLE rsvp.reservations
+    DROP CONSTRAINT reservations_conflict,
+    ADD CONSTRAINT reservations_conflict EXCLUDE USING gist (tenant_id WITH =, resource_id WITH =, timespan WITH &&);
+DROP INDEX rsvp.reservations_resource_id_idx;
+CREATE INDEX reservations_resource_id_idx ON rsvp.reservations (tenant_id, resource_id);
+DROP INDEX rsvp.reservations_user_id_idx;
+CREATE INDEX reservations_user_id_idx ON rsvp.reservations (tenant_id, user_id);
+
+ALTER TABLE rsvp.reservation_changes ADD COLUMN tenant_id VARCHAR(64) NOT NULL DEFAULT 'default';
+CREATE INDEX reservation_changes_tenant_id_idx ON rsvp.reservation_changes (tenant_id, id);
+
+ALTER TABLE rsvp.idempotency_keys
+    ADD COLUMN tenant_id VARCHAR(64) NOT NULL DEFAULT 'default',
+    DROP CONSTRAINT idempotency_keys_pkey,
+    ADD CONSTRAINT idempotency_keys_pkey PRIMARY KEY (tenant_id, key);
+
+-- change records carry the tenant of the reservation
+CREATE OR REPLACE FUNCTION rsvp.reservations_trigger() RETURNS TRIGGER AS $$
+DECLARE
+    v_actor VARCHAR(64) := NULLIF(current_setting('rsvp.actor', true), '');
+    v_reason TEXT := NULLIF(current_setting('rsvp.reason', true), '');
+    v_all TEXT[] := ARRAY['user_id', 'status', 'resource_id', 'timespan', 'note'];
+    v_changed TEXT[] := '{}';
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        -- update reservation_changes
+        INSERT INTO rsvp.reservation_changes (reservation_id, tenant_id, old, new, op, actor, reason, changed_fields) VALUES (NEW.id, NEW.tenant_id, null, to_jsonb(NEW), 'create', v_actor, v_reason, v_all);
+    ELSIF TG_OP = 'UPDATE' THEN
+        -- version is bumped on every update, so it is not a meaningful change by itself
+        IF OLD.user_id IS DISTINCT FROM NEW.user_id THEN
+            v_changed := array_append(v_changed, 'user_id');
+        END IF;
+        IF OLD.status IS DISTINCT FROM NEW.status THEN
+            v_changed := array_append(v_changed, 'status');
+        END IF;
+        IF OLD.resource_id IS DISTINCT FROM NEW.resource_id THEN
+            v_changed := array_append(v_changed, 'resource_id');
+        END IF;
+        IF OLD.timespan IS DISTINCT FROM NEW.timespan THEN
+            v_changed := array_append(v_changed, 'timespan');
+        END IF;
+        IF OLD.note IS DISTINCT FROM NEW.note THEN
+            v_changed := array_append(v_changed, 'note');
+        END IF;
+        -- nothing changed, no record and no notification
+        IF cardinality(v_changed) = 0 THEN
+            RETURN NULL;
+        END IF;
+        INSERT INTO rsvp.reservation_changes (reservation_id, tenant_id, old, new, op, actor, reason, changed_fields) VALUES (NEW.id, NEW.tenant_id, to_jsonb(OLD), to_jsonb(NEW), 'update', v_actor, v_reason, v_changed);
+    ELSIF TG_OP = 'DELETE' THEN
+        -- update reservation_changes
+        INSERT INTO rsvp.reservation_changes (reservation_id, tenant_id, old, new, op, actor, reason, changed_fields) VALUES (OLD.id, OLD.tenant_id, to_jsonb(OLD), null, 'delete', v_actor, v_reason, v_all);
+    END IF;
+    -- notify a channel called reservation_update
+    NOTIFY reservation_update;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- defense in depth: rows are only visible to transactions that set rsvp.tenant_id to their tenant.
+-- the policies are inert until row level security is enabled on the tables (db.row_level_security),
+-- FORCE is used then so the policies also apply to the table owner
+CREATE POLICY reservations_tenant_isolation ON rsvp.reservations
+    USING (tenant_id = current_setting('rsvp.tenant_id', true))
+    WITH CHECK (tenant_id = current_setting('rsvp.tenant_id', true));
+CREATE POLICY reservation_changes_tenant_isolation ON rsvp.reservation_changes
+    USING (tenant_id = current_setting('rsvp.tenant_id', true))
+    WITH CHECK (tenant_id = current_setting('rsvp.tenant_id', true));
+CREATE POLICY idempotency_keys_tenant_isolation ON rsvp.idempotency_keys
+    USING (tenant_id = current_setting('rsvp.tenant_id', true))
+    WITH CHECK (tenant_id = current_setting('rsvp.tenant_id', true));
@@ -0,0 +1,3 @@
+DROP TABLE idempotency_keys;
+DROP TABLE reservation_changes;
+DROP TABLE reservations;
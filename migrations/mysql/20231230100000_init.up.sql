@@ -0,0 +1,50 @@
+-- mysql schema, mirrors rsvp.* in postgres
+CREATE TABLE reservations (
+    id BIGINT NOT NULL AUTO_INCREMENT,
+    user_id VARCHAR(64) NOT NULL,
+    status ENUM('unknown', 'pending', 'confirmed', 'blocked') NOT NULL DEFAULT 'pending',
+
+    resource_id VARCHAR(64) NOT NULL,
+    -- timespan is half-open: [start_at, end_at), stored in UTC
+    start_at DATETIME(6) NOT NULL,
+    end_at DATETIME(6) NOT NULL,
+
+    note TEXT,
+    version BIGINT NOT NULL DEFAULT 1,
+    public_id CHAR(36) NOT NULL,
+
+    CONSTRAINT reservations_pkey PRIMARY KEY (id),
+    CONSTRAINT reservations_timespan_check CHECK (start_at < end_at),
+    UNIQUE INDEX reservations_public_id_idx (public_id),
+    -- no range types, overlaps are rejected with a locking read on this index in the inserting transaction
+    INDEX reservations_resource_id_idx (resource_id, start_at),
+    INDEX reservations_user_id_idx (user_id)
+);
+
+-- written by the store in the same transaction as the change, old/new are to_jsonb-like snapshots
+CREATE TABLE reservation_changes (
+    id BIGINT NOT NULL AUTO_INCREMENT,
+    reservation_id BIGINT NOT NULL,
+    old JSON,
+    new JSON,
+    op ENUM('create', 'update', 'delete') NOT NULL,
+    actor VARCHAR(64),
+    reason TEXT,
+    changed_fields JSON NOT NULL,
+    created_at DATETIME(6) NOT NULL,
+
+    CONSTRAINT reservation_changes_pkey PRIMARY KEY (id),
+    INDEX reservation_changes_reservation_id_idx (reservation_id)
+);
+
+CREATE TABLE idempotency_keys (
+    `key` VARCHAR(128) NOT NULL,
+    op VARCHAR(16) NOT NULL,
+    request BLOB NOT NULL,
+    response BLOB,
+    created_at DATETIME(6) NOT NULL,
+    expires_at DATETIME(6) NOT NULL,
+
+    CONSTRAINT idempotency_keys_pkey PRIMARY KEY (`key`),
+    INDEX idempotency_keys_expires_at_idx (expires_at)
+);
@@ -0,0 +1,16 @@
+-- fails if the same idempotency key is used by several tenants
+ALTER TABLE idempotency_keys
+    DROP PRIMARY KEY,
+    ADD CONSTRAINT idempotency_keys_pkey PRIMARY KEY (`key`),
+    DROP COLUMN tenant_id;
+
+ALTER TABLE reservation_changes
+    DROP INDEX reservation_changes_tenant_id_idx,
+    DROP COLUMN tenant_id;
+
+ALTER TABLE reservations
+    DROP INDEX reservations_user_id_idx,
+    ADD INDEX reservations_user_id_idx (user_id),
+    DROP INDEX reservations_resource_id_idx,
+    ADD INDEX reservations_resource_id_idx (resource_id, start_at),
+    DROP COLUMN tenant_id;
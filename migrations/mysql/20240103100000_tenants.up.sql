@@ -0,0 +1,17 @@
+-- every reservation belongs to a tenant, existing rows belong to the default tenant
+-- overlaps are only rejected within the same tenant and resource
+ALTER TABLE reservations
+    ADD COLUMN tenant_id VARCHAR(64) NOT NULL DEFAULT 'default',
+    DROP INDEX reservations_resource_id_idx,
+    ADD INDEX reservations_resource_id_idx (tenant_id, resource_id, start_at),
+    DROP INDEX reservations_user_id_idx,
+    ADD INDEX reservations_user_id_idx (tenant_id, user_id);
+
+ALTER TABLE reservation_changes
+    ADD COLUMN tenant_id VARCHAR(64) NOT NULL DEFAULT 'default',
+    ADD INDEX reservation_changes_tenant_id_idx (tenant_id, id);
+
+ALTER TABLE idempotency_keys
+    ADD COLUMN tenant_id VARCHAR(64) NOT NULL DEFAULT 'default',
+    DROP PRIMARY KEY,
+    ADD CONSTRAINT idempotency_keys_pkey PRIMARY KEY (tenant_id, `key`);
@@ -0,0 +1,47 @@
+-- sqlite schema for single-node deployments, mirrors rsvp.* in postgres
+-- times are stored as unix microseconds, the same precision as postgres timestamptz
+CREATE TABLE reservations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id VARCHAR(64) NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('unknown', 'pending', 'confirmed', 'blocked')),
+
+    resource_id VARCHAR(64) NOT NULL,
+    start_at INTEGER NOT NULL,
+    end_at INTEGER NOT NULL,
+
+    note TEXT,
+    version INTEGER NOT NULL DEFAULT 1,
+    public_id TEXT NOT NULL,
+
+    -- timespan is half-open: [start_at, end_at)
+    CHECK (start_at < end_at)
+);
+-- sqlite has no EXCLUDE constraint, overlaps are rejected in the inserting transaction
+CREATE INDEX reservations_resource_id_idx ON reservations (resource_id, start_at);
+CREATE INDEX reservations_user_id_idx ON reservations (user_id);
+CREATE UNIQUE INDEX reservations_public_id_idx ON reservations (public_id);
+
+-- written by the store in the same transaction as the change, old/new are to_jsonb-like snapshots
+CREATE TABLE reservation_changes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    reservation_id INTEGER NOT NULL,
+    old TEXT,
+    new TEXT,
+    op TEXT NOT NULL CHECK (op IN ('create', 'update', 'delete')),
+    actor VARCHAR(64),
+    reason TEXT,
+    -- json array of changed field names
+    changed_fields TEXT NOT NULL DEFAULT '[]',
+    created_at INTEGER NOT NULL
+);
+CREATE INDEX reservation_changes_reservation_id_idx ON reservation_changes (reservation_id);
+
+CREATE TABLE idempotency_keys (
+    key VARCHAR(128) PRIMARY KEY,
+    op VARCHAR(16) NOT NULL,
+    request BLOB NOT NULL,
+    response BLOB,
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL
+);
+CREATE INDEX idempotency_keys_expires_at_idx ON idempotency_keys (expires_at);
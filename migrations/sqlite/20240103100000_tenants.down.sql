@@ -0,0 +1,19 @@
+DROP TABLE idempotency_keys;
+CREATE TABLE idempotency_keys (
+    key VARCHAR(128) PRIMARY KEY,
+    op VARCHAR(16) NOT NULL,
+    request BLOB NOT NULL,
+    response BLOB,
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL
+);
+CREATE INDEX idempotency_keys_expires_at_idx ON idempotency_keys (expires_at);
+
+DROP INDEX reservation_changes_tenant_id_idx;
+ALTER TABLE reservation_changes DROP COLUMN tenant_id;
+
+DROP INDEX reservations_user_id_idx;
+CREATE INDEX reservations_user_id_idx ON reservations (user_id);
+DROP INDEX reservations_resource_id_idx;
+CREATE INDEX reservations_resource_id_idx ON reservations (resource_id, start_at);
+ALTER TABLE reservations DROP COLUMN tenant_id;
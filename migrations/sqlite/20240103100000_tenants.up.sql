@@ -0,0 +1,24 @@
+-- every reservation belongs to a tenant, existing rows belong to the default tenant
+ALTER TABLE reservations ADD COLUMN tenant_id VARCHAR(64) NOT NULL DEFAULT 'default';
+-- overlaps are only rejected within the same tenant and resource
+DROP INDEX reservations_resource_id_idx;
+CREATE INDEX reservations_resource_id_idx ON reservations (tenant_id, resource_id, start_at);
+DROP INDEX reservations_user_id_idx;
+CREATE INDEX reservations_user_id_idx ON reservations (tenant_id, user_id);
+
+ALTER TABLE reservation_changes ADD COLUMN tenant_id VARCHAR(64) NOT NULL DEFAULT 'default';
+CREATE INDEX reservation_changes_tenant_id_idx ON reservation_changes (tenant_id, id);
+
+-- sqlite cannot alter a primary key, idempotency keys are short-lived so the table is recreated
+DROP TABLE idempotency_keys;
+CREATE TABLE idempotency_keys (
+    tenant_id VARCHAR(64) NOT NULL,
+    key VARCHAR(128) NOT NULL,
+    op VARCHAR(16) NOT NULL,
+    request BLOB NOT NULL,
+    response BLOB,
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL,
+    PRIMARY KEY (tenant_id, key)
+);
+CREATE INDEX idempotency_keys_expires_at_idx ON idempotency_keys (expires_at);
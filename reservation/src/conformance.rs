@@ -0,0 +1,833 @@
+// 所有存储后端共用的一致性测试，存储后端通过conformance_tests!(setup)生成测试用例
+// setup返回(需要在测试期间保持存活的资源, 预定管理)
+use crate::{IdempotentRsvp, ReservationManager, ReservationStore, Rsvp};
+use abi::Reservation;
+use chrono::{Duration, Utc};
+use prost::Message;
+
+// 为指定的setup函数生成所有一致性测试用例
+macro_rules! conformance_tests {
+    ($(#[$attr:meta])* $setup:ident) => {
+        conformance_tests!(
+            [$(#[$attr])*] $setup;
+            reserve_should_work_for_valid_window,
+            reserve_should_reject_conflicting_window,
+            reserve_should_allow_adjacent_window,
+            reserve_all_should_report_each_reservation,
+            import_should_create_new_reservations,
+            import_should_upsert_by_id,
+            import_should_not_touch_other_tenants,
+            export_should_return_all_statuses_by_start_time,
+            reserve_once_should_replay_same_key_and_payload,
+            reserve_once_should_reject_same_key_with_different_payload,
+            reserve_once_should_retry_after_lease_expires,
+            claim_should_purge_expired_idempotency_keys,
+            change_status_should_only_confirm_pending_reservation,
+            update_note_should_reject_stale_version,
+            delete_should_remove_reservation,
+            history_should_record_actor_reason_and_changed_fields,
+            history_should_only_report_note_for_note_update,
+            listen_should_only_send_subscribed_field_changes,
+            resolve_should_find_reservation_by_public_id,
+            query_should_return_reservations_within_range,
+            filter_should_page_by_id,
+            filter_should_page_within_time_range,
+            filter_should_bind_user_id_literally,
+            tenants_should_not_see_each_other,
+            listen_lag_should_drop_to_zero_once_delivered
+        );
+    };
+    // 属性(例如#[ignore])作为一个整体传递给每个测试
+    ($attrs:tt $setup:ident; $($name:ident),*) => {
+        $(
+            conformance_tests!(@test $attrs $setup $name);
+        )*
+    };
+    (@test [$(#[$attr:meta])*] $setup:ident $name:ident) => {
+        #[tokio::test]
+        $(#[$attr])*
+        async fn $name() {
+            let (_guard, manager) = $setup().await;
+            $crate::conformance::$name(&manager).await;
+        }
+    };
+}
+
+pub(crate) use conformance_tests;
+
+pub async fn reserve_should_work_for_valid_window(manager: &impl Rsvp) {
+    let rsvp = make_tyr_reservation(manager).await;
+    assert!(rsvp.id != 0);
+    assert_eq!(rsvp.version, 1);
+}
+
+pub async fn reserve_should_reject_conflicting_window(manager: &impl Rsvp) {
+    make_tyr_reservation(manager).await;
+    let rsvp = abi::Reservation::new_pending(
+        "aliceid",
+        "ocean-view-room-713",
+        "2022-12-26T15:00:00-0700".parse().unwrap(),
+        "2022-12-30T12:00:00-0700".parse().unwrap(),
+        "hello.",
+    );
+    let err = manager.reserve(rsvp, &admin()).await.unwrap_err();
+    assert_eq!(err, abi::Error::ConflictReservation("".into()));
+}
+
+pub async fn reserve_should_allow_adjacent_window(manager: &impl Rsvp) {
+    make_tyr_reservation(manager).await;
+    // 预定时间段为左闭右开区间，首尾相接不算冲突
+    let rsvp = abi::Reservation::new_pending(
+        "aliceid",
+        "ocean-view-room-713",
+        "2022-12-28T12:00:00-0700".parse().unwrap(),
+        "2022-12-30T12:00:00-0700".parse().unwrap(),
+        "hello.",
+    );
+    assert!(manager.reserve(rsvp, &admin()).await.is_ok());
+
+    let rsvp = abi::Reservation::new_pending(
+        "aliceid",
+        "ocean-view-room-713",
+        "2022-12-20T12:00:00-0700".parse().unwrap(),
+        "2022-12-25T15:00:00-0700".parse().unwrap(),
+        "hello.",
+    );
+    assert!(manager.reserve(rsvp, &admin()).await.is_ok());
+}
+
+pub async fn reserve_all_should_report_each_reservation(manager: &impl Rsvp) {
+    make_tyr_reservation(manager).await;
+    let rsvp = |start: &str, end: &str| {
+        abi::Reservation::new_pending(
+            "aliceid",
+            "ocean-view-room-713",
+            start.parse().unwrap(),
+            end.parse().unwrap(),
+            "imported",
+        )
+    };
+    // 与已有预定冲突、正常、与这批中之前的预定冲突、时间不合法、首尾相接
+    let batch = vec![
+        rsvp("2022-12-26T15:00:00-0700", "2022-12-27T12:00:00-0700"),
+        rsvp("2023-01-02T09:00:00-0700", "2023-01-02T17:00:00-0700"),
+        rsvp("2023-01-02T12:00:00-0700", "2023-01-02T13:00:00-0700"),
+        rsvp("2023-01-03T17:00:00-0700", "2023-01-03T09:00:00-0700"),
+        rsvp("2023-01-02T17:00:00-0700", "2023-01-02T18:00:00-0700"),
+    ];
+    let check = |results: &[Result<Reservation, abi::Error>]| {
+        assert_eq!(results.len(), 5);
+        let conflict = abi::Error::ConflictReservation("".into());
+        assert_eq!(results[0].as_ref().unwrap_err(), &conflict);
+        assert_eq!(results[2].as_ref().unwrap_err(), &conflict);
+        assert!(matches!(results[3], Err(abi::Error::InvalidTime)));
+        assert!(results[1].is_ok() && results[4].is_ok());
+    };
+    let count = || async {
+        let filter = abi::ReservationFilter {
+            user_id: "aliceid".into(),
+            ..Default::default()
+        };
+        manager.filter(filter).await.unwrap().0.total
+    };
+
+    // 只做检查时不会创建预定
+    let results = manager
+        .reserve_all(batch.clone(), &admin(), false)
+        .await
+        .unwrap();
+    check(&results);
+    assert_eq!(results[1].as_ref().unwrap().id, 0);
+    assert_eq!(count().await, 0);
+
+    let results = manager.reserve_all(batch, &admin(), true).await.unwrap();
+    check(&results);
+    let created = results[1].as_ref().unwrap();
+    assert!(created.id > 0);
+    assert_eq!(manager.get(created.id).await.unwrap(), *created);
+    assert_eq!(count().await, 2);
+}
+
+pub async fn import_should_create_new_reservations(manager: &impl Rsvp) {
+    let existing = make_tyr_reservation(manager).await;
+    let rsvp = |user: &str, start: &str, end: &str| {
+        abi::Reservation::new_pending(
+            user,
+            "ocean-view-room-713",
+            start.parse().unwrap(),
+            end.parse().unwrap(),
+            "migrated",
+        )
+    };
+    // 与已有预定冲突、正常(ID和公开ID会被忽略)、与这批中之前的预定冲突、用户为空
+    let batch = vec![
+        rsvp(
+            "aliceid",
+            "2022-12-26T15:00:00-0700",
+            "2022-12-27T12:00:00-0700",
+        ),
+        Reservation {
+            id: existing.id,
+            public_id: existing.public_id.clone(),
+            status: abi::ReservationStatus::Confirmed as i32,
+            ..rsvp(
+                "aliceid",
+                "2023-01-02T09:00:00-0700",
+                "2023-01-02T17:00:00-0700",
+            )
+        },
+        rsvp(
+            "aliceid",
+            "2023-01-02T12:00:00-0700",
+            "2023-01-02T13:00:00-0700",
+        ),
+        rsvp("", "2023-01-03T09:00:00-0700", "2023-01-03T17:00:00-0700"),
+    ];
+    let results = manager.import_all(batch, false, &admin()).await.unwrap();
+    assert_eq!(results.len(), 4);
+    let conflict = abi::Error::ConflictReservation("".into());
+    assert_eq!(results[0].as_ref().unwrap_err(), &conflict);
+    assert_eq!(results[2].as_ref().unwrap_err(), &conflict);
+    assert!(matches!(results[3], Err(abi::Error::InvalidUserId(_))));
+
+    let created = results[1].as_ref().unwrap();
+    assert!(created.id > existing.id);
+    assert_ne!(created.public_id, existing.public_id);
+    assert_eq!(created.version, 1);
+    assert_eq!(created.status, abi::ReservationStatus::Confirmed as i32);
+    assert_eq!(manager.get(created.id).await.unwrap(), *created);
+    assert_eq!(manager.get(existing.id).await.unwrap(), existing);
+    assert_eq!(manager.history(created.id).await.unwrap().len(), 1);
+}
+
+pub async fn import_should_upsert_by_id<S: ReservationStore>(manager: &ReservationManager<S>) {
+    let existing = make_tyr_reservation(manager).await;
+    let public_id = "018c9a1e-7b1a-7c3e-9f00-000000000001";
+    let rsvp = |id: i64, public_id: &str, start: &str, end: &str| Reservation {
+        id,
+        public_id: public_id.to_string(),
+        ..abi::Reservation::new_pending(
+            "aliceid",
+            "ocean-view-room-713",
+            start.parse().unwrap(),
+            end.parse().unwrap(),
+            "migrated",
+        )
+    };
+    let batch = vec![
+        // 覆盖已有的预定并移到新的时间段，保留原来的公开ID
+        Reservation {
+            user_id: "tyrid".into(),
+            ..rsvp(
+                existing.id,
+                "",
+                "2023-01-02T09:00:00-0700",
+                "2023-01-02T17:00:00-0700",
+            )
+        },
+        // 使用文件中的ID和公开ID创建
+        rsvp(
+            1000,
+            public_id,
+            "2022-12-25T15:00:00-0700",
+            "2022-12-26T12:00:00-0700",
+        ),
+        // 与这批中覆盖后的预定冲突
+        rsvp(
+            0,
+            "",
+            "2023-01-02T12:00:00-0700",
+            "2023-01-02T13:00:00-0700",
+        ),
+        // 同一批中重复的ID和公开ID
+        rsvp(
+            existing.id,
+            "",
+            "2023-02-02T09:00:00-0700",
+            "2023-02-02T17:00:00-0700",
+        ),
+        rsvp(
+            0,
+            public_id,
+            "2023-03-02T09:00:00-0700",
+            "2023-03-02T17:00:00-0700",
+        ),
+    ];
+    let results = manager.import_all(batch, true, &admin()).await.unwrap();
+    assert_eq!(results.len(), 5);
+
+    let updated = results[0].as_ref().unwrap();
+    assert_eq!(updated.id, existing.id);
+    assert_eq!(updated.public_id, existing.public_id);
+    assert_eq!(updated.version, 2);
+    assert_eq!(updated.note, "migrated");
+    assert_eq!(manager.get(existing.id).await.unwrap(), *updated);
+    let history = manager.history(existing.id).await.unwrap();
+    assert_eq!(history.len(), 2);
+
+    let created = results[1].as_ref().unwrap();
+    assert_eq!((created.id, created.public_id.as_str()), (1000, public_id));
+    assert_eq!(created.version, 1);
+    assert_eq!(manager.get(1000).await.unwrap(), *created);
+
+    assert_eq!(
+        results[2].as_ref().unwrap_err(),
+        &abi::Error::ConflictReservation("".into())
+    );
+    assert!(matches!(results[3], Err(abi::Error::InvalidReservationId(id)) if id == existing.id));
+    assert_eq!(
+        results[4].as_ref().unwrap_err(),
+        &abi::Error::InvalidPublicId(public_id.into())
+    );
+
+    // 之后分配的ID不会与导入的ID重复
+    let next = make_alice_reservation(manager).await;
+    assert!(next.id > 1000);
+}
+
+pub async fn import_should_not_touch_other_tenants<S: ReservationStore>(
+    manager: &ReservationManager<S>,
+) {
+    let acme = manager.with_tenant("acme").unwrap();
+    let foreign = make_tyr_reservation(&acme).await;
+
+    // 使用acme预定的ID和公开ID导入到默认租户
+    let rsvp = |id: i64, public_id: &str| Reservation {
+        id,
+        public_id: public_id.to_string(),
+        ..abi::Reservation::new_pending(
+            "aliceid",
+            "ixia-test-1",
+            "2023-01-25T15:00:00-0700".parse().unwrap(),
+            "2023-02-25T12:00:00-0700".parse().unwrap(),
+            "",
+        )
+    };
+    let batch = vec![rsvp(foreign.id, ""), rsvp(0, &foreign.public_id)];
+    let results = manager.import_all(batch, true, &admin()).await.unwrap();
+    assert_eq!(results.len(), 2);
+    // 与其它租户的预定冲突一样处理，错误中不包含其它租户预定的内容
+    for result in results {
+        let err = result.unwrap_err();
+        assert_eq!(err, abi::Error::ConflictReservation("".into()));
+        let msg = err.to_string();
+        assert!(!msg.contains(&foreign.user_id) && !msg.contains(&foreign.resource_id));
+        assert!(!msg.contains("acme"));
+    }
+    assert_eq!(acme.get(foreign.id).await.unwrap(), foreign);
+    assert_eq!(acme.history(foreign.id).await.unwrap().len(), 1);
+}
+
+pub async fn export_should_return_all_statuses_by_start_time<S: ReservationStore>(
+    manager: &ReservationManager<S>,
+) {
+    let alice = make_alice_reservation(manager).await;
+    let alice = manager
+        .change_status(alice.id, None, &admin())
+        .await
+        .unwrap();
+    let tyr = make_tyr_reservation(manager).await;
+    let export = |status: abi::ReservationStatus| async move {
+        let filter = abi::ReservationFilter {
+            status: status as i32,
+            ..Default::default()
+        };
+        let mut rx = manager.export(filter).await;
+        let mut rsvps = Vec::new();
+        while let Some(ret) = rx.recv().await {
+            rsvps.push(ret.unwrap());
+        }
+        rsvps
+    };
+
+    // 未指定状态时导出所有状态，在同一个查询中按开始时间排序
+    let all = export(abi::ReservationStatus::Unknown).await;
+    assert_eq!(all, vec![tyr.clone(), alice.clone()]);
+    assert_eq!(export(abi::ReservationStatus::Confirmed).await, vec![alice]);
+    assert_eq!(export(abi::ReservationStatus::Pending).await, vec![tyr]);
+}
+
+pub async fn reserve_once_should_replay_same_key_and_payload(manager: &impl IdempotentRsvp) {
+    let req = abi::ReserveRequest {
+        reservation: Some(tyr_pending_reservation()),
+        idempotency_key: "tyr-retry-1".to_string(),
+        ..Default::default()
+    };
+    let first = manager.reserve_once(req.clone(), tyr()).await.unwrap();
+    let second = manager.reserve_once(req, tyr()).await.unwrap();
+    assert_eq!(first, second);
+}
+
+pub async fn reserve_once_should_reject_same_key_with_different_payload(
+    manager: &impl IdempotentRsvp,
+) {
+    let mut req = abi::ReserveRequest {
+        reservation: Some(tyr_pending_reservation()),
+        idempotency_key: "tyr-retry-2".to_string(),
+        ..Default::default()
+    };
+    manager.reserve_once(req.clone(), tyr()).await.unwrap();
+
+    req.reservation.as_mut().unwrap().note = "changed".to_string();
+    let err = manager.reserve_once(req, tyr()).await.unwrap_err();
+    assert_eq!(
+        err,
+        abi::Error::IdempotencyKeyReused("tyr-retry-2".to_string())
+    );
+}
+
+pub async fn reserve_once_should_retry_after_lease_expires<S: ReservationStore + Clone>(
+    manager: &ReservationManager<S>,
+) {
+    let req = abi::ReserveRequest {
+        reservation: Some(tyr_pending_reservation()),
+        idempotency_key: "tyr-retry-3".to_string(),
+        ..Default::default()
+    };
+    // 模拟进程在抢占幂等键之后崩溃，租约过期后客户端可以重试
+    let claimed = manager
+        .store
+        .claim_idempotency_key(
+            &req.idempotency_key,
+            "reserve",
+            &req.encode_to_vec(),
+            Utc::now() - Duration::seconds(1),
+        )
+        .await
+        .unwrap();
+    assert!(claimed);
+
+    // 租约为0时，处理中的幂等键立即过期，但完成后的响应仍然按有效期保存
+    let manager = manager.clone().with_idempotency_lease(Duration::zero());
+    let first = manager.reserve_once(req.clone(), tyr()).await.unwrap();
+    let second = manager.reserve_once(req, tyr()).await.unwrap();
+    assert_eq!(first, second);
+}
+
+pub async fn claim_should_purge_expired_idempotency_keys<S: ReservationStore>(
+    manager: &ReservationManager<S>,
+) {
+    let store = &manager.store;
+    let expired = Utc::now() - Duration::seconds(1);
+    assert!(store
+        .claim_idempotency_key("stale", "reserve", b"stale", expired)
+        .await
+        .unwrap());
+    assert!(store.get_idempotency_key("stale").await.unwrap().is_some());
+
+    let expires_at = Utc::now() + Duration::minutes(1);
+    assert!(store
+        .claim_idempotency_key("fresh", "reserve", b"fresh", expires_at)
+        .await
+        .unwrap());
+    assert_eq!(store.get_idempotency_key("stale").await.unwrap(), None);
+    assert!(store.get_idempotency_key("fresh").await.unwrap().is_some());
+}
+
+pub async fn change_status_should_only_confirm_pending_reservation(manager: &impl Rsvp) {
+    let rsvp = make_tyr_reservation(manager).await;
+    let rsvp = manager
+        .change_status(rsvp.id, None, &admin())
+        .await
+        .unwrap();
+    assert_eq!(rsvp.status, abi::ReservationStatus::Confirmed as i32);
+    assert_eq!(rsvp.version, 2);
+
+    // 已确认的预定不能再次确认
+    let err = manager
+        .change_status(rsvp.id, None, &admin())
+        .await
+        .unwrap_err();
+    assert_eq!(err, abi::Error::NotFound);
+}
+
+pub async fn update_note_should_reject_stale_version(manager: &impl Rsvp) {
+    let rsvp = make_tyr_reservation(manager).await;
+    assert_eq!(rsvp.version, 1);
+
+    let rsvp = manager
+        .update_note(rsvp.id, "first admin".into(), Some(rsvp.version), &admin())
+        .await
+        .unwrap();
+    assert_eq!(rsvp.version, 2);
+
+    let err = manager
+        .update_note(rsvp.id, "second admin".into(), Some(1), &admin())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err,
+        abi::Error::VersionMismatch {
+            expected: 1,
+            actual: 2
+        }
+    );
+}
+
+pub async fn delete_should_remove_reservation(manager: &impl Rsvp) {
+    let rsvp = make_tyr_reservation(manager).await;
+    let deleted = manager.delete(rsvp.id, None, &admin()).await.unwrap();
+    assert_eq!(deleted, rsvp);
+
+    assert_eq!(
+        manager.get(rsvp.id).await.unwrap_err(),
+        abi::Error::NotFound
+    );
+    assert_eq!(
+        manager.delete(rsvp.id, None, &admin()).await.unwrap_err(),
+        abi::Error::NotFound
+    );
+    // 删除后时间段可以被重新预定
+    make_tyr_reservation(manager).await;
+}
+
+pub async fn history_should_record_actor_reason_and_changed_fields(manager: &impl Rsvp) {
+    let rsvp = make_tyr_reservation(manager).await;
+    let audit = abi::Audit::new(abi::Actor::user("admin"), "guest asked to confirm");
+    manager.change_status(rsvp.id, None, &audit).await.unwrap();
+
+    let changes = manager.history(rsvp.id).await.unwrap();
+    assert_eq!(changes.len(), 2);
+    assert_eq!(changes[0].op, abi::ReservationUpdateType::Create as i32);
+    assert_eq!(changes[0].actor, "user:tyrid");
+    assert_eq!(changes[1].op, abi::ReservationUpdateType::Update as i32);
+    assert_eq!(changes[1].actor, "user:admin");
+    assert_eq!(changes[1].reason, "guest asked to confirm");
+    assert!(changes[1]
+        .fields
+        .iter()
+        .any(|f| f.field == "status" && f.old == r#""pending""# && f.new == r#""confirmed""#));
+}
+
+pub async fn history_should_only_report_note_for_note_update(manager: &impl Rsvp) {
+    let rsvp = make_tyr_reservation(manager).await;
+    manager
+        .update_note(rsvp.id, "arrive late".into(), None, &admin())
+        .await
+        .unwrap();
+
+    // 版本号等记账字段的变化不算作变更
+    let changes = manager.history(rsvp.id).await.unwrap();
+    let fields: Vec<_> = changes[1].fields.iter().map(|f| f.field.as_str()).collect();
+    assert_eq!(fields, vec!["note"]);
+}
+
+pub async fn listen_should_only_send_subscribed_field_changes(manager: &impl Rsvp) {
+    let mut rx = manager
+        .listen(abi::ListenRequest {
+            changed_fields: vec!["status".into()],
+        })
+        .await;
+    // 等待监听任务建立订阅
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    let rsvp = manager
+        .reserve(tyr_pending_reservation(), &admin())
+        .await
+        .unwrap();
+    manager
+        .update_note(rsvp.id, "arrive late".into(), None, &admin())
+        .await
+        .unwrap();
+    manager
+        .change_status(rsvp.id, None, &admin())
+        .await
+        .unwrap();
+
+    let created = rx.recv().await.unwrap().unwrap();
+    assert_eq!(created.op, abi::ReservationUpdateType::Create as i32);
+    let confirmed = rx.recv().await.unwrap().unwrap();
+    assert_eq!(confirmed.op, abi::ReservationUpdateType::Update as i32);
+    assert_eq!(confirmed.changed_fields, vec!["status".to_string()]);
+    assert_eq!(
+        confirmed.reservation.unwrap().status,
+        abi::ReservationStatus::Confirmed as i32
+    );
+}
+
+pub async fn resolve_should_find_reservation_by_public_id(manager: &impl Rsvp) {
+    let rsvp = make_tyr_reservation(manager).await;
+    assert_eq!(
+        abi::parse_public_id(&rsvp.public_id)
+            .unwrap()
+            .get_version_num(),
+        7
+    );
+
+    let id = manager.resolve_id(0, &rsvp.public_id).await.unwrap();
+    assert_eq!(id, rsvp.id);
+    assert_eq!(manager.resolve_id(rsvp.id, "").await.unwrap(), rsvp.id);
+    assert_eq!(
+        manager.resolve_id(0, "not-a-uuid").await.unwrap_err(),
+        abi::Error::InvalidPublicId("not-a-uuid".into())
+    );
+}
+
+pub async fn query_should_return_reservations_within_range(manager: &impl Rsvp) {
+    let tyr = make_tyr_reservation(manager).await;
+    let alice = make_alice_reservation(manager).await;
+
+    let query = abi::ReservationQuery {
+        start: Some("2022-12-01T00:00:00Z".parse().unwrap()),
+        end: Some("2023-01-01T00:00:00Z".parse().unwrap()),
+        ..Default::default()
+    };
+    let mut rx = manager.query(query).await;
+    assert_eq!(rx.recv().await.unwrap().unwrap(), tyr);
+    assert!(rx.recv().await.is_none());
+
+    let query = abi::ReservationQuery {
+        desc: true,
+        ..Default::default()
+    };
+    let mut rx = manager.query(query).await;
+    assert_eq!(rx.recv().await.unwrap().unwrap(), alice);
+    assert_eq!(rx.recv().await.unwrap().unwrap(), tyr);
+    assert!(rx.recv().await.is_none());
+}
+
+pub async fn filter_should_page_by_id(manager: &impl Rsvp) {
+    let mut ids = vec![];
+    for i in 0..3 {
+        let rsvp = abi::Reservation::new_pending(
+            "tyrid",
+            format!("room-{}", i),
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        ids.push(manager.reserve(rsvp, &admin()).await.unwrap().id);
+    }
+
+    let mut filter = abi::ReservationFilter {
+        user_id: "tyrid".into(),
+        page_size: 2,
+        ..Default::default()
+    };
+    let (pager, rsvps) = manager.filter(filter.clone()).await.unwrap();
+    assert_eq!(rsvps.iter().map(|r| r.id).collect::<Vec<_>>(), ids[..2]);
+    assert_eq!(
+        pager,
+        abi::FilterPager {
+            prev: 0,
+            next: ids[1],
+            total: 3
+        }
+    );
+
+    filter.cursor = pager.next;
+    let (pager, rsvps) = manager.filter(filter).await.unwrap();
+    assert_eq!(rsvps.iter().map(|r| r.id).collect::<Vec<_>>(), ids[2..]);
+    assert_eq!(
+        pager,
+        abi::FilterPager {
+            prev: ids[2],
+            next: 0,
+            total: 3
+        }
+    );
+}
+
+pub async fn filter_should_page_within_time_range(manager: &impl Rsvp) {
+    let mut ids = vec![];
+    for day in 1..=4 {
+        let rsvp = abi::Reservation::new_pending(
+            "tyrid",
+            "ocean-view-room-713",
+            format!("2023-01-0{}T09:00:00-0700", day).parse().unwrap(),
+            format!("2023-01-0{}T17:00:00-0700", day).parse().unwrap(),
+            "",
+        );
+        ids.push(manager.reserve(rsvp, &admin()).await.unwrap().id);
+    }
+
+    // 只包含完全落在时间段内的预定(第2到第4天)，总数也只统计时间段内的预定
+    let mut filter = abi::ReservationFilter {
+        user_id: "tyrid".into(),
+        page_size: 2,
+        start: Some("2023-01-02T00:00:00-0700".parse().unwrap()),
+        end: Some("2023-01-05T00:00:00-0700".parse().unwrap()),
+        ..Default::default()
+    };
+    let (pager, rsvps) = manager.filter(filter.clone()).await.unwrap();
+    assert_eq!(rsvps.iter().map(|r| r.id).collect::<Vec<_>>(), ids[1..3]);
+    assert_eq!((pager.next, pager.total), (ids[2], 3));
+
+    filter.cursor = pager.next;
+    let (pager, rsvps) = manager.filter(filter).await.unwrap();
+    assert_eq!(rsvps.iter().map(|r| r.id).collect::<Vec<_>>(), ids[3..]);
+    assert_eq!((pager.next, pager.total), (0, 3));
+}
+
+pub async fn filter_should_bind_user_id_literally(manager: &impl Rsvp) {
+    let rsvp = abi::Reservation::new_pending(
+        "o'neil",
+        "ocean-view-room-713",
+        "2022-12-25T15:00:00-0700".parse().unwrap(),
+        "2022-12-28T12:00:00-0700".parse().unwrap(),
+        "",
+    );
+    let rsvp = manager.reserve(rsvp, &admin()).await.unwrap();
+    make_alice_reservation(manager).await;
+
+    // 用户ID作为参数绑定，其中的引号不会改变SQL的含义
+    let filter = |user_id: &str| abi::ReservationFilter {
+        user_id: user_id.into(),
+        ..Default::default()
+    };
+    let (_, rsvps) = manager.filter(filter("o'neil")).await.unwrap();
+    assert_eq!(rsvps, vec![rsvp.clone()]);
+    let (pager, rsvps) = manager.filter(filter("x' OR '1'='1")).await.unwrap();
+    assert!(rsvps.is_empty());
+    assert_eq!(pager.total, 0);
+
+    let query = abi::ReservationQuery {
+        user_id: "o'neil".into(),
+        ..Default::default()
+    };
+    let mut rx = manager.query(query).await;
+    assert_eq!(rx.recv().await.unwrap().unwrap(), rsvp);
+    assert!(rx.recv().await.is_none());
+}
+
+pub async fn tenants_should_not_see_each_other<S: ReservationStore>(
+    manager: &ReservationManager<S>,
+) {
+    let acme = manager.with_tenant("acme").unwrap();
+    let globex = manager.with_tenant("globex").unwrap();
+    let mut rx = globex.listen(abi::ListenRequest::default()).await;
+    // 等待监听任务建立订阅
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    // 不同租户的同名资源互不冲突
+    let rsvp = make_tyr_reservation(&acme).await;
+    assert_eq!(rsvp.tenant_id, "acme");
+    let other = make_tyr_reservation(&globex).await;
+    assert_eq!(other.tenant_id, "globex");
+
+    assert_eq!(globex.get(rsvp.id).await.unwrap_err(), abi::Error::NotFound);
+    let public_id = abi::parse_public_id(&rsvp.public_id).unwrap();
+    assert_eq!(
+        globex.resolve(public_id).await.unwrap_err(),
+        abi::Error::NotFound
+    );
+    assert_eq!(
+        globex
+            .change_status(rsvp.id, None, &admin())
+            .await
+            .unwrap_err(),
+        abi::Error::NotFound
+    );
+    assert_eq!(
+        globex.delete(rsvp.id, None, &admin()).await.unwrap_err(),
+        abi::Error::NotFound
+    );
+    assert!(globex.history(rsvp.id).await.unwrap().is_empty());
+    assert_eq!(acme.get(rsvp.id).await.unwrap(), rsvp);
+
+    let filter = abi::ReservationFilter {
+        user_id: "tyrid".into(),
+        ..Default::default()
+    };
+    let (_, rsvps) = globex.filter(filter).await.unwrap();
+    assert_eq!(rsvps, vec![other.clone()]);
+    let mut query = globex.query(abi::ReservationQuery::default()).await;
+    assert_eq!(query.recv().await.unwrap().unwrap(), other);
+    assert!(query.recv().await.is_none());
+
+    // 监听者只会收到本租户的变更
+    let change = rx.recv().await.unwrap().unwrap();
+    assert_eq!(change.reservation.unwrap().id, other.id);
+
+    // 幂等键也按租户隔离
+    let mut req = abi::ReserveRequest {
+        reservation: Some(tyr_pending_reservation()),
+        idempotency_key: "shared-key".to_string(),
+        ..Default::default()
+    };
+    req.reservation.as_mut().unwrap().resource_id = "room-42".to_string();
+    let first = acme.reserve_once(req.clone(), tyr()).await.unwrap();
+    let second = globex.reserve_once(req, tyr()).await.unwrap();
+    assert_ne!(first.reservation, second.reservation);
+
+    assert!(matches!(
+        manager.with_tenant("acme corp"),
+        Err(abi::Error::InvalidTenant(_))
+    ));
+}
+
+pub async fn listen_lag_should_drop_to_zero_once_delivered<S: ReservationStore>(
+    manager: &ReservationManager<S>,
+) {
+    let mut rx = manager.listen(abi::ListenRequest::default()).await;
+    // 等待监听任务建立订阅
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert_eq!(manager.listen_subscribers(), 1);
+
+    make_tyr_reservation(manager).await;
+    make_alice_reservation(manager).await;
+    rx.recv().await.unwrap().unwrap();
+    rx.recv().await.unwrap().unwrap();
+    assert_eq!(manager.listen_lag().await.unwrap(), 0);
+
+    // 订阅者断开后监听任务结束并注销
+    drop(rx);
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert_eq!(manager.listen_subscribers(), 0);
+}
+
+fn tyr() -> abi::Actor {
+    abi::Actor::user("tyrid")
+}
+
+fn admin() -> abi::Audit {
+    abi::Actor::user("admin").into()
+}
+
+fn tyr_pending_reservation() -> Reservation {
+    abi::Reservation::new_pending(
+        "tyrid",
+        "ocean-view-room-713",
+        "2022-12-25T15:00:00-0700".parse().unwrap(),
+        "2022-12-28T12:00:00-0700".parse().unwrap(),
+        "I'll arrive at 3pm.",
+    )
+}
+
+async fn make_tyr_reservation(manager: &impl Rsvp) -> Reservation {
+    make_reservation(
+        manager,
+        "tyrid",
+        "ocean-view-room-713",
+        "2022-12-25T15:00:00-0700",
+        "2022-12-28T12:00:00-0700",
+        "I'll arrive at 3pm. Please help to upgrade to execuitive room if possible.",
+    )
+    .await
+}
+
+async fn make_alice_reservation(manager: &impl Rsvp) -> Reservation {
+    make_reservation(
+        manager,
+        "aliceid",
+        "ixia-test-1",
+        "2023-01-25T15:00:00-0700",
+        "2023-02-25T12:00:00-0700",
+        "I need to book this for xyz project for a month.",
+    )
+    .await
+}
+
+async fn make_reservation(
+    manager: &impl Rsvp,
+    uid: &str,
+    rid: &str,
+    start: &str,
+    end: &str,
+    note: &str,
+) -> Reservation {
+    let rsvp =
+        abi::Reservation::new_pending(uid, rid, start.parse().unwrap(), end.parse().unwrap(), note);
+    let audit = abi::Actor::user(uid).into();
+    manager.reserve(rsvp, &audit).await.unwrap()
+}
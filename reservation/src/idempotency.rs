@@ -0,0 +1,173 @@
+use crate::{manager::Latency, IdempotentRsvp, ReservationManager, ReservationStore, Rsvp};
+use abi::Error;
+use async_trait::async_trait;
+use chrono::Utc;
+use prost::Message;
+use std::future::Future;
+use tracing::{field::Empty, instrument, Span};
+
+// 幂等键的最大长度(与数据库字段长度保持一致)
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 128;
+
+impl<S: ReservationStore> ReservationManager<S> {
+    // 以幂等的方式执行变更操作:
+    // 1. 未携带幂等键时直接执行
+    // 2. 首次携带幂等键时执行并保存响应
+    // 3. 相同幂等键且相同请求时直接返回保存的响应，请求不同时返回错误
+    #[instrument(level = "debug", skip_all, fields(op = op, replayed = Empty, latency_ms = Empty))]
+    async fn idempotent<Req, Resp, F, Fut>(
+        &self,
+        op: &str,
+        key: &str,
+        req: &Req,
+        f: F,
+    ) -> Result<Resp, Error>
+    where
+        Req: Message,
+        Resp: Message + Default,
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = Result<Resp, Error>> + Send,
+    {
+        let _latency = Latency::start();
+        if key.is_empty() {
+            return f().await;
+        }
+        if key.len() > MAX_IDEMPOTENCY_KEY_LEN {
+            return Err(Error::InvalidIdempotencyKey(key.to_string()));
+        }
+
+        let request = req.encode_to_vec();
+        let lease_expires_at = Utc::now() + self.idempotency_lease;
+
+        // 抢占幂等键，已经过期的记录可以被重新抢占。处理中只持有较短的租约，
+        // 执行完成后才按有效期保存响应
+        let claimed = self
+            .store
+            .claim_idempotency_key(key, op, &request, lease_expires_at)
+            .await?;
+
+        Span::current().record("replayed", !claimed);
+        if !claimed {
+            return self.replay(op, key, &request).await;
+        }
+
+        match f().await {
+            Ok(resp) => {
+                let expires_at = Utc::now() + self.idempotency_ttl;
+                self.store
+                    .complete_idempotency_key(key, &resp.encode_to_vec(), expires_at)
+                    .await?;
+                Ok(resp)
+            }
+            Err(e) => {
+                // 执行失败时释放幂等键，允许客户端重试
+                self.store.release_idempotency_key(key).await?;
+                Err(e)
+            }
+        }
+    }
+
+    // 重放已保存的响应
+    async fn replay<Resp>(&self, op: &str, key: &str, request: &[u8]) -> Result<Resp, Error>
+    where
+        Resp: Message + Default,
+    {
+        let record = self
+            .store
+            .get_idempotency_key(key)
+            .await?
+            .ok_or_else(|| Error::IdempotencyKeyInFlight(key.to_string()))?;
+
+        if record.op != op || record.request != request {
+            return Err(Error::IdempotencyKeyReused(key.to_string()));
+        }
+
+        match record.response {
+            Some(response) => Resp::decode(response.as_slice()).map_err(|_| Error::Unknown),
+            None => Err(Error::IdempotencyKeyInFlight(key.to_string())),
+        }
+    }
+}
+
+// 请求中的期望版本为0时表示不做版本校验
+fn expected_version(version: i64) -> Option<i64> {
+    (version > 0).then_some(version)
+}
+
+#[async_trait]
+impl<S: ReservationStore> IdempotentRsvp for ReservationManager<S> {
+    async fn reserve_once(
+        &self,
+        req: abi::ReserveRequest,
+        actor: abi::Actor,
+    ) -> Result<abi::ReserveResponse, Error> {
+        let audit = abi::Audit::new(actor, req.reason.clone());
+        self.idempotent("reserve", &req.idempotency_key, &req, || async {
+            let rsvp = req.reservation.clone().unwrap_or_default();
+            let rsvp = self.reserve(rsvp, &audit).await?;
+            Ok(abi::ReserveResponse {
+                reservation: Some(rsvp),
+            })
+        })
+        .await
+    }
+
+    async fn confirm_once(
+        &self,
+        req: abi::ConfirmRequest,
+        actor: abi::Actor,
+    ) -> Result<abi::ConfirmResponse, Error> {
+        let audit = abi::Audit::new(actor, req.reason.clone());
+        self.idempotent("confirm", &req.idempotency_key, &req, || async {
+            let id = self.resolve_id(req.id, &req.public_id).await?;
+            let rsvp = self
+                .change_status(id, expected_version(req.expected_version), &audit)
+                .await?;
+            Ok(abi::ConfirmResponse {
+                reservation: Some(rsvp),
+            })
+        })
+        .await
+    }
+
+    async fn update_once(
+        &self,
+        req: abi::UpdateRequest,
+        actor: abi::Actor,
+    ) -> Result<abi::UpdateResponse, Error> {
+        let audit = abi::Audit::new(actor, req.reason.clone());
+        self.idempotent("update", &req.idempotency_key, &req, || async {
+            let id = self.resolve_id(req.id, &req.public_id).await?;
+            let rsvp = self
+                .update_note(
+                    id,
+                    req.note.clone(),
+                    expected_version(req.expected_version),
+                    &audit,
+                )
+                .await?;
+            Ok(abi::UpdateResponse {
+                reservation: Some(rsvp),
+            })
+        })
+        .await
+    }
+
+    async fn cancel_once(
+        &self,
+        req: abi::CancelRequest,
+        actor: abi::Actor,
+    ) -> Result<abi::CancelResponse, Error> {
+        let audit = abi::Audit::new(actor, req.reason.clone());
+        self.idempotent("cancel", &req.idempotency_key, &req, || async {
+            let id = self.resolve_id(req.id, &req.public_id).await?;
+            let rsvp = self
+                .delete(id, expected_version(req.expected_version), &audit)
+                .await?;
+            Ok(abi::CancelResponse {
+                reservation: Some(rsvp),
+            })
+        })
+        .await
+    }
+}
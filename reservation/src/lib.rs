@@ -1,30 +1,94 @@
 /*开始实现服务入口 */
+#[cfg(test)]
+mod conformance;
+mod idempotency;
 mod manager;
+mod metrics;
+mod migrate;
+#[cfg(test)]
 mod sqlx_tester;
+mod store;
 
 use abi::FilterPager;
 use async_trait::async_trait;
-use sqlx::PgPool;
+pub use metrics::*;
+pub use migrate::*;
+#[cfg(test)]
 use sqlx_tester::*;
+pub use store::*;
 use tokio::sync::mpsc;
+use uuid::Uuid;
 
-// 定义业务接口
+// 定义业务接口(所有变更操作都需要提供审计信息)
 #[async_trait]
-pub trait Rsvp {
+pub trait Rsvp: Sync {
     // 创建一个预定
-    async fn reserve(&self, req: abi::Reservation) -> Result<abi::Reservation, abi::Error>;
+    async fn reserve(
+        &self,
+        req: abi::Reservation,
+        audit: &abi::Audit,
+    ) -> Result<abi::Reservation, abi::Error>;
+    // 批量创建预定(例如从日历导入)，在一个事务中插入，返回每条预定的结果
+    // 不合法或时间冲突的预定不影响其它预定；commit为false时只做检查，不会创建任何预定
+    async fn reserve_all(
+        &self,
+        rsvps: Vec<abi::Reservation>,
+        audit: &abi::Audit,
+        commit: bool,
+    ) -> Result<Vec<Result<abi::Reservation, abi::Error>>, abi::Error>;
+    // 批量导入预定(例如在环境之间迁移)，在一个事务中按顺序写入，返回每条预定的结果
+    // upsert为false时总是创建新的预定，忽略ID和公开ID；为true时按ID覆盖已有的预定，ID不存在时
+    // 使用文件中的ID和公开ID创建。ID或公开ID被其它租户使用时与时间冲突一样返回ConflictReservation，
+    // 不合法或冲突的预定不影响其它预定
+    async fn import_all(
+        &self,
+        rsvps: Vec<abi::Reservation>,
+        upsert: bool,
+        audit: &abi::Audit,
+    ) -> Result<Vec<Result<abi::Reservation, abi::Error>>, abi::Error>;
     // 修改预定状态(如果当前预定是pending状态，可以修改为确认或者取消状态)
-    async fn change_status(&self, id: abi::ReservationId) -> Result<abi::Reservation, abi::Error>;
+    // version不为空时，只有当前版本与之一致才会修改
+    async fn change_status(
+        &self,
+        id: abi::ReservationId,
+        version: Option<i64>,
+        audit: &abi::Audit,
+    ) -> Result<abi::Reservation, abi::Error>;
     // 修改预定备注
     async fn update_note(
         &self,
         id: abi::ReservationId,
         note: String,
+        version: Option<i64>,
+        audit: &abi::Audit,
     ) -> Result<abi::Reservation, abi::Error>;
     // 删除预定
-    async fn delete(&self, id: abi::ReservationId) -> Result<abi::Reservation, abi::Error>;
+    async fn delete(
+        &self,
+        id: abi::ReservationId,
+        version: Option<i64>,
+        audit: &abi::Audit,
+    ) -> Result<abi::Reservation, abi::Error>;
     // 获取指定预定
     async fn get(&self, id: abi::ReservationId) -> Result<abi::Reservation, abi::Error>;
+    // 根据公开ID获取预定的内部ID
+    async fn resolve(&self, public_id: Uuid) -> Result<abi::ReservationId, abi::Error>;
+    // 解析请求中的预定标识，公开ID不为空时优先使用公开ID
+    async fn resolve_id(
+        &self,
+        id: abi::ReservationId,
+        public_id: &str,
+    ) -> Result<abi::ReservationId, abi::Error> {
+        if public_id.is_empty() {
+            return Ok(id);
+        }
+        self.resolve(abi::parse_public_id(public_id)?).await
+    }
+    // 获取预定的变更历史(按变更顺序排列)
+    async fn history(
+        &self,
+        id: abi::ReservationId,
+    ) -> Result<Vec<abi::ReservationChange>, abi::Error>;
     // 条件查询预定
     async fn query(
         &self,
@@ -34,10 +98,49 @@ pub trait Rsvp {
         &self,
         query: abi::ReservationFilter,
     ) -> Result<(FilterPager, Vec<abi::Reservation>), abi::Error>;
+    // 监听订阅之后产生的预定变更(可以按变更的字段过滤)
+    async fn listen(
+        &self,
+        req: abi::ListenRequest,
+    ) -> mpsc::Receiver<Result<abi::ListenResponse, abi::Error>>;
+}
+
+// 定义携带幂等键的变更请求接口(客户端重试时返回首次请求的响应)
+// actor为发起请求的用户或服务主体，请求中的reason会一起写入审计记录
+#[async_trait]
+pub trait IdempotentRsvp {
+    // 幂等地创建预定
+    async fn reserve_once(
+        &self,
+        req: abi::ReserveRequest,
+        actor: abi::Actor,
+    ) -> Result<abi::ReserveResponse, abi::Error>;
+    // 幂等地确认预定
+    async fn confirm_once(
+        &self,
+        req: abi::ConfirmRequest,
+        actor: abi::Actor,
+    ) -> Result<abi::ConfirmResponse, abi::Error>;
+    // 幂等地修改预定备注
+    async fn update_once(
+        &self,
+        req: abi::UpdateRequest,
+        actor: abi::Actor,
+    ) -> Result<abi::UpdateResponse, abi::Error>;
+    // 幂等地取消预定
+    async fn cancel_once(
+        &self,
+        req: abi::CancelRequest,
+        actor: abi::Actor,
+    ) -> Result<abi::CancelResponse, abi::Error>;
 }
 
-// 定义一个预定管理实现类
-#[derive(Debug)]
-pub struct ReservationManager {
-    pool: PgPool,
+// 定义一个预定管理实现类，业务逻辑与存储后端(默认为postgres)分离
+#[derive(Debug, Clone)]
+pub struct ReservationManager<S = PgStore> {
+    store: S,
+    // 幂等键的有效期
+    idempotency_ttl: chrono::Duration,
+    // 处理中的幂等键的租约，进程崩溃后客户端在租约过期后即可重试
+    idempotency_lease: chrono::Duration,
 }
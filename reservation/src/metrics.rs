@@ -0,0 +1,38 @@
+use metrics::counter;
+
+// 预定管理产生的指标，通过metrics门面记录，由服务安装具体的exporter，未安装时不做任何事
+pub const RESERVATIONS_CREATED: &str = "rsvp_reservations_created_total";
+pub const RESERVATIONS_CONFIRMED: &str = "rsvp_reservations_confirmed_total";
+pub const RESERVATIONS_CANCELLED: &str = "rsvp_reservations_cancelled_total";
+pub const RESERVATION_CONFLICTS: &str = "rsvp_reservation_conflicts_total";
+
+// 资源ID中的类型部分，作为指标的标签(资源ID本身的基数太高):
+// 去掉末尾的编号，例如 ocean-view-room-713 的类型为 ocean-view-room
+pub fn resource_type(resource_id: &str) -> &str {
+    let kind = resource_id
+        .trim_end_matches(|c: char| c.is_ascii_digit())
+        .trim_end_matches(['-', '_', ':', '/', '.', '#']);
+    if kind.is_empty() {
+        "unknown"
+    } else {
+        kind
+    }
+}
+
+pub(crate) fn increment(name: &'static str, resource_id: &str) {
+    counter!(name, "resource_type" => resource_type(resource_id).to_string()).increment(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resource_type_should_strip_trailing_number() {
+        assert_eq!(resource_type("ocean-view-room-713"), "ocean-view-room");
+        assert_eq!(resource_type("room:101"), "room");
+        assert_eq!(resource_type("projector"), "projector");
+        assert_eq!(resource_type("713"), "unknown");
+        assert_eq!(resource_type(""), "unknown");
+    }
+}
@@ -0,0 +1,191 @@
+use abi::Error;
+use sqlx::{
+    migrate::{Migrate, Migrator},
+    PgPool,
+};
+
+// 编译时嵌入postgres的迁移脚本，部署时不再需要sqlx命令行工具
+pub static MIGRATOR: Migrator = sqlx::migrate!("../migrations");
+
+// 按租户隔离的表，行级安全策略由迁移创建
+const TENANT_TABLES: [&str; 3] = [
+    "rsvp.reservations",
+    "rsvp.reservation_changes",
+    "rsvp.idempotency_keys",
+];
+
+// 迁移脚本的执行状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+// 执行所有尚未执行的迁移
+pub async fn migrate_up(pool: &PgPool) -> Result<(), Error> {
+    MIGRATOR.run(pool).await?;
+    Ok(())
+}
+
+// 回滚迁移: 指定target时回滚所有版本大于target的迁移，否则只回滚最近一次迁移
+// 返回回滚后的版本，没有已执行的迁移时返回None
+pub async fn migrate_down(pool: &PgPool, target: Option<i64>) -> Result<Option<i64>, Error> {
+    let mut applied = applied_versions(pool).await?;
+    let Some(latest) = applied.pop() else {
+        return Ok(None);
+    };
+    let target = target.unwrap_or_else(|| applied.last().copied().unwrap_or(0));
+    if target < latest {
+        MIGRATOR.undo(pool, target).await?;
+    }
+    Ok(Some(target))
+}
+
+// 列出所有嵌入的迁移及其是否已经执行
+pub async fn migration_status(pool: &PgPool) -> Result<Vec<MigrationStatus>, Error> {
+    let applied = applied_versions(pool).await?;
+    Ok(MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| MigrationStatus {
+            version: m.version,
+            description: m.description.to_string(),
+            applied: applied.contains(&m.version),
+        })
+        .collect())
+}
+
+// 开启或关闭租户表的行级安全，需要表的所有者权限
+// 开启后只有设置了rsvp.tenant_id的事务才能读写对应租户的数据，FORCE使策略对表的所有者同样生效
+pub async fn set_row_level_security(pool: &PgPool, enabled: bool) -> Result<(), Error> {
+    let action = if enabled {
+        "ENABLE ROW LEVEL SECURITY, FORCE ROW LEVEL SECURITY"
+    } else {
+        "NO FORCE ROW LEVEL SECURITY, DISABLE ROW LEVEL SECURITY"
+    };
+    let mut tx = pool.begin().await?;
+    for table in TENANT_TABLES {
+        sqlx::query(&format!("ALTER TABLE {} {}", table, action))
+            .execute(&mut tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+// 已执行的迁移版本(升序)
+async fn applied_versions(pool: &PgPool) -> Result<Vec<i64>, Error> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let mut versions = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| m.version)
+        .collect::<Vec<_>>();
+    versions.sort_unstable();
+    Ok(versions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReservationManager, Rsvp, TestPg};
+    use sqlx::Executor;
+
+    #[test]
+    fn migrator_should_only_embed_postgres_migrations() {
+        let versions = MIGRATOR.iter().map(|m| m.version).collect::<Vec<_>>();
+        assert!(versions.contains(&20221008222343));
+        assert!(versions.contains(&20231226093410));
+        // sqlite和mysql的迁移在子目录中，不会被嵌入
+        assert!(!versions.contains(&20231228100000));
+        assert!(!versions.contains(&20231230100000));
+    }
+
+    #[tokio::test]
+    async fn migrate_down_should_revert_latest_migration() {
+        let tdb = TestPg::default();
+        let pool = tdb.get_pool().await;
+        let status = migration_status(&pool).await.unwrap();
+        assert!(status.iter().all(|m| m.applied));
+
+        let latest = status.last().unwrap().version;
+        let previous = status[status.len() - 2].version;
+        assert_eq!(migrate_down(&pool, None).await.unwrap(), Some(previous));
+        let status = migration_status(&pool).await.unwrap();
+        assert!(!status.iter().find(|m| m.version == latest).unwrap().applied);
+
+        migrate_up(&pool).await.unwrap();
+        let status = migration_status(&pool).await.unwrap();
+        assert!(status.iter().all(|m| m.applied));
+    }
+
+    #[tokio::test]
+    async fn row_level_security_should_hide_other_tenants() {
+        let tdb = TestPg::default();
+        let pool = tdb.get_pool().await;
+        let manager = ReservationManager::new(pool.clone());
+        let rsvp = abi::Reservation::new_pending(
+            "tyrid",
+            "ocean-view-room-713",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        let audit = abi::Actor::user("tyrid").into();
+        manager
+            .with_tenant("acme")
+            .unwrap()
+            .reserve(rsvp, &audit)
+            .await
+            .unwrap();
+        set_row_level_security(&pool, true).await.unwrap();
+
+        // 超级用户不受行级安全限制，使用一个普通角色读取
+        let role = format!("rsvp_rls_{}", tdb.dbname);
+        sqlx::query(&format!("CREATE ROLE {} NOLOGIN", role))
+            .execute(&pool)
+            .await
+            .unwrap();
+        // 多条语句需要使用简单查询协议执行
+        pool.execute(
+            format!(
+                "GRANT USAGE ON SCHEMA rsvp TO {0}; GRANT SELECT ON ALL TABLES IN SCHEMA rsvp TO {0}",
+                role
+            )
+            .as_str(),
+        )
+        .await
+        .unwrap();
+
+        let count = |tenant: &'static str| {
+            let (pool, role) = (pool.clone(), role.clone());
+            async move {
+                let mut tx = pool.begin().await.unwrap();
+                sqlx::query(&format!("SET LOCAL ROLE {}", role))
+                    .execute(&mut tx)
+                    .await
+                    .unwrap();
+                sqlx::query("SELECT set_config('rsvp.tenant_id', $1, true)")
+                    .bind(tenant)
+                    .execute(&mut tx)
+                    .await
+                    .unwrap();
+                let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM rsvp.reservations")
+                    .fetch_one(&mut tx)
+                    .await
+                    .unwrap();
+                count
+            }
+        };
+        assert_eq!(count("acme").await, 1);
+        assert_eq!(count("globex").await, 0);
+
+        // 角色属于整个集群，不会随测试数据库一起删除
+        pool.execute(format!("DROP OWNED BY {0}; DROP ROLE {0}", role).as_str())
+            .await
+            .unwrap();
+    }
+}
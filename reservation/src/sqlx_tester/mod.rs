@@ -1,7 +1,22 @@
-// mod mysql;
+mod mysql;
 mod postgres;
-// mod sqlite;
+mod sqlite;
 
-// pub use mysql::*;
+use anyhow::{anyhow, Result};
+use std::{future::Future, thread};
+use tokio::runtime::Runtime;
+
+pub use mysql::*;
 pub use postgres::*;
-// pub use sqlite::*;
+pub use sqlite::*;
+
+// 在独立的线程和运行时中执行异步任务，避免阻塞调用者所在的运行时
+fn block_on_thread<F, Fut>(f: F) -> Result<()>
+where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<()>>,
+{
+    thread::spawn(move || Runtime::new()?.block_on(f()))
+        .join()
+        .map_err(|_| anyhow!("database thread panicked"))?
+}
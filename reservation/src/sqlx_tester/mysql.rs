@@ -1,142 +1,118 @@
-// use sqlx::{
-//     migrate::{MigrationSource, Migrator},
-//     Connection, Executor, MySqlConnection, MySqlPool,
-// };
-// use std::{path::Path, thread};
-// use tokio::runtime::Runtime;
-// use uuid::Uuid;
-
-// #[derive(Debug)]
-// pub struct TestMysql {
-//     pub host: String,
-//     pub port: u16,
-//     pub user: String,
-//     pub password: String,
-//     pub dbname: String,
-// }
-
-// impl TestMysql {
-//     pub fn new<S>(
-//         host: impl Into<String>,
-//         port: u16,
-//         user: impl Into<String>,
-//         password: impl Into<String>,
-//         migrations: S,
-//     ) -> Self
-//     where
-//         S: MigrationSource<'static> + Send + Sync + 'static,
-//     {
-//         let host = host.into();
-//         let user = user.into();
-//         let password = password.into();
-
-//         let uuid = Uuid::new_v4();
-//         let dbname = format!("test_{}", uuid);
-//         let dbname_cloned = dbname.clone();
-
-//         let tdb = Self {
-//             host,
-//             port,
-//             user,
-//             password,
-//             dbname,
-//         };
-
-//         let server_url = tdb.server_url();
-//         let url = tdb.url();
-
-//         // create database dbname
-//         thread::spawn(move || {
-//             let rt = Runtime::new().unwrap();
-//             rt.block_on(async move {
-//                 // use server url to create database
-//                 let mut conn = MySqlConnection::connect(&server_url).await.unwrap();
-//                 conn.execute(format!(r#"CREATE DATABASE "{}""#, dbname_cloned).as_str())
-//                     .await
-//                     .unwrap();
-
-//                 // now connect to test database for migration
-//                 let mut conn = MySqlConnection::connect(&url).await.unwrap();
-//                 let m = Migrator::new(migrations).await.unwrap();
-//                 m.run(&mut conn).await.unwrap();
-//             });
-//         })
-//         .join()
-//         .expect("failed to create database");
-
-//         tdb
-//     }
-
-//     pub fn server_url(&self) -> String {
-//         if self.password.is_empty() {
-//             format!("mysql://{}@{}:{}", self.user, self.host, self.port)
-//         } else {
-//             format!(
-//                 "mysql://{}:{}@{}:{}",
-//                 self.user, self.password, self.host, self.port
-//             )
-//         }
-//     }
-
-//     pub fn url(&self) -> String {
-//         format!("{}/{}", self.server_url(), self.dbname)
-//     }
-
-//     pub async fn get_pool(&self) -> MySqlPool {
-//         MySqlPool::connect(&self.url()).await.unwrap()
-//     }
-// }
-
-// impl Drop for TestMysql {
-//     fn drop(&mut self) {
-//         let server_url = self.server_url();
-//         let dbname = self.dbname.clone();
-//         thread::spawn(move || {
-//             let rt = Runtime::new().unwrap();
-//             rt.block_on(async move {
-//                 let mut conn = MySqlConnection::connect(&server_url).await.unwrap();
-//                 // TODO: terminate existing connections
-//                 conn.execute(format!(r#"DROP DATABASE "{}""#, dbname).as_str())
-//                     .await
-//                     .expect("Error while querying the drop database");
-//             });
-//         })
-//         .join()
-//         .expect("failed to drop database");
-//     }
-// }
-
-// impl Default for TestMysql {
-//     fn default() -> Self {
-//         Self::new(
-//             "localhost",
-//             5432,
-//             "mysql",
-//             "mysql",
-//             Path::new("./migrations"),
-//         )
-//     }
-// }
-
-// #[cfg(test)]
-// mod tests {
-//     use crate::mysql::TestMysql;
-
-//     #[tokio::test]
-//     async fn test_mysql_should_create_and_drop() {
-//         let tdb = TestMysql::default();
-//         let pool = tdb.get_pool().await;
-//         // insert todo
-//         sqlx::query("INSERT INTO todos (title) VALUES ('test')")
-//             .execute(&pool)
-//             .await
-//             .unwrap();
-//         // get todo
-//         let (id, title) = sqlx::query_as::<_, (i32, String)>("SELECT id, title FROM todos")
-//             .fetch_one(&pool)
-//             .await
-//             .unwrap();
-//         assert_eq!(id, 1);
-//         assert_eq!(title, "test");
-//     }
-// }
+use super::block_on_thread;
+use anyhow::Result;
+use sqlx::{
+    migrate::{MigrationSource, Migrator},
+    Connection, Executor, MySqlConnection, MySqlPool,
+};
+use std::{env, path::Path};
+use uuid::Uuid;
+
+// 测试数据库服务地址的环境变量，未设置时使用本地数据库
+const TEST_MYSQL_URL: &str = "TEST_MYSQL_URL";
+const DEFAULT_SERVER_URL: &str = "mysql://root@localhost:3306";
+
+#[derive(Debug)]
+pub struct TestMysql {
+    pub server_url: String,
+    pub dbname: String,
+}
+
+impl TestMysql {
+    pub fn new<S>(server_url: String, migrations: S) -> Self
+    where
+        S: MigrationSource<'static> + Send + Sync + 'static,
+    {
+        Self::try_new(server_url, migrations).expect("failed to create database")
+    }
+
+    // 使用TEST_MYSQL_URL环境变量指定的数据库服务
+    pub fn from_env<S>(migrations: S) -> Self
+    where
+        S: MigrationSource<'static> + Send + Sync + 'static,
+    {
+        let server_url = env::var(TEST_MYSQL_URL).unwrap_or_else(|_| DEFAULT_SERVER_URL.into());
+        Self::new(server_url, migrations)
+    }
+
+    pub fn try_new<S>(server_url: String, migrations: S) -> Result<Self>
+    where
+        S: MigrationSource<'static> + Send + Sync + 'static,
+    {
+        // 每个测试使用一个独立的数据库，互不影响
+        let dbname = format!("test_{}", Uuid::new_v4().simple());
+        let dbname_cloned = dbname.clone();
+
+        let tdb = Self { server_url, dbname };
+        let server_url = tdb.server_url();
+        let url = tdb.url();
+
+        // 在独立的线程中执行migrate迁移操作，错误会返回给调用者
+        block_on_thread(move || async move {
+            // 连接mysql服务并执行创建数据库操作，mysql使用反引号引用标识符
+            let mut conn = MySqlConnection::connect(&server_url).await?;
+            conn.execute(format!("CREATE DATABASE `{}`", dbname_cloned).as_str())
+                .await?;
+
+            // 连接测试数据库并执行迁移代码
+            let mut conn = MySqlConnection::connect(&url).await?;
+            let m = Migrator::new(migrations).await?;
+            m.run(&mut conn).await?;
+            Ok(())
+        })?;
+
+        Ok(tdb)
+    }
+
+    pub fn server_url(&self) -> String {
+        self.server_url.clone()
+    }
+
+    pub fn url(&self) -> String {
+        format!("{}/{}", self.server_url, self.dbname)
+    }
+
+    pub async fn get_pool(&self) -> MySqlPool {
+        MySqlPool::connect(&self.url()).await.unwrap()
+    }
+}
+
+impl Drop for TestMysql {
+    fn drop(&mut self) {
+        let server_url = self.server_url();
+        let dbname = self.dbname.clone();
+        let ret = block_on_thread(move || async move {
+            let mut conn = MySqlConnection::connect(&server_url).await?;
+            conn.execute(format!("DROP DATABASE IF EXISTS `{}`", dbname).as_str())
+                .await?;
+            Ok(())
+        });
+        // drop中不能返回错误，也不能在展开时再次panic
+        if let Err(e) = ret {
+            eprintln!("failed to drop database {}: {}", self.dbname, e);
+        }
+    }
+}
+
+impl Default for TestMysql {
+    fn default() -> Self {
+        Self::from_env(Path::new("../migrations/mysql"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::TestMysql;
+
+    #[tokio::test]
+    #[ignore = "需要mysql服务"]
+    async fn test_mysql_should_create_and_drop() {
+        let tdb = TestMysql::default();
+        let pool = tdb.get_pool().await;
+        // 迁移之后可以直接使用预定表
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM reservations")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+}
@@ -1,72 +1,88 @@
-// use sqlx::{
-//     migrate::{MigrationSource, Migrator},
-//     Connection, SqliteConnection, SqlitePool,
-// };
-// use std::{path::Path, thread};
-// use tokio::runtime::Runtime;
+use sqlx::{
+    migrate::{MigrationSource, Migrator},
+    sqlite::SqliteConnectOptions,
+    Connection, SqliteConnection, SqlitePool,
+};
+use std::{fs, path::Path, path::PathBuf, thread};
+use tokio::runtime::Runtime;
+use uuid::Uuid;
 
-// #[derive(Debug)]
-// pub struct TestSqlite;
+#[derive(Debug)]
+pub struct TestSqlite {
+    pub path: PathBuf,
+}
 
-// impl TestSqlite {
-//     pub fn new<S>(migrations: S) -> Self
-//     where
-//         S: MigrationSource<'static> + Send + Sync + 'static,
-//     {
-//         let tdb = Self {};
-//         let url = tdb.url();
+impl TestSqlite {
+    pub fn new<S>(migrations: S) -> Self
+    where
+        S: MigrationSource<'static> + Send + Sync + 'static,
+    {
+        // 每个测试使用一个独立的临时数据库文件，互不影响
+        let path = std::env::temp_dir().join(format!("rsvp-test-{}.db", Uuid::new_v4()));
+        let tdb = Self { path };
+        let options = tdb.options();
 
-//         // create database dbname
-//         thread::spawn(move || {
-//             let rt = Runtime::new().unwrap();
-//             rt.block_on(async move {
-//                 // now connect to test database for migration
-//                 let mut conn = SqliteConnection::connect(&url).await.unwrap();
-//                 let m = Migrator::new(migrations).await.unwrap();
-//                 m.run(&mut conn).await.unwrap();
-//             });
-//         })
-//         .join()
-//         .expect("failed to create database");
+        // 启动一个线程执行migrate迁移操作
+        thread::spawn(move || {
+            let rt = Runtime::new().unwrap();
+            rt.block_on(async move {
+                let mut conn = SqliteConnection::connect_with(&options).await.unwrap();
+                let m = Migrator::new(migrations).await.unwrap();
+                m.run(&mut conn).await.unwrap();
+            });
+        })
+        .join()
+        .expect("failed to create database");
 
-//         tdb
-//     }
+        tdb
+    }
 
-//     pub fn url(&self) -> String {
-//         "sqlite::memory:".to_owned()
-//     }
+    pub async fn get_pool(&self) -> SqlitePool {
+        SqlitePool::connect_with(self.options()).await.unwrap()
+    }
 
-//     pub async fn get_pool(&self) -> SqlitePool {
-//         SqlitePool::connect(&self.url()).await.unwrap()
-//     }
-// }
+    fn options(&self) -> SqliteConnectOptions {
+        SqliteConnectOptions::new()
+            .filename(&self.path)
+            .create_if_missing(true)
+    }
+}
 
-// impl Default for TestSqlite {
-//     fn default() -> Self {
-//         Self::new(Path::new("./migrations"))
-//     }
-// }
+impl Drop for TestSqlite {
+    fn drop(&mut self) {
+        // 删除数据库文件以及可能存在的日志文件
+        for suffix in ["", "-wal", "-shm", "-journal"] {
+            let mut path = self.path.clone().into_os_string();
+            path.push(suffix);
+            let _ = fs::remove_file(path);
+        }
+    }
+}
 
-// #[cfg(test)]
-// mod tests {
-//     use crate::TestSqlite;
+impl Default for TestSqlite {
+    fn default() -> Self {
+        Self::new(Path::new("../migrations/sqlite"))
+    }
+}
 
-//     #[tokio::test]
-//     async fn test_sqlite_should_create_and_drop() {
-//         let tdb = TestSqlite::default();
-//         let pool = tdb.get_pool().await;
-//         println!("!!!");
-//         // insert todo
-//         sqlx::query("INSERT INTO todos (title) VALUES ('test')")
-//             .execute(&pool)
-//             .await
-//             .unwrap();
-//         // get todo
-//         let (id, title) = sqlx::query_as::<_, (i32, String)>("SELECT id, title FROM todos")
-//             .fetch_one(&pool)
-//             .await
-//             .unwrap();
-//         assert_eq!(id, 1);
-//         assert_eq!(title, "test");
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use crate::TestSqlite;
+
+    #[tokio::test]
+    async fn test_sqlite_should_create_and_drop() {
+        let tdb = TestSqlite::default();
+        let path = tdb.path.clone();
+        let pool = tdb.get_pool().await;
+        // 迁移之后可以直接使用预定表
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM reservations")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+
+        pool.close().await;
+        drop(tdb);
+        assert!(!path.exists());
+    }
+}
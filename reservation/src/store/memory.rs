@@ -0,0 +1,679 @@
+use super::{
+    all_fields, changed_fields, conflict_error, import_ids, span, to_json, unavailable_error,
+    IdempotencyRecord, IntervalTree, ReservationStore, Subscribers,
+};
+use abi::{
+    convert_to_utc_time, Audit, Error, ReservationId, ReservationStatus, ReservationUpdateType,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, MutexGuard},
+};
+use tokio::sync::{mpsc, watch};
+use uuid::Uuid;
+
+// 定义基于内存的预定存储，语义与postgres存储保持一致，用于测试以及不需要持久化的场景
+#[derive(Debug, Clone)]
+pub struct MemoryStore {
+    state: Arc<Mutex<State>>,
+    // 最新一条变更记录的ID，用于通知监听者
+    changes: Arc<watch::Sender<i64>>,
+    subscribers: Subscribers,
+    tenant: String,
+}
+
+#[derive(Debug, Default, Clone)]
+struct State {
+    next_id: ReservationId,
+    reservations: BTreeMap<ReservationId, abi::Reservation>,
+    // 每个租户的每个资源一棵区间树
+    resources: HashMap<(String, String), IntervalTree>,
+    public_ids: HashMap<Uuid, ReservationId>,
+    changes: Vec<Change>,
+    // (租户, 幂等键) -> (记录, 过期时间)
+    idempotency_keys: HashMap<(String, String), (IdempotencyRecord, DateTime<Utc>)>,
+}
+
+// 定义一条变更记录，对应rsvp.reservation_changes表
+#[derive(Debug, Clone)]
+struct Change {
+    id: i64,
+    op: ReservationUpdateType,
+    old: Option<abi::Reservation>,
+    new: Option<abi::Reservation>,
+    actor: String,
+    reason: String,
+    changed_fields: Vec<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        let (changes, _) = watch::channel(0);
+        Self {
+            state: Arc::new(Mutex::new(State::default())),
+            changes: Arc::new(changes),
+            subscribers: Subscribers::default(),
+            tenant: abi::DEFAULT_TENANT.to_string(),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        lock(&self.state)
+    }
+
+    fn idempotency_key(&self, key: &str) -> (String, String) {
+        (self.tenant.clone(), key.to_string())
+    }
+
+    // 记录变更后通知监听者
+    fn notify(&self, change_id: Option<i64>) {
+        if let Some(id) = change_id {
+            self.changes.send_replace(id);
+        }
+    }
+}
+
+impl State {
+    fn insert(
+        &mut self,
+        tenant: &str,
+        rsvp: abi::Reservation,
+        audit: &Audit,
+    ) -> Result<(abi::Reservation, i64), Error> {
+        self.create(tenant, rsvp, None, Uuid::now_v7(), audit)
+    }
+
+    // 插入预定并记录变更，与同一资源已有的预定时间重叠时返回ConflictReservation
+    // id为空时分配新的ID，否则使用指定的ID(与序列一样，之后分配的ID总是更大)
+    fn create(
+        &mut self,
+        tenant: &str,
+        mut rsvp: abi::Reservation,
+        id: Option<ReservationId>,
+        public_id: Uuid,
+        audit: &Audit,
+    ) -> Result<(abi::Reservation, i64), Error> {
+        rsvp.tenant_id = tenant.to_string();
+        let (start, end) = span(&rsvp);
+        let resource = self
+            .resources
+            .entry((rsvp.tenant_id.clone(), rsvp.resource_id.clone()))
+            .or_default();
+        if let Some(id) = resource.overlap(start, end) {
+            return Err(conflict_error(&rsvp, &self.reservations[&id]));
+        }
+
+        self.next_id = match id {
+            Some(id) => self.next_id.max(id),
+            None => self.next_id + 1,
+        };
+        rsvp.id = id.unwrap_or(self.next_id);
+        rsvp.version = 1;
+        rsvp.public_id = public_id.to_string();
+
+        resource.insert(start, end, rsvp.id);
+        self.public_ids.insert(public_id, rsvp.id);
+        self.reservations.insert(rsvp.id, rsvp.clone());
+        let change_id = self.record(
+            ReservationUpdateType::Create,
+            None,
+            Some(rsvp.clone()),
+            audit,
+            all_fields(),
+        );
+        Ok((rsvp, change_id))
+    }
+
+    // 导入一条预定: 按ID导入且ID已存在时覆盖原来的预定，否则创建新的预定
+    fn import(
+        &mut self,
+        tenant: &str,
+        mut rsvp: abi::Reservation,
+        upsert: bool,
+        audit: &Audit,
+    ) -> Result<(abi::Reservation, Option<i64>), Error> {
+        let (id, public_id) = import_ids(&rsvp, upsert);
+        // 其它租户的ID和公开ID与其它租户的预定冲突一样处理，不透露其内容
+        let foreign = |id: &ReservationId| self.reservations[id].tenant_id != tenant;
+        if let Some(owner) = public_id.and_then(|p| self.public_ids.get(&p)) {
+            if foreign(owner) {
+                return Err(unavailable_error(&rsvp));
+            }
+            if Some(*owner) != id {
+                return Err(Error::InvalidPublicId(rsvp.public_id));
+            }
+        }
+        if id.is_some_and(|id| self.reservations.contains_key(&id) && foreign(&id)) {
+            return Err(unavailable_error(&rsvp));
+        }
+        let Some(old) = id.and_then(|id| self.reservations.get(&id)).cloned() else {
+            let public_id = public_id.unwrap_or_else(Uuid::now_v7);
+            let (rsvp, change_id) = self.create(tenant, rsvp, id, public_id, audit)?;
+            return Ok((rsvp, Some(change_id)));
+        };
+
+        // 原来的时间段不算冲突，先移出区间树，冲突时再放回
+        rsvp.id = old.id;
+        rsvp.tenant_id = old.tenant_id.clone();
+        let (old_start, old_end) = span(&old);
+        let old_key = (old.tenant_id.clone(), old.resource_id.clone());
+        self.resources
+            .entry(old_key.clone())
+            .or_default()
+            .remove(old_start);
+        let (start, end) = span(&rsvp);
+        let resource = self
+            .resources
+            .entry((rsvp.tenant_id.clone(), rsvp.resource_id.clone()))
+            .or_default();
+        if let Some(other) = resource.overlap(start, end) {
+            let err = conflict_error(&rsvp, &self.reservations[&other]);
+            self.resources
+                .entry(old_key)
+                .or_default()
+                .insert(old_start, old_end, old.id);
+            return Err(err);
+        }
+        resource.insert(start, end, rsvp.id);
+
+        rsvp.public_id = match public_id {
+            Some(public_id) => {
+                if let Ok(old_public_id) = Uuid::parse_str(&old.public_id) {
+                    self.public_ids.remove(&old_public_id);
+                }
+                self.public_ids.insert(public_id, rsvp.id);
+                public_id.to_string()
+            }
+            None => old.public_id,
+        };
+        Ok(self.update(rsvp, audit))
+    }
+
+    // 取出租户内的预定，确认其版本与期望一致
+    fn checked(
+        &self,
+        tenant: &str,
+        id: ReservationId,
+        version: Option<i64>,
+    ) -> Result<&abi::Reservation, Error> {
+        let rsvp = self
+            .reservations
+            .get(&id)
+            .filter(|r| r.tenant_id == tenant)
+            .ok_or(Error::NotFound)?;
+        match version {
+            Some(expected) if expected != rsvp.version => Err(Error::VersionMismatch {
+                expected,
+                actual: rsvp.version,
+            }),
+            _ => Ok(rsvp),
+        }
+    }
+
+    // 更新预定并记录变更，与触发器一致: 版本号总会增加，但只有字段发生变化时才记录变更
+    fn update(
+        &mut self,
+        mut rsvp: abi::Reservation,
+        audit: &Audit,
+    ) -> (abi::Reservation, Option<i64>) {
+        let old = self.reservations[&rsvp.id].clone();
+        rsvp.version = old.version + 1;
+        self.reservations.insert(rsvp.id, rsvp.clone());
+
+        let changed_fields = changed_fields(&old, &rsvp);
+        if changed_fields.is_empty() {
+            return (rsvp, None);
+        }
+        let id = self.record(
+            ReservationUpdateType::Update,
+            Some(old),
+            Some(rsvp.clone()),
+            audit,
+            changed_fields,
+        );
+        (rsvp, Some(id))
+    }
+
+    fn record(
+        &mut self,
+        op: ReservationUpdateType,
+        old: Option<abi::Reservation>,
+        new: Option<abi::Reservation>,
+        audit: &Audit,
+        changed_fields: Vec<String>,
+    ) -> i64 {
+        let id = self.changes.len() as i64 + 1;
+        self.changes.push(Change {
+            id,
+            op,
+            old,
+            new,
+            actor: audit.actor.to_string(),
+            reason: audit.reason.clone().unwrap_or_default(),
+            changed_fields,
+            created_at: Utc::now(),
+        });
+        id
+    }
+}
+
+impl Change {
+    fn reservation(&self) -> Option<&abi::Reservation> {
+        self.new.as_ref().or(self.old.as_ref())
+    }
+
+    fn reservation_id(&self) -> ReservationId {
+        self.reservation().map_or(0, |r| r.id)
+    }
+
+    fn tenant_id(&self) -> &str {
+        self.reservation().map_or("", |r| r.tenant_id.as_str())
+    }
+
+    fn to_listen_response(&self) -> abi::ListenResponse {
+        abi::ListenResponse {
+            op: self.op as i32,
+            reservation: self.new.clone().or_else(|| self.old.clone()),
+            changed_fields: self.changed_fields.clone(),
+        }
+    }
+
+    fn to_reservation_change(&self) -> abi::ReservationChange {
+        abi::ReservationChange {
+            id: self.id,
+            reservation_id: self.reservation_id(),
+            op: self.op as i32,
+            actor: self.actor.clone(),
+            reason: self.reason.clone(),
+            changed_at: Some(abi::convert_to_timestamp(&self.created_at)),
+            fields: abi::diff_fields(
+                self.old.as_ref().map(to_json).as_ref(),
+                self.new.as_ref().map(to_json).as_ref(),
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl ReservationStore for MemoryStore {
+    fn with_tenant(&self, tenant: &str) -> Self {
+        Self {
+            tenant: tenant.to_string(),
+            ..self.clone()
+        }
+    }
+
+    async fn insert(
+        &self,
+        rsvp: abi::Reservation,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error> {
+        let (rsvp, change_id) = self.state().insert(&self.tenant, rsvp, audit)?;
+        self.notify(Some(change_id));
+        Ok(rsvp)
+    }
+
+    async fn insert_all(
+        &self,
+        rsvps: Vec<abi::Reservation>,
+        audit: &Audit,
+        commit: bool,
+    ) -> Result<Vec<Result<abi::Reservation, Error>>, Error> {
+        let mut guard = self.state();
+        // 只做检查时在状态的副本上插入，相当于回滚
+        let mut scratch = (!commit).then(|| guard.clone());
+        let state = scratch.as_mut().unwrap_or(&mut guard);
+        let mut last_change = None;
+        let results = rsvps
+            .into_iter()
+            .map(|rsvp| {
+                let (rsvp, change_id) = state.insert(&self.tenant, rsvp, audit)?;
+                last_change = Some(change_id);
+                Ok(rsvp)
+            })
+            .collect();
+        drop(guard);
+
+        if commit {
+            self.notify(last_change);
+        }
+        Ok(results)
+    }
+
+    async fn import(
+        &self,
+        rsvps: Vec<abi::Reservation>,
+        upsert: bool,
+        audit: &Audit,
+    ) -> Result<Vec<Result<abi::Reservation, Error>>, Error> {
+        let mut state = self.state();
+        let mut last_change = None;
+        let results = rsvps
+            .into_iter()
+            .map(|rsvp| {
+                let (rsvp, change_id) = state.import(&self.tenant, rsvp, upsert, audit)?;
+                last_change = change_id.or(last_change);
+                Ok(rsvp)
+            })
+            .collect();
+        drop(state);
+
+        self.notify(last_change);
+        Ok(results)
+    }
+
+    async fn confirm(
+        &self,
+        id: ReservationId,
+        version: Option<i64>,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error> {
+        let mut state = self.state();
+        let mut rsvp = state.checked(&self.tenant, id, version)?.clone();
+        // 只有pending状态的预定可以确认
+        if rsvp.status != ReservationStatus::Pending as i32 {
+            return Err(Error::NotFound);
+        }
+        rsvp.status = ReservationStatus::Confirmed as i32;
+        let (rsvp, change_id) = state.update(rsvp, audit);
+        drop(state);
+
+        self.notify(change_id);
+        Ok(rsvp)
+    }
+
+    async fn update_note(
+        &self,
+        id: ReservationId,
+        note: String,
+        version: Option<i64>,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error> {
+        let mut state = self.state();
+        let mut rsvp = state.checked(&self.tenant, id, version)?.clone();
+        rsvp.note = note;
+        let (rsvp, change_id) = state.update(rsvp, audit);
+        drop(state);
+
+        self.notify(change_id);
+        Ok(rsvp)
+    }
+
+    async fn delete(
+        &self,
+        id: ReservationId,
+        version: Option<i64>,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error> {
+        let mut state = self.state();
+        state.checked(&self.tenant, id, version)?;
+        let rsvp = state.reservations.remove(&id).ok_or(Error::NotFound)?;
+
+        let (start, _) = span(&rsvp);
+        let key = (rsvp.tenant_id.clone(), rsvp.resource_id.clone());
+        if let Some(resource) = state.resources.get_mut(&key) {
+            resource.remove(start);
+        }
+        if let Ok(public_id) = Uuid::parse_str(&rsvp.public_id) {
+            state.public_ids.remove(&public_id);
+        }
+        let change_id = state.record(
+            ReservationUpdateType::Delete,
+            Some(rsvp.clone()),
+            None,
+            audit,
+            all_fields(),
+        );
+        drop(state);
+
+        self.notify(Some(change_id));
+        Ok(rsvp)
+    }
+
+    async fn get(&self, id: ReservationId) -> Result<abi::Reservation, Error> {
+        self.state().checked(&self.tenant, id, None).cloned()
+    }
+
+    async fn resolve(&self, public_id: Uuid) -> Result<ReservationId, Error> {
+        let state = self.state();
+        let id = state.public_ids.get(&public_id).ok_or(Error::NotFound)?;
+        Ok(state.checked(&self.tenant, *id, None)?.id)
+    }
+
+    async fn history(&self, id: ReservationId) -> Result<Vec<abi::ReservationChange>, Error> {
+        Ok(self
+            .state()
+            .changes
+            .iter()
+            .filter(|c| c.reservation_id() == id && c.tenant_id() == self.tenant)
+            .map(Change::to_reservation_change)
+            .collect())
+    }
+
+    async fn query(
+        &self,
+        query: abi::ReservationQuery,
+    ) -> mpsc::Receiver<Result<abi::Reservation, Error>> {
+        // 与SQL一致: 返回完全落在查询区间内的预定，按开始时间排序
+        let start = query.start.as_ref().map(convert_to_utc_time);
+        let end = query.end.as_ref().map(convert_to_utc_time);
+        let mut rsvps: Vec<abi::Reservation> = self
+            .state()
+            .reservations
+            .values()
+            .filter(|r| r.tenant_id == self.tenant)
+            .filter(|r| {
+                query.status == ReservationStatus::Unknown as i32 || r.status == query.status
+            })
+            .filter(|r| matches(r, &query.user_id, &query.resource_id))
+            .filter(|r| within(r, start, end))
+            .cloned()
+            .collect();
+        rsvps.sort_by_key(|r| span(r).0);
+        if query.desc {
+            rsvps.reverse();
+        }
+
+        let (tx, rx) = mpsc::channel(128);
+        tokio::spawn(async move {
+            for rsvp in rsvps {
+                if tx.send(Ok(rsvp)).await.is_err() {
+                    break;
+                }
+            }
+        });
+        rx
+    }
+
+    async fn filter(
+        &self,
+        filter: &abi::ReservationFilter,
+    ) -> Result<(Vec<abi::Reservation>, i64), Error> {
+        let start = filter.start.as_ref().map(convert_to_utc_time);
+        let end = filter.end.as_ref().map(convert_to_utc_time);
+        let state = self.state();
+        let matched = state
+            .reservations
+            .values()
+            .filter(|r| r.tenant_id == self.tenant)
+            .filter(|r| r.status == filter.status)
+            .filter(|r| matches(r, &filter.user_id, &filter.resource_id))
+            .filter(|r| within(r, start, end));
+        let total = matched.clone().count() as i64;
+
+        // 与SQL一致: 从游标处按ID排序，多取一条用于判断是否还有下一页
+        let limit = filter.page_size as usize + 1;
+        let rsvps = match (filter.cursor, filter.desc) {
+            (0, false) => matched.take(limit).cloned().collect(),
+            (0, true) => matched.rev().take(limit).cloned().collect(),
+            (cursor, false) => matched
+                .filter(|r| r.id > cursor)
+                .take(limit)
+                .cloned()
+                .collect(),
+            (cursor, true) => matched
+                .rev()
+                .filter(|r| r.id < cursor)
+                .take(limit)
+                .cloned()
+                .collect(),
+        };
+        Ok((rsvps, total))
+    }
+
+    async fn listen(
+        &self,
+        req: abi::ListenRequest,
+    ) -> mpsc::Receiver<Result<abi::ListenResponse, Error>> {
+        // 先订阅通知再读取游标，避免遗漏两者之间产生的变更
+        let mut notified = self.changes.subscribe();
+        let cursor = *notified.borrow_and_update();
+        let mut subscription = self.subscribers.subscribe(&self.tenant, cursor);
+        let (tx, rx) = mpsc::channel(128);
+
+        // 监听任务持有共享的状态，存储被释放后通知通道关闭，任务随之结束
+        let state = self.state.clone();
+        let tenant = self.tenant.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = tx.closed() => return,
+                    ret = notified.changed() => {
+                        if ret.is_err() {
+                            return;
+                        }
+                    }
+                }
+
+                let changes: Vec<Change> = lock(&state)
+                    .changes
+                    .iter()
+                    .skip(subscription.cursor() as usize)
+                    .cloned()
+                    .collect();
+                for change in changes {
+                    subscription.advance(change.id);
+                    if change.tenant_id() != tenant || !req.matches(&change.changed_fields) {
+                        continue;
+                    }
+                    if tx.send(Ok(change.to_listen_response())).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        rx
+    }
+
+    fn subscribers(&self) -> &Subscribers {
+        &self.subscribers
+    }
+
+    async fn latest_change_id(&self) -> Result<i64, Error> {
+        Ok(self
+            .state()
+            .changes
+            .iter()
+            .rev()
+            .find(|c| c.tenant_id() == self.tenant)
+            .map_or(0, |c| c.id))
+    }
+
+    async fn claim_idempotency_key(
+        &self,
+        key: &str,
+        op: &str,
+        request: &[u8],
+        expires_at: DateTime<Utc>,
+    ) -> Result<bool, Error> {
+        let key = self.idempotency_key(key);
+        let mut state = self.state();
+        // 清理已过期的幂等键，之后仍然存在的幂等键不能被抢占
+        let now = Utc::now();
+        state
+            .idempotency_keys
+            .retain(|_, (_, expires_at)| *expires_at >= now);
+        if state.idempotency_keys.contains_key(&key) {
+            return Ok(false);
+        }
+        let record = IdempotencyRecord {
+            op: op.to_string(),
+            request: request.to_vec(),
+            response: None,
+        };
+        state.idempotency_keys.insert(key, (record, expires_at));
+        Ok(true)
+    }
+
+    async fn get_idempotency_key(&self, key: &str) -> Result<Option<IdempotencyRecord>, Error> {
+        Ok(self
+            .state()
+            .idempotency_keys
+            .get(&self.idempotency_key(key))
+            .map(|(record, _)| record.clone()))
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        key: &str,
+        response: &[u8],
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let key = self.idempotency_key(key);
+        if let Some((record, expires)) = self.state().idempotency_keys.get_mut(&key) {
+            record.response = Some(response.to_vec());
+            *expires = expires_at;
+        }
+        Ok(())
+    }
+
+    async fn release_idempotency_key(&self, key: &str) -> Result<(), Error> {
+        self.state()
+            .idempotency_keys
+            .remove(&self.idempotency_key(key));
+        Ok(())
+    }
+}
+
+// 持有锁时不会panic，锁中毒时直接沿用其中的数据
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// 为空的用户或资源条件会被忽略
+fn matches(rsvp: &abi::Reservation, user_id: &str, resource_id: &str) -> bool {
+    (user_id.is_empty() || rsvp.user_id == user_id)
+        && (resource_id.is_empty() || rsvp.resource_id == resource_id)
+}
+
+// 与SQL一致: 预定完全落在[start, end)内，未指定的一端不限制
+fn within(
+    rsvp: &abi::Reservation,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+) -> bool {
+    let (s, e) = span(rsvp);
+    start.is_none_or(|start| start <= s) && end.is_none_or(|end| e <= end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{conformance::conformance_tests, ReservationManager};
+
+    async fn setup() -> ((), ReservationManager<MemoryStore>) {
+        ((), ReservationManager::in_memory())
+    }
+
+    conformance_tests!(setup);
+}
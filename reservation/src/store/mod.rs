@@ -0,0 +1,539 @@
+mod memory;
+mod mysql;
+mod postgres;
+mod sqlite;
+
+pub use memory::*;
+pub use mysql::*;
+pub use postgres::*;
+pub use sqlite::*;
+
+use abi::{
+    convert_to_timestamp, convert_to_utc_time, Audit, Error, ReservationId, ReservationStatus,
+    ReservationUpdateType,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+use tokio::sync::{mpsc, watch};
+use uuid::Uuid;
+
+// 定义预定的存储接口，校验、幂等等业务逻辑由ReservationManager负责
+// 所有后端都需要保证相同的语义(冲突检测、状态流转、版本号以及变更记录)
+// 存储总是限定在一个租户内，所有读写(包括变更记录和幂等键)都只涉及该租户的数据
+#[async_trait]
+pub trait ReservationStore: Send + Sync + 'static {
+    // 返回共享同一连接池、限定在指定租户内的存储
+    fn with_tenant(&self, tenant: &str) -> Self
+    where
+        Self: Sized;
+    // 插入预定，与同一资源已有的预定时间重叠时返回ConflictReservation
+    async fn insert(
+        &self,
+        rsvp: abi::Reservation,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error>;
+    // 在一个事务中依次插入一批预定，返回每条预定的结果，与已有预定或这批中之前的预定时间重叠时
+    // 对应的结果为ConflictReservation，不影响其它预定；commit为false时只做检查，最后回滚
+    async fn insert_all(
+        &self,
+        rsvps: Vec<abi::Reservation>,
+        audit: &Audit,
+        commit: bool,
+    ) -> Result<Vec<Result<abi::Reservation, Error>>, Error>;
+    // 在一个事务中依次导入一批(已经校验过的)预定，返回每条预定的结果
+    // upsert为false时总是创建新的预定；为true时ID不为0的预定覆盖租户内ID相同的预定(版本号增加)，
+    // ID不存在时使用该ID和公开ID(为空时生成)创建。公开ID被租户内其它预定使用时返回InvalidPublicId；
+    // ID或公开ID被其它租户使用，或与已有预定、这批中之前导入的预定时间重叠时返回ConflictReservation
+    // (不包含其它租户预定的内容)，都不影响其它预定
+    async fn import(
+        &self,
+        rsvps: Vec<abi::Reservation>,
+        upsert: bool,
+        audit: &Audit,
+    ) -> Result<Vec<Result<abi::Reservation, Error>>, Error>;
+    // 将pending状态的预定修改为confirmed
+    async fn confirm(
+        &self,
+        id: ReservationId,
+        version: Option<i64>,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error>;
+    // 修改预定备注
+    async fn update_note(
+        &self,
+        id: ReservationId,
+        note: String,
+        version: Option<i64>,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error>;
+    // 删除预定
+    async fn delete(
+        &self,
+        id: ReservationId,
+        version: Option<i64>,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error>;
+    // 获取指定预定
+    async fn get(&self, id: ReservationId) -> Result<abi::Reservation, Error>;
+    // 根据公开ID获取预定的内部ID
+    async fn resolve(&self, public_id: Uuid) -> Result<ReservationId, Error>;
+    // 获取预定的变更历史(按变更顺序排列)
+    async fn history(&self, id: ReservationId) -> Result<Vec<abi::ReservationChange>, Error>;
+    // 按(已经校验的)条件查询预定，状态为UNKNOWN时返回所有状态的预定
+    async fn query(
+        &self,
+        query: abi::ReservationQuery,
+    ) -> mpsc::Receiver<Result<abi::Reservation, Error>>;
+    // 按(已经规范化的)条件从游标处查询最多page_size + 1条预定，同时返回不考虑游标时的总数
+    async fn filter(
+        &self,
+        filter: &abi::ReservationFilter,
+    ) -> Result<(Vec<abi::Reservation>, i64), Error>;
+    // 监听订阅之后产生的预定变更
+    async fn listen(
+        &self,
+        req: abi::ListenRequest,
+    ) -> mpsc::Receiver<Result<abi::ListenResponse, Error>>;
+    // 所有租户共享的订阅者登记表
+    fn subscribers(&self) -> &Subscribers;
+    // 租户内最新一条变更记录的ID，没有变更时为0
+    async fn latest_change_id(&self) -> Result<i64, Error>;
+
+    // 抢占幂等键(已过期的幂等键可以被重新抢占)，抢占成功返回true
+    // expires_at为处理中的租约，抢占时会顺便清理租户内已经过期的幂等键
+    async fn claim_idempotency_key(
+        &self,
+        key: &str,
+        op: &str,
+        request: &[u8],
+        expires_at: DateTime<Utc>,
+    ) -> Result<bool, Error>;
+    // 获取幂等键对应的请求和响应
+    async fn get_idempotency_key(&self, key: &str) -> Result<Option<IdempotencyRecord>, Error>;
+    // 保存幂等键对应的响应，并将有效期延长到expires_at
+    async fn complete_idempotency_key(
+        &self,
+        key: &str,
+        response: &[u8],
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), Error>;
+    // 释放幂等键，允许客户端重试
+    async fn release_idempotency_key(&self, key: &str) -> Result<(), Error>;
+}
+
+// 定义幂等键保存的记录，response为空表示请求还在处理中
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdempotencyRecord {
+    pub op: String,
+    pub request: Vec<u8>,
+    pub response: Option<Vec<u8>>,
+}
+
+// 记录当前的订阅者及其已经处理到的变更ID，用于统计订阅者数量和变更推送的延迟
+#[derive(Debug, Clone, Default)]
+pub struct Subscribers {
+    inner: Arc<Mutex<SubscriberMap>>,
+}
+
+#[derive(Debug, Default)]
+struct SubscriberMap {
+    next_id: u64,
+    // 订阅者ID -> (租户, 游标)
+    cursors: HashMap<u64, (String, i64)>,
+}
+
+// 一个订阅者的游标，析构时从登记表中移除
+#[derive(Debug)]
+pub(crate) struct Subscription {
+    subscribers: Subscribers,
+    id: u64,
+    cursor: i64,
+}
+
+impl Subscribers {
+    // 登记一个从cursor之后开始接收变更的订阅者
+    pub(crate) fn subscribe(&self, tenant: &str, cursor: i64) -> Subscription {
+        let mut map = self.lock();
+        map.next_id += 1;
+        let id = map.next_id;
+        map.cursors.insert(id, (tenant.to_string(), cursor));
+        Subscription {
+            subscribers: self.clone(),
+            id,
+            cursor,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().cursors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // 每个租户中最落后的订阅者游标
+    pub fn min_cursors(&self) -> HashMap<String, i64> {
+        let mut cursors = HashMap::new();
+        for (tenant, cursor) in self.lock().cursors.values() {
+            cursors
+                .entry(tenant.clone())
+                .and_modify(|c: &mut i64| *c = (*c).min(*cursor))
+                .or_insert(*cursor);
+        }
+        cursors
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SubscriberMap> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Subscription {
+    pub(crate) fn cursor(&self) -> i64 {
+        self.cursor
+    }
+
+    // 处理完一条变更(无论是否推送给订阅者)后前移游标
+    pub(crate) fn advance(&mut self, cursor: i64) {
+        self.cursor = cursor;
+        if let Some(entry) = self.subscribers.lock().cursors.get_mut(&self.id) {
+            entry.1 = cursor;
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.subscribers.lock().cursors.remove(&self.id);
+    }
+}
+
+// 不支持变更通知的数据库，其它进程写入的变更需要定期轮询
+const LISTEN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+fn span(rsvp: &abi::Reservation) -> (DateTime<Utc>, DateTime<Utc>) {
+    let start = rsvp
+        .start
+        .as_ref()
+        .map(convert_to_utc_time)
+        .unwrap_or_default();
+    let end = rsvp
+        .end
+        .as_ref()
+        .map(convert_to_utc_time)
+        .unwrap_or_default();
+    (start, end)
+}
+
+// 按postgres的tstzrange文本格式输出预定时间段
+pub(crate) fn timespan(rsvp: &abi::Reservation) -> String {
+    let (start, end) = span(rsvp);
+    let fmt = "%Y-%m-%d %H:%M:%S%.f+00";
+    format!(r#"["{}","{}")"#, start.format(fmt), end.format(fmt))
+}
+
+// 与to_jsonb(rsvp.reservations)的输出保持一致，用于计算变更的字段
+fn to_json(rsvp: &abi::Reservation) -> Value {
+    let status = ReservationStatus::try_from(rsvp.status).unwrap_or(ReservationStatus::Unknown);
+    json!({
+        "id": rsvp.id,
+        "user_id": rsvp.user_id,
+        "status": status.to_string(),
+        "resource_id": rsvp.resource_id,
+        "timespan": timespan(rsvp),
+        "note": rsvp.note,
+        "version": rsvp.version,
+        "public_id": rsvp.public_id,
+        "tenant_id": rsvp.tenant_id,
+    })
+}
+
+fn changed_fields(old: &abi::Reservation, new: &abi::Reservation) -> Vec<String> {
+    let (old, new) = (to_json(old), to_json(new));
+    abi::TRACKED_FIELDS
+        .iter()
+        .filter(|field| old[**field] != new[**field])
+        .map(|field| field.to_string())
+        .collect()
+}
+
+// 创建和删除时视为所有记录的字段都发生了变化
+fn all_fields() -> Vec<String> {
+    abi::TRACKED_FIELDS
+        .iter()
+        .map(|field| field.to_string())
+        .collect()
+}
+
+// 导入预定时指定的ID和公开ID，只有按ID导入时才使用文件中的值
+fn import_ids(rsvp: &abi::Reservation, upsert: bool) -> (Option<ReservationId>, Option<Uuid>) {
+    if !upsert {
+        return (None, None);
+    }
+    (
+        (rsvp.id > 0).then_some(rsvp.id),
+        Uuid::parse_str(&rsvp.public_id).ok(),
+    )
+}
+
+// 导入时只影响单条预定的错误，其它错误会中止整个导入
+fn is_row_error(e: &Error) -> bool {
+    matches!(
+        e,
+        Error::ConflictReservation(_) | Error::InvalidReservationId(_) | Error::InvalidPublicId(_)
+    )
+}
+
+// 与postgres排他约束的报错信息保持一致
+fn conflict_error(rsvp: &abi::Reservation, existing: &abi::Reservation) -> Error {
+    Error::ConflictReservation(format!(
+        "Key (tenant_id, resource_id, timespan)=({}, {}, {}) conflicts with existing key (tenant_id, resource_id, timespan)=({}, {}, {}).",
+        rsvp.tenant_id,
+        rsvp.resource_id,
+        timespan(rsvp),
+        existing.tenant_id,
+        existing.resource_id,
+        timespan(existing)
+    ))
+}
+
+// 导入的ID或公开ID已被其它租户使用。与时间冲突返回同一种错误，
+// 错误中只包含导入的预定本身的信息，不透露其它租户的预定
+fn unavailable_error(rsvp: &abi::Reservation) -> Error {
+    Error::ConflictReservation(format!(
+        "Key (id, public_id)=({}, {}) is not available.",
+        rsvp.id, rsvp.public_id
+    ))
+}
+
+// 同一资源的预定时间段互不重叠，因此按开始时间排序即可作为区间树使用:
+// 与[start, end)重叠的只可能是开始时间早于end的最后一个区间
+#[derive(Debug, Clone)]
+struct IntervalTree<T = ReservationId> {
+    // 开始时间 -> (结束时间, 值)
+    spans: BTreeMap<DateTime<Utc>, (DateTime<Utc>, T)>,
+}
+
+impl<T> Default for IntervalTree<T> {
+    fn default() -> Self {
+        Self {
+            spans: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T: Copy> IntervalTree<T> {
+    // 查找与[start, end)重叠的区间
+    fn overlap(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Option<T> {
+        self.spans
+            .range(..end)
+            .next_back()
+            .filter(|(_, (span_end, _))| *span_end > start)
+            .map(|(_, (_, value))| *value)
+    }
+
+    fn insert(&mut self, start: DateTime<Utc>, end: DateTime<Utc>, value: T) {
+        self.spans.insert(start, (end, value));
+    }
+
+    fn remove(&mut self, start: DateTime<Utc>) {
+        self.spans.remove(&start);
+    }
+}
+
+// 定义变更记录中保存的快照，用于在事务中自行写入变更记录的后端
+struct Snapshot {
+    reservation_id: ReservationId,
+    tenant_id: String,
+    old: Option<Value>,
+    new: Option<Value>,
+    changed_fields: Value,
+}
+
+impl Snapshot {
+    fn new(
+        old: Option<&abi::Reservation>,
+        new: Option<&abi::Reservation>,
+        changed_fields: &[String],
+    ) -> Self {
+        Self {
+            reservation_id: new.or(old).map_or(0, |r| r.id),
+            tenant_id: new
+                .or(old)
+                .map_or_else(String::new, |r| r.tenant_id.clone()),
+            old: old.map(to_json),
+            new: new.map(to_json),
+            changed_fields: Value::from(changed_fields),
+        }
+    }
+}
+
+// 轮询变更记录并推送给订阅者，收到本进程的变更通知时立即读取
+// fetch按顺序返回游标之后的(变更ID, 监听响应)
+async fn poll_changes<F, Fut>(
+    mut notified: watch::Receiver<i64>,
+    mut subscription: Subscription,
+    req: abi::ListenRequest,
+    tx: &mpsc::Sender<Result<abi::ListenResponse, Error>>,
+    fetch: F,
+) -> Result<(), Error>
+where
+    F: Fn(i64) -> Fut,
+    Fut: Future<Output = Result<Vec<(i64, abi::ListenResponse)>, Error>>,
+{
+    let mut interval = tokio::time::interval(LISTEN_POLL_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = tx.closed() => return Ok(()),
+            ret = notified.changed() => {
+                // 存储已经释放
+                if ret.is_err() {
+                    return Ok(());
+                }
+            }
+            _ = interval.tick() => {}
+        }
+
+        for (id, change) in fetch(subscription.cursor()).await? {
+            subscription.advance(id);
+            if !req.matches(&change.changed_fields) {
+                continue;
+            }
+            if tx.send(Ok(change)).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// 根据变更记录中的快照生成监听响应，删除时为删除前的预定
+fn to_listen_response(
+    op: &str,
+    old: Option<Value>,
+    new: Option<Value>,
+    changed_fields: Vec<String>,
+) -> abi::ListenResponse {
+    abi::ListenResponse {
+        op: ReservationUpdateType::from_op(op) as i32,
+        reservation: new.or(old).as_ref().and_then(from_json),
+        changed_fields,
+    }
+}
+
+// 从to_json生成的快照中还原预定
+fn from_json(value: &Value) -> Option<abi::Reservation> {
+    let (start, end) = parse_timespan(value["timespan"].as_str()?)?;
+    Some(abi::Reservation {
+        id: value["id"].as_i64()?,
+        user_id: value["user_id"].as_str()?.to_string(),
+        status: parse_status(value["status"].as_str()?) as i32,
+        resource_id: value["resource_id"].as_str()?.to_string(),
+        start: Some(convert_to_timestamp(&start)),
+        end: Some(convert_to_timestamp(&end)),
+        note: value["note"].as_str().unwrap_or_default().to_string(),
+        version: value["version"].as_i64()?,
+        public_id: value["public_id"].as_str()?.to_string(),
+        // 引入多租户之前的快照中没有租户
+        tenant_id: value["tenant_id"]
+            .as_str()
+            .unwrap_or(abi::DEFAULT_TENANT)
+            .to_string(),
+    })
+}
+
+// 解析tstzrange文本格式的时间段，如 ["2022-12-25 22:00:00+00","2022-12-28 19:00:00+00")
+fn parse_timespan(s: &str) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let (start, end) = s
+        .strip_prefix("[\"")?
+        .strip_suffix("\")")?
+        .split_once("\",\"")?;
+    let parse = |t: &str| {
+        NaiveDateTime::parse_from_str(t.strip_suffix("+00")?, "%Y-%m-%d %H:%M:%S%.f")
+            .ok()
+            .map(|t| t.and_utc())
+    };
+    Some((parse(start)?, parse(end)?))
+}
+
+fn parse_status(status: &str) -> ReservationStatus {
+    match status {
+        "pending" => ReservationStatus::Pending,
+        "confirmed" => ReservationStatus::Confirmed,
+        "blocked" => ReservationStatus::Blocked,
+        _ => ReservationStatus::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interval_tree_should_detect_half_open_overlap() {
+        let t = |h: u32| {
+            "2022-12-25T00:00:00Z".parse::<DateTime<Utc>>().unwrap()
+                + chrono::Duration::hours(h as i64)
+        };
+        let mut tree = IntervalTree::default();
+        tree.insert(t(2), t(4), 1);
+        tree.insert(t(6), t(8), 2);
+
+        assert_eq!(tree.overlap(t(0), t(2)), None);
+        assert_eq!(tree.overlap(t(4), t(6)), None);
+        assert_eq!(tree.overlap(t(3), t(5)), Some(1));
+        assert_eq!(tree.overlap(t(5), t(7)), Some(2));
+        assert_eq!(tree.overlap(t(0), t(10)), Some(2));
+
+        tree.remove(t(6));
+        assert_eq!(tree.overlap(t(5), t(7)), None);
+    }
+
+    #[test]
+    fn timespan_should_round_trip_through_json() {
+        let rsvp = abi::Reservation {
+            id: 1,
+            user_id: "tyrid".into(),
+            resource_id: "ocean-view-room-713".into(),
+            start: Some("2022-12-25T15:00:00.5-07:00".parse().unwrap()),
+            end: Some("2022-12-28T12:00:00-07:00".parse().unwrap()),
+            status: ReservationStatus::Pending as i32,
+            version: 1,
+            ..Default::default()
+        };
+        let json = to_json(&rsvp);
+        assert_eq!(
+            json["timespan"],
+            r#"["2022-12-25 22:00:00.500+00","2022-12-28 19:00:00+00")"#
+        );
+        assert_eq!(from_json(&json), Some(rsvp));
+    }
+
+    #[test]
+    fn subscribers_should_track_slowest_cursor_per_tenant() {
+        let subscribers = Subscribers::default();
+        let mut fast = subscribers.subscribe("acme", 3);
+        let slow = subscribers.subscribe("acme", 1);
+        let other = subscribers.subscribe("globex", 2);
+        fast.advance(5);
+        assert_eq!(subscribers.len(), 3);
+        assert_eq!(
+            subscribers.min_cursors(),
+            HashMap::from([("acme".to_string(), 1), ("globex".to_string(), 2)])
+        );
+
+        drop(slow);
+        drop(other);
+        assert_eq!(
+            subscribers.min_cursors(),
+            HashMap::from([("acme".to_string(), 5)])
+        );
+    }
+}
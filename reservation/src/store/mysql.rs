@@ -0,0 +1,781 @@
+use super::{
+    all_fields, changed_fields, conflict_error, import_ids, is_row_error, parse_status,
+    poll_changes, span, to_listen_response, unavailable_error, IdempotencyRecord, ReservationStore,
+    Snapshot, Subscribers,
+};
+use abi::{
+    convert_to_timestamp, convert_to_utc_time, Audit, Error, ReservationId, ReservationStatus,
+    ReservationUpdateType,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde_json::Value;
+use sqlx::{mysql::MySqlRow, MySql, MySqlPool, Row, Transaction};
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
+use uuid::Uuid;
+
+// 按租户、状态、用户和资源过滤预定的条件，为空的用户或资源条件以及unknown状态会被忽略
+const CONDITION: &str = "tenant_id = ? AND (? = 'unknown' OR status = ?) AND (? = '' OR user_id = ?) AND (? = '' OR resource_id = ?)";
+
+// 预定完全落在[start, end)内的条件，未指定的一端不限制，开始和结束时间各绑定两次
+const TIMESPAN: &str = "(? IS NULL OR start_at >= ?) AND (? IS NULL OR end_at <= ?)";
+
+// 定义基于mysql的预定存储
+// mysql没有范围类型，冲突检测在插入的事务中通过加锁读完成，变更记录也在同一事务中写入
+#[derive(Debug, Clone)]
+pub struct MySqlStore {
+    pool: MySqlPool,
+    // 最新一条变更记录的ID，用于通知本进程的监听者
+    changes: Arc<watch::Sender<i64>>,
+    subscribers: Subscribers,
+    tenant: String,
+}
+
+impl MySqlStore {
+    pub fn new(pool: MySqlPool) -> Self {
+        let (changes, _) = watch::channel(0);
+        Self {
+            pool,
+            changes: Arc::new(changes),
+            subscribers: Subscribers::default(),
+            tenant: abi::DEFAULT_TENANT.to_string(),
+        }
+    }
+
+    // 变更提交后通知监听者
+    fn notify(&self, change_id: Option<i64>) {
+        if let Some(id) = change_id {
+            self.changes.send_replace(id);
+        }
+    }
+
+    // 在事务中修改预定并记录变更，与postgres触发器一致: 版本号总会增加，但只有字段发生变化时才记录变更
+    async fn update<F>(
+        &self,
+        id: ReservationId,
+        version: Option<i64>,
+        audit: &Audit,
+        f: F,
+    ) -> Result<abi::Reservation, Error>
+    where
+        F: FnOnce(&mut abi::Reservation) -> Result<(), Error> + Send,
+    {
+        let mut tx = self.pool.begin().await?;
+        let old = fetch_for_update(&mut tx, &self.tenant, id, version).await?;
+        let mut rsvp = old.clone();
+        f(&mut rsvp)?;
+        rsvp.version = old.version + 1;
+
+        let status = ReservationStatus::try_from(rsvp.status).unwrap_or(ReservationStatus::Unknown);
+        sqlx::query("UPDATE reservations SET status = ?, note = ?, version = ? WHERE id = ?")
+            .bind(status.to_string())
+            .bind(rsvp.note.clone())
+            .bind(rsvp.version)
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+
+        let changed_fields = changed_fields(&old, &rsvp);
+        let change_id = if changed_fields.is_empty() {
+            None
+        } else {
+            let change = Snapshot::new(Some(&old), Some(&rsvp), &changed_fields);
+            Some(record(&mut tx, "update", change, audit).await?)
+        };
+        tx.commit().await?;
+
+        self.notify(change_id);
+        Ok(rsvp)
+    }
+}
+
+#[async_trait]
+impl ReservationStore for MySqlStore {
+    fn with_tenant(&self, tenant: &str) -> Self {
+        Self {
+            tenant: tenant.to_string(),
+            ..self.clone()
+        }
+    }
+
+    async fn insert(
+        &self,
+        rsvp: abi::Reservation,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error> {
+        let mut tx = self.pool.begin().await?;
+        let (rsvp, change_id) = insert_row(&mut tx, &self.tenant, rsvp, audit).await?;
+        tx.commit().await?;
+
+        self.notify(Some(change_id));
+        Ok(rsvp)
+    }
+
+    async fn insert_all(
+        &self,
+        rsvps: Vec<abi::Reservation>,
+        audit: &Audit,
+        commit: bool,
+    ) -> Result<Vec<Result<abi::Reservation, Error>>, Error> {
+        // 冲突在插入之前检测，冲突时不会写入任何数据，不需要保存点
+        let mut tx = self.pool.begin().await?;
+        let mut results = Vec::with_capacity(rsvps.len());
+        let mut last_change = None;
+        for rsvp in rsvps {
+            match insert_row(&mut tx, &self.tenant, rsvp, audit).await {
+                Ok((rsvp, change_id)) => {
+                    last_change = Some(change_id);
+                    results.push(Ok(rsvp));
+                }
+                Err(e @ Error::ConflictReservation(_)) => results.push(Err(e)),
+                Err(e) => return Err(e),
+            }
+        }
+        if commit {
+            tx.commit().await?;
+            self.notify(last_change);
+        } else {
+            tx.rollback().await?;
+        }
+        Ok(results)
+    }
+
+    async fn import(
+        &self,
+        rsvps: Vec<abi::Reservation>,
+        upsert: bool,
+        audit: &Audit,
+    ) -> Result<Vec<Result<abi::Reservation, Error>>, Error> {
+        // 单条预定失败时不会写入任何数据，不需要保存点
+        let mut tx = self.pool.begin().await?;
+        let mut results = Vec::with_capacity(rsvps.len());
+        let mut last_change = None;
+        for rsvp in rsvps {
+            match import_row(&mut tx, &self.tenant, rsvp, upsert, audit).await {
+                Ok((rsvp, change_id)) => {
+                    last_change = change_id.or(last_change);
+                    results.push(Ok(rsvp));
+                }
+                Err(e) if is_row_error(&e) => results.push(Err(e)),
+                Err(e) => return Err(e),
+            }
+        }
+        tx.commit().await?;
+
+        self.notify(last_change);
+        Ok(results)
+    }
+
+    async fn confirm(
+        &self,
+        id: ReservationId,
+        version: Option<i64>,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error> {
+        self.update(id, version, audit, |rsvp| {
+            // 只有pending状态的预定可以确认
+            if rsvp.status != ReservationStatus::Pending as i32 {
+                return Err(Error::NotFound);
+            }
+            rsvp.status = ReservationStatus::Confirmed as i32;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_note(
+        &self,
+        id: ReservationId,
+        note: String,
+        version: Option<i64>,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error> {
+        self.update(id, version, audit, |rsvp| {
+            rsvp.note = note;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete(
+        &self,
+        id: ReservationId,
+        version: Option<i64>,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error> {
+        let mut tx = self.pool.begin().await?;
+        let rsvp = fetch_for_update(&mut tx, &self.tenant, id, version).await?;
+        sqlx::query("DELETE FROM reservations WHERE id = ?")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        let change = Snapshot::new(Some(&rsvp), None, &all_fields());
+        let change_id = record(&mut tx, "delete", change, audit).await?;
+        tx.commit().await?;
+
+        self.notify(Some(change_id));
+        Ok(rsvp)
+    }
+
+    async fn get(&self, id: ReservationId) -> Result<abi::Reservation, Error> {
+        let row = sqlx::query("SELECT * FROM reservations WHERE id = ? AND tenant_id = ?")
+            .bind(id)
+            .bind(self.tenant.clone())
+            .fetch_one(&self.pool)
+            .await?;
+        to_reservation(&row)
+    }
+
+    async fn resolve(&self, public_id: Uuid) -> Result<ReservationId, Error> {
+        let id =
+            sqlx::query_scalar("SELECT id FROM reservations WHERE public_id = ? AND tenant_id = ?")
+                .bind(public_id.to_string())
+                .bind(self.tenant.clone())
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(id)
+    }
+
+    async fn history(&self, id: ReservationId) -> Result<Vec<abi::ReservationChange>, Error> {
+        let rows = sqlx::query(
+            "SELECT * FROM reservation_changes WHERE reservation_id = ? AND tenant_id = ? ORDER BY id",
+        )
+        .bind(id)
+        .bind(self.tenant.clone())
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(to_reservation_change).collect()
+    }
+
+    async fn query(
+        &self,
+        query: abi::ReservationQuery,
+    ) -> mpsc::Receiver<Result<abi::Reservation, Error>> {
+        let pool = self.pool.clone();
+        let tenant = self.tenant.clone();
+        let (tx, rx) = mpsc::channel(128);
+
+        // 与postgres一致: 返回完全落在查询区间内的预定，按开始时间排序
+        tokio::spawn(async move {
+            let sql = format!(
+                "SELECT * FROM reservations WHERE {} AND {} ORDER BY start_at {}",
+                CONDITION,
+                TIMESPAN,
+                if query.desc { "DESC" } else { "ASC" }
+            );
+            let start = query.start.as_ref().map(convert_to_utc_time);
+            let end = query.end.as_ref().map(convert_to_utc_time);
+            let mut rows = sqlx::query(&sql)
+                .bind(tenant)
+                .bind(query.get_status().to_string())
+                .bind(query.get_status().to_string())
+                .bind(query.user_id.clone())
+                .bind(query.user_id.clone())
+                .bind(query.resource_id.clone())
+                .bind(query.resource_id.clone())
+                .bind(start)
+                .bind(start)
+                .bind(end)
+                .bind(end)
+                .fetch(&pool);
+            while let Some(ret) = rows.next().await {
+                let ret = ret
+                    .map_err(Error::from)
+                    .and_then(|row| to_reservation(&row));
+                let failed = ret.is_err();
+                if tx.send(ret).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        rx
+    }
+
+    async fn filter(
+        &self,
+        filter: &abi::ReservationFilter,
+    ) -> Result<(Vec<abi::Reservation>, i64), Error> {
+        let status = filter.get_status().to_string();
+
+        // 与postgres一致: 从游标处按ID排序，多取一条用于判断是否还有下一页
+        let sql = if filter.desc {
+            format!(
+                "SELECT * FROM reservations WHERE {} AND {} AND (? = 0 OR id < ?) ORDER BY id DESC LIMIT ?",
+                CONDITION, TIMESPAN
+            )
+        } else {
+            format!(
+                "SELECT * FROM reservations WHERE {} AND {} AND (? = 0 OR id > ?) ORDER BY id ASC LIMIT ?",
+                CONDITION, TIMESPAN
+            )
+        };
+        let start = filter.start.as_ref().map(convert_to_utc_time);
+        let end = filter.end.as_ref().map(convert_to_utc_time);
+        let rows = sqlx::query(&sql)
+            .bind(self.tenant.clone())
+            .bind(status.clone())
+            .bind(status.clone())
+            .bind(filter.user_id.clone())
+            .bind(filter.user_id.clone())
+            .bind(filter.resource_id.clone())
+            .bind(filter.resource_id.clone())
+            .bind(start)
+            .bind(start)
+            .bind(end)
+            .bind(end)
+            .bind(filter.cursor)
+            .bind(filter.cursor)
+            .bind(filter.page_size + 1)
+            .fetch_all(&self.pool)
+            .await?;
+        let rsvps = rows
+            .iter()
+            .map(to_reservation)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let total = sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) FROM reservations WHERE {} AND {}",
+            CONDITION, TIMESPAN
+        ))
+        .bind(self.tenant.clone())
+        .bind(status.clone())
+        .bind(status)
+        .bind(filter.user_id.clone())
+        .bind(filter.user_id.clone())
+        .bind(filter.resource_id.clone())
+        .bind(filter.resource_id.clone())
+        .bind(start)
+        .bind(start)
+        .bind(end)
+        .bind(end)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok((rsvps, total))
+    }
+
+    async fn listen(
+        &self,
+        req: abi::ListenRequest,
+    ) -> mpsc::Receiver<Result<abi::ListenResponse, Error>> {
+        let pool = self.pool.clone();
+        let tenant = self.tenant.clone();
+        let (tx, rx) = mpsc::channel(128);
+
+        // 先订阅通知再读取游标，避免遗漏两者之间产生的变更
+        let notified = self.changes.subscribe();
+        let cursor =
+            sqlx::query_scalar::<_, i64>("SELECT COALESCE(MAX(id), 0) FROM reservation_changes")
+                .fetch_one(&pool)
+                .await;
+
+        // 开启一个异步任务监听变更，订阅者断开后任务结束
+        let subscription = cursor.map(|cursor| self.subscribers.subscribe(&tenant, cursor));
+        tokio::spawn(async move {
+            let ret = match subscription {
+                Ok(subscription) => {
+                    poll_changes(notified, subscription, req, &tx, |cursor| {
+                        fetch_changes(&pool, &tenant, cursor)
+                    })
+                    .await
+                }
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = ret {
+                let _ = tx.send(Err(e)).await;
+            }
+        });
+
+        rx
+    }
+
+    fn subscribers(&self) -> &Subscribers {
+        &self.subscribers
+    }
+
+    async fn latest_change_id(&self) -> Result<i64, Error> {
+        let id = sqlx::query_scalar(
+            "SELECT COALESCE(MAX(id), 0) FROM reservation_changes WHERE tenant_id = ?",
+        )
+        .bind(&self.tenant)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(id)
+    }
+
+    async fn claim_idempotency_key(
+        &self,
+        key: &str,
+        op: &str,
+        request: &[u8],
+        expires_at: DateTime<Utc>,
+    ) -> Result<bool, Error> {
+        let now = Utc::now();
+        sqlx::query("DELETE FROM idempotency_keys WHERE tenant_id = ? AND expires_at < ?")
+            .bind(self.tenant.clone())
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        let ret = sqlx::query(
+            "INSERT IGNORE INTO idempotency_keys (tenant_id, `key`, op, request, created_at, expires_at) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(self.tenant.clone())
+        .bind(key)
+        .bind(op)
+        .bind(request)
+        .bind(now)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+        if ret.rows_affected() > 0 {
+            return Ok(true);
+        }
+
+        // 幂等键已存在时，只有已经过期才可以被重新抢占
+        let ret = sqlx::query(
+            "UPDATE idempotency_keys SET op = ?, request = ?, response = NULL, created_at = ?, expires_at = ? WHERE tenant_id = ? AND `key` = ? AND expires_at < ?",
+        )
+        .bind(op)
+        .bind(request)
+        .bind(now)
+        .bind(expires_at)
+        .bind(self.tenant.clone())
+        .bind(key)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(ret.rows_affected() > 0)
+    }
+
+    async fn get_idempotency_key(&self, key: &str) -> Result<Option<IdempotencyRecord>, Error> {
+        let row = sqlx::query(
+            "SELECT op, request, response FROM idempotency_keys WHERE tenant_id = ? AND `key` = ?",
+        )
+        .bind(self.tenant.clone())
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?;
+        row.map(|row| {
+            Ok(IdempotencyRecord {
+                op: row.try_get("op")?,
+                request: row.try_get("request")?,
+                response: row.try_get("response")?,
+            })
+        })
+        .transpose()
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        key: &str,
+        response: &[u8],
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        sqlx::query(
+            "UPDATE idempotency_keys SET response = ?, expires_at = ? WHERE tenant_id = ? AND `key` = ?",
+        )
+        .bind(response)
+        .bind(expires_at)
+        .bind(self.tenant.clone())
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn release_idempotency_key(&self, key: &str) -> Result<(), Error> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE tenant_id = ? AND `key` = ?")
+            .bind(self.tenant.clone())
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+async fn insert_row(
+    tx: &mut Transaction<'_, MySql>,
+    tenant: &str,
+    rsvp: abi::Reservation,
+    audit: &Audit,
+) -> Result<(abi::Reservation, i64), Error> {
+    create_row(tx, tenant, rsvp, None, Uuid::now_v7(), audit).await
+}
+
+// 在事务中插入预定并记录变更，返回插入的预定和变更ID，id为空时由数据库分配
+// 加锁读会锁住(tenant_id, resource_id, start_at)索引上开始时间早于end的范围(包括间隙)，
+// 与之重叠的预定在事务提交之前都无法插入
+async fn create_row(
+    tx: &mut Transaction<'_, MySql>,
+    tenant: &str,
+    mut rsvp: abi::Reservation,
+    id: Option<ReservationId>,
+    public_id: Uuid,
+    audit: &Audit,
+) -> Result<(abi::Reservation, i64), Error> {
+    rsvp.tenant_id = tenant.to_string();
+    let status = ReservationStatus::try_from(rsvp.status).unwrap_or(ReservationStatus::Pending);
+    let (start, end) = span(&rsvp);
+    check_conflict(tx, &rsvp, 0).await?;
+
+    let ret = sqlx::query(
+        "INSERT INTO reservations (id, user_id, resource_id, start_at, end_at, note, status, public_id, tenant_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(id)
+    .bind(rsvp.user_id.clone())
+    .bind(rsvp.resource_id.clone())
+    .bind(start)
+    .bind(end)
+    .bind(rsvp.note.clone())
+    .bind(status.to_string())
+    .bind(public_id.to_string())
+    .bind(tenant)
+    .execute(&mut *tx)
+    .await?;
+
+    rsvp.id = id.unwrap_or(ret.last_insert_id() as ReservationId);
+    rsvp.version = 1;
+    rsvp.public_id = public_id.to_string();
+    let change = Snapshot::new(None, Some(&rsvp), &all_fields());
+    let change_id = record(tx, "create", change, audit).await?;
+    Ok((rsvp, change_id))
+}
+
+// 在事务中导入一条预定，返回导入的预定和变更ID(覆盖前后字段都相同时没有变更)
+// 按ID导入且ID已存在时覆盖原来的预定，否则创建新的预定
+async fn import_row(
+    tx: &mut Transaction<'_, MySql>,
+    tenant: &str,
+    mut rsvp: abi::Reservation,
+    upsert: bool,
+    audit: &Audit,
+) -> Result<(abi::Reservation, Option<i64>), Error> {
+    let (id, public_id) = import_ids(&rsvp, upsert);
+    if let Some(public_id) = public_id {
+        let owner: Option<(ReservationId, String)> =
+            sqlx::query_as("SELECT id, tenant_id FROM reservations WHERE public_id = ? FOR UPDATE")
+                .bind(public_id.to_string())
+                .fetch_optional(&mut *tx)
+                .await?;
+        match owner {
+            Some((_, owner_tenant)) if owner_tenant != tenant => {
+                return Err(unavailable_error(&rsvp))
+            }
+            Some((owner, _)) if Some(owner) != id => {
+                return Err(Error::InvalidPublicId(rsvp.public_id))
+            }
+            _ => {}
+        }
+    }
+    // 只读取租户内的预定，ID被其它租户使用时与其它租户的预定冲突一样处理，不透露其内容
+    let old = match id {
+        Some(id) => {
+            sqlx::query("SELECT * FROM reservations WHERE id = ? AND tenant_id = ? FOR UPDATE")
+                .bind(id)
+                .bind(tenant)
+                .fetch_optional(&mut *tx)
+                .await?
+                .map(|row| to_reservation(&row))
+                .transpose()?
+        }
+        None => None,
+    };
+    if old.is_none() && id.is_some() {
+        let taken: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM reservations WHERE id = ?)")
+                .bind(id)
+                .fetch_one(&mut *tx)
+                .await?;
+        if taken {
+            return Err(unavailable_error(&rsvp));
+        }
+    }
+    let Some(old) = old else {
+        let public_id = public_id.unwrap_or_else(Uuid::now_v7);
+        let (rsvp, change_id) = create_row(tx, tenant, rsvp, id, public_id, audit).await?;
+        return Ok((rsvp, Some(change_id)));
+    };
+    rsvp.id = old.id;
+    rsvp.tenant_id = old.tenant_id.clone();
+    rsvp.version = old.version + 1;
+    rsvp.public_id = public_id.map_or(old.public_id.clone(), |p| p.to_string());
+    // 原来的时间段不算冲突
+    check_conflict(tx, &rsvp, rsvp.id).await?;
+
+    let status = ReservationStatus::try_from(rsvp.status).unwrap_or(ReservationStatus::Pending);
+    let (start, end) = span(&rsvp);
+    sqlx::query(
+        "UPDATE reservations SET user_id = ?, resource_id = ?, start_at = ?, end_at = ?, note = ?, status = ?, public_id = ?, version = ? WHERE id = ?",
+    )
+    .bind(rsvp.user_id.clone())
+    .bind(rsvp.resource_id.clone())
+    .bind(start)
+    .bind(end)
+    .bind(rsvp.note.clone())
+    .bind(status.to_string())
+    .bind(rsvp.public_id.clone())
+    .bind(rsvp.version)
+    .bind(rsvp.id)
+    .execute(&mut *tx)
+    .await?;
+
+    let changed_fields = changed_fields(&old, &rsvp);
+    if changed_fields.is_empty() {
+        return Ok((rsvp, None));
+    }
+    let change = Snapshot::new(Some(&old), Some(&rsvp), &changed_fields);
+    let change_id = record(tx, "update", change, audit).await?;
+    Ok((rsvp, Some(change_id)))
+}
+
+// 在事务中加锁检测与预定时间重叠的其它预定(不包括except)，重叠时返回ConflictReservation
+async fn check_conflict(
+    tx: &mut Transaction<'_, MySql>,
+    rsvp: &abi::Reservation,
+    except: ReservationId,
+) -> Result<(), Error> {
+    let (start, end) = span(rsvp);
+    let existing = sqlx::query(
+        "SELECT * FROM reservations WHERE tenant_id = ? AND resource_id = ? AND start_at < ? AND end_at > ? AND id <> ? LIMIT 1 FOR UPDATE",
+    )
+    .bind(rsvp.tenant_id.clone())
+    .bind(rsvp.resource_id.clone())
+    .bind(end)
+    .bind(start)
+    .bind(except)
+    .fetch_optional(&mut *tx)
+    .await?;
+    match existing {
+        Some(row) => Err(conflict_error(rsvp, &to_reservation(&row)?)),
+        None => Ok(()),
+    }
+}
+
+// 在事务中写入变更记录，返回变更记录的ID
+async fn record(
+    tx: &mut Transaction<'_, MySql>,
+    op: &str,
+    change: Snapshot,
+    audit: &Audit,
+) -> Result<i64, Error> {
+    let ret = sqlx::query(
+        "INSERT INTO reservation_changes (reservation_id, tenant_id, old, new, op, actor, reason, changed_fields, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(change.reservation_id)
+    .bind(change.tenant_id)
+    .bind(change.old)
+    .bind(change.new)
+    .bind(op)
+    .bind(audit.actor.to_string())
+    .bind(audit.reason.clone())
+    .bind(change.changed_fields)
+    .bind(Utc::now())
+    .execute(&mut *tx)
+    .await?;
+    Ok(ret.last_insert_id() as i64)
+}
+
+// 在事务中锁定并取出租户内的预定，确认其版本与期望一致
+async fn fetch_for_update(
+    tx: &mut Transaction<'_, MySql>,
+    tenant: &str,
+    id: ReservationId,
+    version: Option<i64>,
+) -> Result<abi::Reservation, Error> {
+    let row = sqlx::query("SELECT * FROM reservations WHERE id = ? AND tenant_id = ? FOR UPDATE")
+        .bind(id)
+        .bind(tenant)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+    let rsvp = to_reservation(&row)?;
+    match version {
+        Some(expected) if expected != rsvp.version => Err(Error::VersionMismatch {
+            expected,
+            actual: rsvp.version,
+        }),
+        _ => Ok(rsvp),
+    }
+}
+
+// 读取游标之后租户内的变更记录
+async fn fetch_changes(
+    pool: &MySqlPool,
+    tenant: &str,
+    cursor: i64,
+) -> Result<Vec<(i64, abi::ListenResponse)>, Error> {
+    let rows =
+        sqlx::query("SELECT * FROM reservation_changes WHERE id > ? AND tenant_id = ? ORDER BY id")
+            .bind(cursor)
+            .bind(tenant)
+            .fetch_all(pool)
+            .await?;
+    rows.iter()
+        .map(|row| {
+            let op: String = row.try_get("op")?;
+            let changed_fields: Value = row.try_get("changed_fields")?;
+            let change = to_listen_response(
+                &op,
+                row.try_get("old")?,
+                row.try_get("new")?,
+                serde_json::from_value(changed_fields).unwrap_or_default(),
+            );
+            Ok((row.try_get("id")?, change))
+        })
+        .collect()
+}
+
+fn to_reservation(row: &MySqlRow) -> Result<abi::Reservation, Error> {
+    let status: String = row.try_get("status")?;
+    let note: Option<String> = row.try_get("note")?;
+    Ok(abi::Reservation {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        status: parse_status(&status) as i32,
+        resource_id: row.try_get("resource_id")?,
+        start: Some(convert_to_timestamp(&row.try_get("start_at")?)),
+        end: Some(convert_to_timestamp(&row.try_get("end_at")?)),
+        note: note.unwrap_or_default(),
+        version: row.try_get("version")?,
+        public_id: row.try_get("public_id")?,
+        tenant_id: row.try_get("tenant_id")?,
+    })
+}
+
+fn to_reservation_change(row: &MySqlRow) -> Result<abi::ReservationChange, Error> {
+    let op: String = row.try_get("op")?;
+    let actor: Option<String> = row.try_get("actor")?;
+    let reason: Option<String> = row.try_get("reason")?;
+    let old: Option<Value> = row.try_get("old")?;
+    let new: Option<Value> = row.try_get("new")?;
+    Ok(abi::ReservationChange {
+        id: row.try_get("id")?,
+        reservation_id: row.try_get("reservation_id")?,
+        op: ReservationUpdateType::from_op(&op) as i32,
+        actor: actor.unwrap_or_default(),
+        reason: reason.unwrap_or_default(),
+        changed_at: Some(convert_to_timestamp(&row.try_get("created_at")?)),
+        fields: abi::diff_fields(old.as_ref(), new.as_ref()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{conformance::conformance_tests, ReservationManager, TestMysql};
+
+    async fn setup() -> (TestMysql, ReservationManager<MySqlStore>) {
+        let tdb = TestMysql::default();
+        let manager = ReservationManager::with_store(MySqlStore::new(tdb.get_pool().await));
+        (tdb, manager)
+    }
+
+    // 需要mysql服务，设置TEST_MYSQL_URL后使用--ignored运行
+    conformance_tests!(
+        #[ignore = "需要mysql服务"]
+        setup
+    );
+}
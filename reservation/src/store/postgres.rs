@@ -0,0 +1,799 @@
+use super::{
+    conflict_error, import_ids, span, timespan, unavailable_error, IdempotencyRecord, IntervalTree,
+    ReservationStore, Subscribers,
+};
+use abi::{Audit, Error, ReservationId, ToSql};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use sqlx::{postgres::PgListener, Acquire, FromRow, PgPool, Postgres, Row, Transaction};
+use std::collections::{HashMap, HashSet};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+// 定义基于postgres的预定存储，冲突检测由排他约束保证，变更记录由触发器写入
+// 每条语句都显式按租户过滤，同时在事务中设置rsvp.tenant_id，开启行级安全时由策略再做一次隔离
+#[derive(Debug, Clone)]
+pub struct PgStore {
+    pool: PgPool,
+    subscribers: Subscribers,
+    tenant: String,
+}
+
+impl PgStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            subscribers: Subscribers::default(),
+            tenant: abi::DEFAULT_TENANT.to_string(),
+        }
+    }
+
+    // 开启一个限定在租户内的事务，开启行级安全时只有设置了rsvp.tenant_id的事务才能看到租户的数据
+    async fn begin(&self) -> Result<Transaction<'static, Postgres>, Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SELECT set_config('rsvp.tenant_id', $1, true)")
+            .bind(&self.tenant)
+            .execute(&mut tx)
+            .await?;
+        Ok(tx)
+    }
+
+    // 变更未命中任何记录时，区分是版本冲突还是记录不存在
+    async fn miss_error(&self, id: ReservationId, version: Option<i64>) -> Error {
+        if let Some(expected) = version {
+            if let Ok(Some(actual)) = self.current_version(id).await {
+                if actual != expected {
+                    return Error::VersionMismatch { expected, actual };
+                }
+            }
+        }
+        Error::NotFound
+    }
+
+    async fn current_version(&self, id: ReservationId) -> Result<Option<i64>, Error> {
+        let mut tx = self.begin().await?;
+        let version = sqlx::query_scalar(
+            "SELECT version FROM rsvp.reservations WHERE id = $1 AND tenant_id = $2",
+        )
+        .bind(id)
+        .bind(&self.tenant)
+        .fetch_optional(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok(version)
+    }
+}
+
+#[async_trait]
+impl ReservationStore for PgStore {
+    fn with_tenant(&self, tenant: &str) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            subscribers: self.subscribers.clone(),
+            tenant: tenant.to_string(),
+        }
+    }
+
+    async fn insert(
+        &self,
+        rsvp: abi::Reservation,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error> {
+        let mut tx = self.begin().await?;
+        set_audit(&mut tx, audit).await?;
+        let rsvp = insert_row(&mut tx, &self.tenant, rsvp).await?;
+        tx.commit().await?;
+        Ok(rsvp)
+    }
+
+    async fn insert_all(
+        &self,
+        rsvps: Vec<abi::Reservation>,
+        audit: &Audit,
+        commit: bool,
+    ) -> Result<Vec<Result<abi::Reservation, Error>>, Error> {
+        let mut tx = self.begin().await?;
+        set_audit(&mut tx, audit).await?;
+        let mut results = Vec::with_capacity(rsvps.len());
+        for rsvp in rsvps {
+            // 违反排他约束会中止整个事务，每条预定在自己的保存点中插入
+            let mut savepoint = tx.begin().await?;
+            match insert_row(&mut savepoint, &self.tenant, rsvp).await {
+                Ok(rsvp) => {
+                    savepoint.commit().await?;
+                    results.push(Ok(rsvp));
+                }
+                Err(e @ Error::ConflictReservation(_)) => {
+                    savepoint.rollback().await?;
+                    results.push(Err(e));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        if commit {
+            tx.commit().await?;
+        } else {
+            tx.rollback().await?;
+        }
+        Ok(results)
+    }
+
+    async fn import(
+        &self,
+        rsvps: Vec<abi::Reservation>,
+        upsert: bool,
+        audit: &Audit,
+    ) -> Result<Vec<Result<abi::Reservation, Error>>, Error> {
+        let mut tx = self.begin().await?;
+        set_audit(&mut tx, audit).await?;
+        let mut plans = plan_import(&mut tx, &self.tenant, rsvps, upsert).await?;
+
+        // 先按顺序覆盖已有的预定，再一次性写入新的预定
+        for plan in plans.iter_mut() {
+            if let Ok(Import::Update(rsvp)) = plan {
+                *rsvp = update_row(&mut tx, rsvp).await?;
+            }
+        }
+        let mut created = plans
+            .iter_mut()
+            .filter_map(|plan| match plan {
+                Ok(Import::Create(rsvp)) => Some(rsvp),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        copy_rows(&mut tx, &mut created).await?;
+        tx.commit().await?;
+
+        Ok(plans
+            .into_iter()
+            .map(|plan| plan.map(Import::into_reservation))
+            .collect())
+    }
+
+    async fn confirm(
+        &self,
+        id: ReservationId,
+        version: Option<i64>,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error> {
+        let mut tx = self.begin().await?;
+        set_audit(&mut tx, audit).await?;
+        let rsvp: Option<abi::Reservation> = sqlx::query_as("UPDATE rsvp.reservations SET status = 'confirmed' WHERE id = $1 AND tenant_id = $3 AND status = 'pending' AND ($2::BIGINT IS NULL OR version = $2) RETURNING *")
+            .bind(id)
+            .bind(version)
+            .bind(&self.tenant)
+            .fetch_optional(&mut tx).await?;
+        tx.commit().await?;
+        match rsvp {
+            Some(rsvp) => Ok(rsvp),
+            None => Err(self.miss_error(id, version).await),
+        }
+    }
+
+    async fn update_note(
+        &self,
+        id: ReservationId,
+        note: String,
+        version: Option<i64>,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error> {
+        let mut tx = self.begin().await?;
+        set_audit(&mut tx, audit).await?;
+        let rsvp: Option<abi::Reservation> = sqlx::query_as(
+            "UPDATE rsvp.reservations SET note = $2 WHERE id = $1 AND tenant_id = $4 AND ($3::BIGINT IS NULL OR version = $3) RETURNING *",
+        )
+        .bind(id)
+        .bind(note)
+        .bind(version)
+        .bind(&self.tenant)
+        .fetch_optional(&mut tx)
+        .await?;
+        tx.commit().await?;
+        match rsvp {
+            Some(rsvp) => Ok(rsvp),
+            None => Err(self.miss_error(id, version).await),
+        }
+    }
+
+    async fn delete(
+        &self,
+        id: ReservationId,
+        version: Option<i64>,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error> {
+        let mut tx = self.begin().await?;
+        set_audit(&mut tx, audit).await?;
+        let rsvp: Option<abi::Reservation> = sqlx::query_as(
+            "DELETE FROM rsvp.reservations WHERE id = $1 AND tenant_id = $3 AND ($2::BIGINT IS NULL OR version = $2) RETURNING *",
+        )
+        .bind(id)
+        .bind(version)
+        .bind(&self.tenant)
+        .fetch_optional(&mut tx)
+        .await?;
+        tx.commit().await?;
+        match rsvp {
+            Some(rsvp) => Ok(rsvp),
+            None => Err(self.miss_error(id, version).await),
+        }
+    }
+
+    async fn get(&self, id: ReservationId) -> Result<abi::Reservation, Error> {
+        let mut tx = self.begin().await?;
+        let rsvp: abi::Reservation =
+            sqlx::query_as("SELECT * FROM rsvp.reservations WHERE id = $1 AND tenant_id = $2")
+                .bind(id)
+                .bind(&self.tenant)
+                .fetch_one(&mut tx)
+                .await?;
+        tx.commit().await?;
+        Ok(rsvp)
+    }
+
+    async fn resolve(&self, public_id: Uuid) -> Result<ReservationId, Error> {
+        let mut tx = self.begin().await?;
+        let id = sqlx::query_scalar(
+            "SELECT id FROM rsvp.reservations WHERE public_id = $1 AND tenant_id = $2",
+        )
+        .bind(public_id)
+        .bind(&self.tenant)
+        .fetch_one(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn history(&self, id: ReservationId) -> Result<Vec<abi::ReservationChange>, Error> {
+        let mut tx = self.begin().await?;
+        let changes: Vec<abi::ReservationChange> = sqlx::query_as(
+            "SELECT id::BIGINT AS id, reservation_id, old, new, op::TEXT AS op, actor, reason, created_at FROM rsvp.reservation_changes WHERE reservation_id = $1 AND tenant_id = $2 ORDER BY id",
+        )
+        .bind(id)
+        .bind(&self.tenant)
+        .fetch_all(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok(changes)
+    }
+
+    async fn query(
+        &self,
+        query: abi::ReservationQuery,
+    ) -> mpsc::Receiver<Result<abi::Reservation, Error>> {
+        let store = self.clone();
+        // 声明一个channel
+        let (tx, rx) = mpsc::channel(128);
+
+        // 开启一个异步任务查询预订记录，边查询边发送
+        tokio::spawn(async move {
+            let mut conn = match store.begin().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    let _ = tx.send(Err(e)).await;
+                    return;
+                }
+            };
+            let sql = query.to_sql();
+            let mut rsvps = sqlx::query_as(&sql)
+                .bind(&store.tenant)
+                .bind(&query.user_id)
+                .bind(&query.resource_id)
+                .fetch(&mut conn);
+            while let Some(ret) = rsvps.next().await {
+                let ret = ret.map_err(Error::from);
+                let failed = ret.is_err();
+                if tx.send(ret).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        rx
+    }
+
+    async fn filter(
+        &self,
+        filter: &abi::ReservationFilter,
+    ) -> Result<(Vec<abi::Reservation>, i64), Error> {
+        let mut tx = self.begin().await?;
+        let rsvps = sqlx::query_as(&filter.to_sql())
+            .bind(&self.tenant)
+            .bind(&filter.user_id)
+            .bind(&filter.resource_id)
+            .fetch_all(&mut tx)
+            .await?;
+        let total = sqlx::query_scalar(&filter.to_count_sql())
+            .bind(&self.tenant)
+            .bind(&filter.user_id)
+            .bind(&filter.resource_id)
+            .fetch_one(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok((rsvps, total))
+    }
+
+    async fn listen(
+        &self,
+        req: abi::ListenRequest,
+    ) -> mpsc::Receiver<Result<abi::ListenResponse, Error>> {
+        let store = self.clone();
+        let (tx, rx) = mpsc::channel(128);
+
+        // 开启一个异步任务监听变更，订阅者断开后任务结束
+        tokio::spawn(async move {
+            if let Err(e) = listen_changes(store, req, &tx).await {
+                let _ = tx.send(Err(e)).await;
+            }
+        });
+
+        rx
+    }
+
+    fn subscribers(&self) -> &Subscribers {
+        &self.subscribers
+    }
+
+    async fn latest_change_id(&self) -> Result<i64, Error> {
+        let mut tx = self.begin().await?;
+        let id = sqlx::query_scalar(
+            "SELECT COALESCE(MAX(id), 0)::BIGINT FROM rsvp.reservation_changes WHERE tenant_id = $1",
+        )
+        .bind(&self.tenant)
+        .fetch_one(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn claim_idempotency_key(
+        &self,
+        key: &str,
+        op: &str,
+        request: &[u8],
+        expires_at: DateTime<Utc>,
+    ) -> Result<bool, Error> {
+        let mut tx = self.begin().await?;
+        sqlx::query(
+            "DELETE FROM rsvp.idempotency_keys WHERE tenant_id = $1 AND expires_at < now()",
+        )
+        .bind(&self.tenant)
+        .execute(&mut tx)
+        .await?;
+        let claimed = sqlx::query(
+            "INSERT INTO rsvp.idempotency_keys (key, op, request, expires_at, tenant_id) VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (tenant_id, key) DO UPDATE SET op = EXCLUDED.op, request = EXCLUDED.request, response = NULL, created_at = now(), expires_at = EXCLUDED.expires_at
+            WHERE rsvp.idempotency_keys.expires_at < now() RETURNING key",
+        )
+        .bind(key)
+        .bind(op)
+        .bind(request)
+        .bind(expires_at)
+        .bind(&self.tenant)
+        .fetch_optional(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok(claimed.is_some())
+    }
+
+    async fn get_idempotency_key(&self, key: &str) -> Result<Option<IdempotencyRecord>, Error> {
+        let mut tx = self.begin().await?;
+        let row = sqlx::query(
+            "SELECT op, request, response FROM rsvp.idempotency_keys WHERE key = $1 AND tenant_id = $2",
+        )
+        .bind(key)
+        .bind(&self.tenant)
+        .fetch_optional(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok(row.map(|row| IdempotencyRecord {
+            op: row.get("op"),
+            request: row.get("request"),
+            response: row.get("response"),
+        }))
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        key: &str,
+        response: &[u8],
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let mut tx = self.begin().await?;
+        sqlx::query(
+            "UPDATE rsvp.idempotency_keys SET response = $2, expires_at = $3 WHERE key = $1 AND tenant_id = $4",
+        )
+        .bind(key)
+        .bind(response)
+        .bind(expires_at)
+        .bind(&self.tenant)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn release_idempotency_key(&self, key: &str) -> Result<(), Error> {
+        let mut tx = self.begin().await?;
+        sqlx::query("DELETE FROM rsvp.idempotency_keys WHERE key = $1 AND tenant_id = $2")
+            .bind(key)
+            .bind(&self.tenant)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+// 监听变更通知，按游标读取租户内新产生的变更记录并推送给订阅者
+async fn listen_changes(
+    store: PgStore,
+    req: abi::ListenRequest,
+    tx: &mpsc::Sender<Result<abi::ListenResponse, Error>>,
+) -> Result<(), Error> {
+    // 先订阅通知再读取游标，避免遗漏两者之间产生的变更
+    let mut listener = PgListener::connect_with(&store.pool).await?;
+    listener.listen("reservation_update").await?;
+    let cursor = store.latest_change_id().await?;
+    let mut subscription = store.subscribers.subscribe(&store.tenant, cursor);
+
+    loop {
+        tokio::select! {
+            _ = tx.closed() => return Ok(()),
+            notification = listener.recv() => {
+                notification?;
+            }
+        }
+
+        // 变更记录中保存了完整的预定，展开后即可转换为预定
+        let mut conn = store.begin().await?;
+        let rows = sqlx::query(
+            "SELECT c.id::BIGINT AS change_id, c.op::TEXT AS op, c.changed_fields, r.*
+            FROM rsvp.reservation_changes c, jsonb_populate_record(NULL::rsvp.reservations, COALESCE(c.new, c.old)) r
+            WHERE c.id > $1 AND c.tenant_id = $2 ORDER BY c.id",
+        )
+        .bind(subscription.cursor())
+        .bind(&store.tenant)
+        .fetch_all(&mut conn)
+        .await?;
+        conn.commit().await?;
+
+        for row in rows {
+            subscription.advance(row.get("change_id"));
+            let change = abi::ListenResponse::from_row(&row)?;
+            if !req.matches(&change.changed_fields) {
+                continue;
+            }
+            if tx.send(Ok(change)).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// 在事务中插入预定，冲突检测由排他约束完成
+async fn insert_row(
+    tx: &mut Transaction<'_, Postgres>,
+    tenant: &str,
+    mut rsvp: abi::Reservation,
+) -> Result<abi::Reservation, Error> {
+    let status =
+        abi::ReservationStatus::try_from(rsvp.status).unwrap_or(abi::ReservationStatus::Pending);
+    let timespan = rsvp.get_timespan();
+    rsvp.tenant_id = tenant.to_string();
+
+    let row = sqlx::query(
+        "INSERT INTO rsvp.reservations (user_id, resource_id, timespan, note, status, tenant_id) VALUES ($1, $2, $3, $4, $5::rsvp.reservation_status, $6) RETURNING id, version, public_id"
+    )
+    .bind(rsvp.user_id.clone())
+    .bind(rsvp.resource_id.clone())
+    .bind(timespan)
+    .bind(rsvp.note.clone())
+    .bind(status.to_string())
+    .bind(tenant)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    rsvp.id = row.get(0);
+    rsvp.version = row.get(1);
+    rsvp.public_id = row.get::<Uuid, _>(2).to_string();
+    Ok(rsvp)
+}
+
+// 导入时对一条预定要做的写入
+enum Import {
+    // 新建预定，ID为0时由序列分配
+    Create(abi::Reservation),
+    // 覆盖ID相同的已有预定
+    Update(abi::Reservation),
+}
+
+impl Import {
+    fn into_reservation(self) -> abi::Reservation {
+        match self {
+            Import::Create(rsvp) | Import::Update(rsvp) => rsvp,
+        }
+    }
+}
+
+// 在写入之前按文件顺序检查这批预定，结果与逐条写入一致:
+// 被覆盖的预定在处理到它之前占用原来的时间段，之后占用新的时间段
+// 与这批之外的预定的冲突由一条查询得到，这批预定之间的冲突在内存中检测
+async fn plan_import(
+    tx: &mut Transaction<'_, Postgres>,
+    tenant: &str,
+    rsvps: Vec<abi::Reservation>,
+    upsert: bool,
+) -> Result<Vec<Result<Import, Error>>, Error> {
+    let ids = rsvps
+        .iter()
+        .map(|rsvp| import_ids(rsvp, upsert))
+        .collect::<Vec<_>>();
+    let keys = ids.iter().filter_map(|(id, _)| *id).collect::<Vec<_>>();
+    let public_ids = ids.iter().filter_map(|(_, p)| *p).collect::<Vec<_>>();
+
+    let olds: Vec<abi::Reservation> = sqlx::query_as(
+        "SELECT * FROM rsvp.reservations WHERE tenant_id = $1 AND (id = ANY($2) OR public_id = ANY($3))",
+    )
+    .bind(tenant)
+    .bind(&keys)
+    .bind(&public_ids)
+    .fetch_all(&mut *tx)
+    .await?;
+    let olds = olds
+        .into_iter()
+        .map(|rsvp| (rsvp.id, rsvp))
+        .collect::<HashMap<_, _>>();
+    // 被其它租户使用的ID和公开ID只查询是否存在，与其它租户的预定冲突一样处理，不读取其内容。
+    // 开启行级安全时看不到其它租户的预定，写入时违反唯一约束会中止整个导入
+    let foreign: Vec<(ReservationId, String)> = sqlx::query_as(
+        "SELECT id, public_id::TEXT FROM rsvp.reservations WHERE tenant_id <> $1 AND (id = ANY($2) OR public_id = ANY($3))",
+    )
+    .bind(tenant)
+    .bind(&keys)
+    .bind(&public_ids)
+    .fetch_all(&mut *tx)
+    .await?;
+    let (foreign_ids, foreign_public_ids): (HashSet<_>, HashSet<_>) = foreign.into_iter().unzip();
+    let mut owners = olds
+        .values()
+        .map(|rsvp| (rsvp.public_id.clone(), rsvp.id))
+        .collect::<HashMap<_, _>>();
+
+    let mut seqs = Vec::with_capacity(rsvps.len());
+    let mut resources = Vec::with_capacity(rsvps.len());
+    let mut starts = Vec::with_capacity(rsvps.len());
+    let mut ends = Vec::with_capacity(rsvps.len());
+    for (seq, rsvp) in rsvps.iter().enumerate() {
+        let (start, end) = span(rsvp);
+        seqs.push(seq as i64);
+        resources.push(rsvp.resource_id.clone());
+        starts.push(start);
+        ends.push(end);
+    }
+    let rows = sqlx::query(
+        "SELECT DISTINCT ON (i.seq) i.seq, r.* FROM UNNEST($2::BIGINT[], $3::TEXT[], $4::TIMESTAMPTZ[], $5::TIMESTAMPTZ[]) AS i(seq, resource_id, start_at, end_at)
+        JOIN rsvp.reservations r ON r.tenant_id = $1 AND r.resource_id = i.resource_id AND r.timespan && tstzrange(i.start_at, i.end_at) AND r.id <> ALL($6)
+        ORDER BY i.seq, r.id",
+    )
+    .bind(tenant)
+    .bind(&seqs)
+    .bind(&resources)
+    .bind(&starts)
+    .bind(&ends)
+    .bind(&keys)
+    .fetch_all(&mut *tx)
+    .await?;
+    let mut conflicts = HashMap::with_capacity(rows.len());
+    for row in rows {
+        let seq: i64 = row.get("seq");
+        conflicts.insert(seq as usize, abi::Reservation::from_row(&row)?);
+    }
+
+    // 这批预定占用的时间段，值为slots中的下标
+    let mut slots = Vec::new();
+    let mut occupied: HashMap<String, IntervalTree<usize>> = HashMap::new();
+    let mut old_slots = HashMap::new();
+    for old in olds.values() {
+        let (start, end) = span(old);
+        occupied
+            .entry(old.resource_id.clone())
+            .or_default()
+            .insert(start, end, slots.len());
+        old_slots.insert(old.id, slots.len());
+        slots.push(old.clone());
+    }
+
+    let mut plans = Vec::with_capacity(rsvps.len());
+    for (seq, (mut rsvp, (id, public_id))) in rsvps.into_iter().zip(ids).enumerate() {
+        rsvp.tenant_id = tenant.to_string();
+        let taken = id.is_some_and(|id| foreign_ids.contains(&id))
+            || public_id.is_some_and(|p| foreign_public_ids.contains(&p.to_string()));
+        if taken {
+            plans.push(Err(unavailable_error(&rsvp)));
+            continue;
+        }
+        let old = id.and_then(|id| olds.get(&id));
+        let owner = public_id.and_then(|p| owners.get(&p.to_string()));
+        if owner.is_some_and(|owner| Some(*owner) != id) {
+            plans.push(Err(Error::InvalidPublicId(rsvp.public_id)));
+            continue;
+        }
+        if let Some(existing) = conflicts.get(&seq) {
+            plans.push(Err(conflict_error(&rsvp, existing)));
+            continue;
+        }
+
+        // 原来的时间段不算冲突，先释放，冲突时再放回
+        if let Some(old) = old {
+            if let Some(tree) = occupied.get_mut(&old.resource_id) {
+                tree.remove(span(old).0);
+            }
+        }
+        let (start, end) = span(&rsvp);
+        let overlap = occupied
+            .entry(rsvp.resource_id.clone())
+            .or_default()
+            .overlap(start, end);
+        if let Some(slot) = overlap {
+            if let Some(old) = old {
+                let (old_start, old_end) = span(old);
+                occupied.entry(old.resource_id.clone()).or_default().insert(
+                    old_start,
+                    old_end,
+                    old_slots[&old.id],
+                );
+            }
+            plans.push(Err(conflict_error(&rsvp, &slots[slot])));
+            continue;
+        }
+        occupied
+            .entry(rsvp.resource_id.clone())
+            .or_default()
+            .insert(start, end, slots.len());
+
+        let plan = match old {
+            Some(old) => {
+                rsvp.id = old.id;
+                rsvp.public_id = match public_id {
+                    Some(public_id) => {
+                        owners.remove(&old.public_id);
+                        public_id.to_string()
+                    }
+                    None => old.public_id.clone(),
+                };
+                Import::Update(rsvp.clone())
+            }
+            None => {
+                rsvp.id = id.unwrap_or_default();
+                rsvp.public_id = public_id.unwrap_or_else(Uuid::now_v7).to_string();
+                Import::Create(rsvp.clone())
+            }
+        };
+        owners.insert(rsvp.public_id.clone(), rsvp.id);
+        slots.push(rsvp);
+        plans.push(Ok(plan));
+    }
+    Ok(plans)
+}
+
+// 在事务中覆盖已有的预定，版本号由触发器增加
+async fn update_row(
+    tx: &mut Transaction<'_, Postgres>,
+    rsvp: &abi::Reservation,
+) -> Result<abi::Reservation, Error> {
+    let status =
+        abi::ReservationStatus::try_from(rsvp.status).unwrap_or(abi::ReservationStatus::Pending);
+    let rsvp = sqlx::query_as(
+        "UPDATE rsvp.reservations SET user_id = $3, status = $4::rsvp.reservation_status, resource_id = $5, timespan = $6, note = $7, public_id = $8::UUID WHERE id = $1 AND tenant_id = $2 RETURNING *",
+    )
+    .bind(rsvp.id)
+    .bind(rsvp.tenant_id.clone())
+    .bind(rsvp.user_id.clone())
+    .bind(status.to_string())
+    .bind(rsvp.resource_id.clone())
+    .bind(rsvp.get_timespan())
+    .bind(rsvp.note.clone())
+    .bind(rsvp.public_id.clone())
+    .fetch_one(&mut *tx)
+    .await?;
+    Ok(rsvp)
+}
+
+// 在事务中写入新的预定，没有指定ID的预定从序列中分配ID
+// 开启行级安全的表不支持COPY FROM，先COPY到临时表，再由一条INSERT写入(同样经过排他约束和触发器)
+async fn copy_rows(
+    tx: &mut Transaction<'_, Postgres>,
+    rsvps: &mut [&mut abi::Reservation],
+) -> Result<(), Error> {
+    if rsvps.is_empty() {
+        return Ok(());
+    }
+    // 指定的ID可能大于序列的当前值，先推进序列，之后分配的ID不会与之重复
+    if let Some(max) = rsvps.iter().map(|rsvp| rsvp.id).max().filter(|id| *id > 0) {
+        sqlx::query(
+            "SELECT setval(pg_get_serial_sequence('rsvp.reservations', 'id'), GREATEST($1, nextval(pg_get_serial_sequence('rsvp.reservations', 'id'))))",
+        )
+        .bind(max)
+        .execute(&mut *tx)
+        .await?;
+    }
+    let missing = rsvps.iter().filter(|rsvp| rsvp.id == 0).count();
+    if missing > 0 {
+        let ids: Vec<ReservationId> = sqlx::query_scalar(
+            "SELECT nextval(pg_get_serial_sequence('rsvp.reservations', 'id')) FROM generate_series(1, $1)",
+        )
+        .bind(missing as i64)
+        .fetch_all(&mut *tx)
+        .await?;
+        for (rsvp, id) in rsvps.iter_mut().filter(|rsvp| rsvp.id == 0).zip(ids) {
+            rsvp.id = id;
+        }
+    }
+
+    // 所有字段都加引号，空的备注会写入空字符串而不是NULL；临时表的seq保留文件中的顺序
+    let mut wtr = csv::WriterBuilder::new()
+        .quote_style(csv::QuoteStyle::Always)
+        .from_writer(vec![]);
+    for rsvp in rsvps.iter_mut() {
+        rsvp.version = 1;
+        let status = abi::ReservationStatus::try_from(rsvp.status)
+            .unwrap_or(abi::ReservationStatus::Pending);
+        wtr.write_record([
+            rsvp.id.to_string(),
+            rsvp.public_id.clone(),
+            rsvp.user_id.clone(),
+            status.to_string(),
+            rsvp.resource_id.clone(),
+            timespan(rsvp),
+            rsvp.note.clone(),
+            rsvp.tenant_id.clone(),
+        ])
+        .map_err(copy_error)?;
+    }
+    let data = wtr.into_inner().map_err(|e| copy_error(e.into_error()))?;
+
+    sqlx::query("CREATE TEMP TABLE reservations_import (seq BIGSERIAL, LIKE rsvp.reservations INCLUDING DEFAULTS) ON COMMIT DROP")
+        .execute(&mut *tx)
+        .await?;
+    let mut copy = tx
+        .copy_in_raw("COPY reservations_import (id, public_id, user_id, status, resource_id, timespan, note, tenant_id) FROM STDIN WITH (FORMAT csv)")
+        .await?;
+    copy.send(data).await?;
+    copy.finish().await?;
+    sqlx::query(
+        "INSERT INTO rsvp.reservations (id, public_id, user_id, status, resource_id, timespan, note, tenant_id)
+        SELECT id, public_id, user_id, status, resource_id, timespan, note, tenant_id FROM reservations_import ORDER BY seq",
+    )
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+// 生成COPY的数据失败按IO错误处理
+fn copy_error(e: impl Into<std::io::Error>) -> Error {
+    Error::DbError(sqlx::Error::Io(e.into()))
+}
+
+// 在事务中设置本次变更的操作者和原因，由触发器写入变更记录
+async fn set_audit(tx: &mut Transaction<'_, Postgres>, audit: &Audit) -> Result<(), Error> {
+    sqlx::query("SELECT set_config('rsvp.actor', $1, true), set_config('rsvp.reason', $2, true)")
+        .bind(audit.actor.to_string())
+        .bind(audit.reason.clone().unwrap_or_default())
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{conformance::conformance_tests, ReservationManager, TestPg};
+
+    async fn setup() -> (TestPg, ReservationManager) {
+        let tdb = TestPg::default();
+        let manager = ReservationManager::new(tdb.get_pool().await);
+        (tdb, manager)
+    }
+
+    conformance_tests!(setup);
+}
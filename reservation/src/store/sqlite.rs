@@ -0,0 +1,774 @@
+use super::{
+    all_fields, changed_fields, conflict_error, import_ids, is_row_error, parse_status,
+    poll_changes, span, to_listen_response, unavailable_error, IdempotencyRecord, ReservationStore,
+    Snapshot, Subscribers,
+};
+use abi::{
+    convert_to_timestamp, convert_to_utc_time, Audit, Error, ReservationId, ReservationStatus,
+    ReservationUpdateType,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use futures::StreamExt;
+use serde_json::Value;
+use sqlx::{sqlite::SqliteRow, Row, Sqlite, SqlitePool, Transaction};
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
+use uuid::Uuid;
+
+// 按租户、状态、用户和资源过滤预定的条件，为空的用户或资源条件以及unknown状态会被忽略
+const CONDITION: &str = "tenant_id = ?1 AND (?2 = 'unknown' OR status = ?2) AND (?3 = '' OR user_id = ?3) AND (?4 = '' OR resource_id = ?4)";
+
+// 预定完全落在[?5, ?6)内的条件，未指定的一端不限制
+const TIMESPAN: &str = "(?5 IS NULL OR start_at >= ?5) AND (?6 IS NULL OR end_at <= ?6)";
+
+// 定义基于sqlite的预定存储，用于单机以及嵌入式部署
+// sqlite不支持排他约束，冲突检测在插入的事务中完成，变更记录也在同一事务中写入
+#[derive(Debug, Clone)]
+pub struct SqliteStore {
+    pool: SqlitePool,
+    // 最新一条变更记录的ID，用于通知本进程的监听者
+    changes: Arc<watch::Sender<i64>>,
+    subscribers: Subscribers,
+    tenant: String,
+}
+
+impl SqliteStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        let (changes, _) = watch::channel(0);
+        Self {
+            pool,
+            changes: Arc::new(changes),
+            subscribers: Subscribers::default(),
+            tenant: abi::DEFAULT_TENANT.to_string(),
+        }
+    }
+
+    // 变更提交后通知监听者
+    fn notify(&self, change_id: Option<i64>) {
+        if let Some(id) = change_id {
+            self.changes.send_replace(id);
+        }
+    }
+
+    // 在事务中修改预定并记录变更，与postgres触发器一致: 版本号总会增加，但只有字段发生变化时才记录变更
+    async fn update<F>(
+        &self,
+        id: ReservationId,
+        version: Option<i64>,
+        audit: &Audit,
+        f: F,
+    ) -> Result<abi::Reservation, Error>
+    where
+        F: FnOnce(&mut abi::Reservation) -> Result<(), Error> + Send,
+    {
+        let mut tx = self.pool.begin().await?;
+        let old = fetch_checked(&mut tx, &self.tenant, id, version).await?;
+        let mut rsvp = old.clone();
+        f(&mut rsvp)?;
+        rsvp.version = old.version + 1;
+
+        let status = ReservationStatus::try_from(rsvp.status).unwrap_or(ReservationStatus::Unknown);
+        sqlx::query("UPDATE reservations SET status = ?2, note = ?3, version = ?4 WHERE id = ?1")
+            .bind(id)
+            .bind(status.to_string())
+            .bind(rsvp.note.clone())
+            .bind(rsvp.version)
+            .execute(&mut tx)
+            .await?;
+
+        let changed_fields = changed_fields(&old, &rsvp);
+        let change_id = if changed_fields.is_empty() {
+            None
+        } else {
+            let change = Snapshot::new(Some(&old), Some(&rsvp), &changed_fields);
+            Some(record(&mut tx, "update", change, audit).await?)
+        };
+        tx.commit().await?;
+
+        self.notify(change_id);
+        Ok(rsvp)
+    }
+}
+
+#[async_trait]
+impl ReservationStore for SqliteStore {
+    fn with_tenant(&self, tenant: &str) -> Self {
+        Self {
+            tenant: tenant.to_string(),
+            ..self.clone()
+        }
+    }
+
+    async fn insert(
+        &self,
+        rsvp: abi::Reservation,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error> {
+        let mut tx = self.pool.begin().await?;
+        let (rsvp, change_id) = insert_row(&mut tx, &self.tenant, rsvp, audit).await?;
+        tx.commit().await?;
+
+        self.notify(Some(change_id));
+        Ok(rsvp)
+    }
+
+    async fn insert_all(
+        &self,
+        rsvps: Vec<abi::Reservation>,
+        audit: &Audit,
+        commit: bool,
+    ) -> Result<Vec<Result<abi::Reservation, Error>>, Error> {
+        // 冲突时不会写入任何数据，不需要保存点
+        let mut tx = self.pool.begin().await?;
+        let mut results = Vec::with_capacity(rsvps.len());
+        let mut last_change = None;
+        for rsvp in rsvps {
+            match insert_row(&mut tx, &self.tenant, rsvp, audit).await {
+                Ok((rsvp, change_id)) => {
+                    last_change = Some(change_id);
+                    results.push(Ok(rsvp));
+                }
+                Err(e @ Error::ConflictReservation(_)) => results.push(Err(e)),
+                Err(e) => return Err(e),
+            }
+        }
+        if commit {
+            tx.commit().await?;
+            self.notify(last_change);
+        } else {
+            tx.rollback().await?;
+        }
+        Ok(results)
+    }
+
+    async fn import(
+        &self,
+        rsvps: Vec<abi::Reservation>,
+        upsert: bool,
+        audit: &Audit,
+    ) -> Result<Vec<Result<abi::Reservation, Error>>, Error> {
+        // 单条预定失败时不会写入任何数据，不需要保存点
+        let mut tx = self.pool.begin().await?;
+        let mut results = Vec::with_capacity(rsvps.len());
+        let mut last_change = None;
+        for rsvp in rsvps {
+            match import_row(&mut tx, &self.tenant, rsvp, upsert, audit).await {
+                Ok((rsvp, change_id)) => {
+                    last_change = change_id.or(last_change);
+                    results.push(Ok(rsvp));
+                }
+                Err(e) if is_row_error(&e) => results.push(Err(e)),
+                Err(e) => return Err(e),
+            }
+        }
+        tx.commit().await?;
+
+        self.notify(last_change);
+        Ok(results)
+    }
+
+    async fn confirm(
+        &self,
+        id: ReservationId,
+        version: Option<i64>,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error> {
+        self.update(id, version, audit, |rsvp| {
+            // 只有pending状态的预定可以确认
+            if rsvp.status != ReservationStatus::Pending as i32 {
+                return Err(Error::NotFound);
+            }
+            rsvp.status = ReservationStatus::Confirmed as i32;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_note(
+        &self,
+        id: ReservationId,
+        note: String,
+        version: Option<i64>,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error> {
+        self.update(id, version, audit, |rsvp| {
+            rsvp.note = note;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete(
+        &self,
+        id: ReservationId,
+        version: Option<i64>,
+        audit: &Audit,
+    ) -> Result<abi::Reservation, Error> {
+        let mut tx = self.pool.begin().await?;
+        let rsvp = fetch_checked(&mut tx, &self.tenant, id, version).await?;
+        sqlx::query("DELETE FROM reservations WHERE id = ?1")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        let change = Snapshot::new(Some(&rsvp), None, &all_fields());
+        let change_id = record(&mut tx, "delete", change, audit).await?;
+        tx.commit().await?;
+
+        self.notify(Some(change_id));
+        Ok(rsvp)
+    }
+
+    async fn get(&self, id: ReservationId) -> Result<abi::Reservation, Error> {
+        let row = sqlx::query("SELECT * FROM reservations WHERE id = ?1 AND tenant_id = ?2")
+            .bind(id)
+            .bind(self.tenant.clone())
+            .fetch_one(&self.pool)
+            .await?;
+        to_reservation(&row)
+    }
+
+    async fn resolve(&self, public_id: Uuid) -> Result<ReservationId, Error> {
+        let id = sqlx::query_scalar(
+            "SELECT id FROM reservations WHERE public_id = ?1 AND tenant_id = ?2",
+        )
+        .bind(public_id.to_string())
+        .bind(self.tenant.clone())
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(id)
+    }
+
+    async fn history(&self, id: ReservationId) -> Result<Vec<abi::ReservationChange>, Error> {
+        let rows = sqlx::query(
+            "SELECT * FROM reservation_changes WHERE reservation_id = ?1 AND tenant_id = ?2 ORDER BY id",
+        )
+        .bind(id)
+        .bind(self.tenant.clone())
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(to_reservation_change).collect()
+    }
+
+    async fn query(
+        &self,
+        query: abi::ReservationQuery,
+    ) -> mpsc::Receiver<Result<abi::Reservation, Error>> {
+        let pool = self.pool.clone();
+        let tenant = self.tenant.clone();
+        let (tx, rx) = mpsc::channel(128);
+
+        // 与postgres一致: 返回完全落在查询区间内的预定，按开始时间排序
+        tokio::spawn(async move {
+            let sql = format!(
+                "SELECT * FROM reservations WHERE {} AND {} ORDER BY start_at {}",
+                CONDITION,
+                TIMESPAN,
+                if query.desc { "DESC" } else { "ASC" }
+            );
+            let mut rows = sqlx::query(&sql)
+                .bind(tenant)
+                .bind(query.get_status().to_string())
+                .bind(query.user_id.clone())
+                .bind(query.resource_id.clone())
+                .bind(
+                    query
+                        .start
+                        .as_ref()
+                        .map(|ts| convert_to_utc_time(ts).timestamp_micros()),
+                )
+                .bind(
+                    query
+                        .end
+                        .as_ref()
+                        .map(|ts| convert_to_utc_time(ts).timestamp_micros()),
+                )
+                .fetch(&pool);
+            while let Some(ret) = rows.next().await {
+                let ret = ret
+                    .map_err(Error::from)
+                    .and_then(|row| to_reservation(&row));
+                let failed = ret.is_err();
+                if tx.send(ret).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        rx
+    }
+
+    async fn filter(
+        &self,
+        filter: &abi::ReservationFilter,
+    ) -> Result<(Vec<abi::Reservation>, i64), Error> {
+        let status = filter.get_status().to_string();
+
+        // 与postgres一致: 从游标处按ID排序，多取一条用于判断是否还有下一页
+        let sql = if filter.desc {
+            format!("SELECT * FROM reservations WHERE {} AND {} AND (?7 = 0 OR id < ?7) ORDER BY id DESC LIMIT ?8", CONDITION, TIMESPAN)
+        } else {
+            format!("SELECT * FROM reservations WHERE {} AND {} AND (?7 = 0 OR id > ?7) ORDER BY id ASC LIMIT ?8", CONDITION, TIMESPAN)
+        };
+        let start = filter
+            .start
+            .as_ref()
+            .map(|ts| convert_to_utc_time(ts).timestamp_micros());
+        let end = filter
+            .end
+            .as_ref()
+            .map(|ts| convert_to_utc_time(ts).timestamp_micros());
+        let rows = sqlx::query(&sql)
+            .bind(self.tenant.clone())
+            .bind(status.clone())
+            .bind(filter.user_id.clone())
+            .bind(filter.resource_id.clone())
+            .bind(start)
+            .bind(end)
+            .bind(filter.cursor)
+            .bind(filter.page_size + 1)
+            .fetch_all(&self.pool)
+            .await?;
+        let rsvps = rows
+            .iter()
+            .map(to_reservation)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let total = sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) FROM reservations WHERE {} AND {}",
+            CONDITION, TIMESPAN
+        ))
+        .bind(self.tenant.clone())
+        .bind(status)
+        .bind(filter.user_id.clone())
+        .bind(filter.resource_id.clone())
+        .bind(start)
+        .bind(end)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok((rsvps, total))
+    }
+
+    async fn listen(
+        &self,
+        req: abi::ListenRequest,
+    ) -> mpsc::Receiver<Result<abi::ListenResponse, Error>> {
+        let pool = self.pool.clone();
+        let tenant = self.tenant.clone();
+        let (tx, rx) = mpsc::channel(128);
+
+        // 先订阅通知再读取游标，避免遗漏两者之间产生的变更
+        let notified = self.changes.subscribe();
+        let cursor =
+            sqlx::query_scalar::<_, i64>("SELECT COALESCE(MAX(id), 0) FROM reservation_changes")
+                .fetch_one(&pool)
+                .await;
+
+        // 开启一个异步任务监听变更，订阅者断开后任务结束
+        let subscription = cursor.map(|cursor| self.subscribers.subscribe(&tenant, cursor));
+        tokio::spawn(async move {
+            let ret = match subscription {
+                Ok(subscription) => {
+                    poll_changes(notified, subscription, req, &tx, |cursor| {
+                        fetch_changes(&pool, &tenant, cursor)
+                    })
+                    .await
+                }
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = ret {
+                let _ = tx.send(Err(e)).await;
+            }
+        });
+
+        rx
+    }
+
+    fn subscribers(&self) -> &Subscribers {
+        &self.subscribers
+    }
+
+    async fn latest_change_id(&self) -> Result<i64, Error> {
+        let id = sqlx::query_scalar(
+            "SELECT COALESCE(MAX(id), 0) FROM reservation_changes WHERE tenant_id = ?1",
+        )
+        .bind(&self.tenant)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(id)
+    }
+
+    async fn claim_idempotency_key(
+        &self,
+        key: &str,
+        op: &str,
+        request: &[u8],
+        expires_at: DateTime<Utc>,
+    ) -> Result<bool, Error> {
+        let now = Utc::now().timestamp_micros();
+        sqlx::query("DELETE FROM idempotency_keys WHERE tenant_id = ?1 AND expires_at < ?2")
+            .bind(self.tenant.clone())
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        let ret = sqlx::query(
+            "INSERT INTO idempotency_keys (key, op, request, created_at, expires_at, tenant_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            ON CONFLICT (tenant_id, key) DO UPDATE SET op = excluded.op, request = excluded.request, response = NULL, created_at = excluded.created_at, expires_at = excluded.expires_at
+            WHERE idempotency_keys.expires_at < ?4",
+        )
+        .bind(key)
+        .bind(op)
+        .bind(request)
+        .bind(now)
+        .bind(expires_at.timestamp_micros())
+        .bind(self.tenant.clone())
+        .execute(&self.pool)
+        .await?;
+        Ok(ret.rows_affected() > 0)
+    }
+
+    async fn get_idempotency_key(&self, key: &str) -> Result<Option<IdempotencyRecord>, Error> {
+        let row = sqlx::query(
+            "SELECT op, request, response FROM idempotency_keys WHERE key = ?1 AND tenant_id = ?2",
+        )
+        .bind(key)
+        .bind(self.tenant.clone())
+        .fetch_optional(&self.pool)
+        .await?;
+        row.map(|row| {
+            Ok(IdempotencyRecord {
+                op: row.try_get("op")?,
+                request: row.try_get("request")?,
+                response: row.try_get("response")?,
+            })
+        })
+        .transpose()
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        key: &str,
+        response: &[u8],
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        sqlx::query(
+            "UPDATE idempotency_keys SET response = ?2, expires_at = ?3 WHERE key = ?1 AND tenant_id = ?4",
+        )
+        .bind(key)
+        .bind(response)
+        .bind(expires_at.timestamp_micros())
+        .bind(self.tenant.clone())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn release_idempotency_key(&self, key: &str) -> Result<(), Error> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE key = ?1 AND tenant_id = ?2")
+            .bind(key)
+            .bind(self.tenant.clone())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+async fn insert_row(
+    tx: &mut Transaction<'_, Sqlite>,
+    tenant: &str,
+    rsvp: abi::Reservation,
+    audit: &Audit,
+) -> Result<(abi::Reservation, i64), Error> {
+    create_row(tx, tenant, rsvp, None, Uuid::now_v7(), audit).await
+}
+
+// 在事务中插入预定并记录变更，返回插入的预定和变更ID，id为空时由数据库分配
+// 冲突检测与插入在同一条语句中完成，语句开始执行时即持有写锁，检测之后不会有其它预定插入
+async fn create_row(
+    tx: &mut Transaction<'_, Sqlite>,
+    tenant: &str,
+    mut rsvp: abi::Reservation,
+    id: Option<ReservationId>,
+    public_id: Uuid,
+    audit: &Audit,
+) -> Result<(abi::Reservation, i64), Error> {
+    rsvp.tenant_id = tenant.to_string();
+    let status = ReservationStatus::try_from(rsvp.status).unwrap_or(ReservationStatus::Pending);
+    let (start, end) = span(&rsvp);
+
+    let ret = sqlx::query(
+        "INSERT INTO reservations (id, user_id, resource_id, start_at, end_at, note, status, public_id, tenant_id)
+        SELECT ?9, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8
+        WHERE NOT EXISTS (SELECT 1 FROM reservations WHERE tenant_id = ?8 AND resource_id = ?2 AND start_at < ?4 AND end_at > ?3)",
+    )
+    .bind(rsvp.user_id.clone())
+    .bind(rsvp.resource_id.clone())
+    .bind(start.timestamp_micros())
+    .bind(end.timestamp_micros())
+    .bind(rsvp.note.clone())
+    .bind(status.to_string())
+    .bind(public_id.to_string())
+    .bind(tenant)
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+
+    if ret.rows_affected() == 0 {
+        return Err(find_conflict(tx, &rsvp, 0).await?);
+    }
+
+    rsvp.id = id.unwrap_or(ret.last_insert_rowid());
+    rsvp.version = 1;
+    rsvp.public_id = public_id.to_string();
+    let change = Snapshot::new(None, Some(&rsvp), &all_fields());
+    let change_id = record(tx, "create", change, audit).await?;
+    Ok((rsvp, change_id))
+}
+
+// 在事务中导入一条预定，返回导入的预定和变更ID(覆盖前后字段都相同时没有变更)
+// 按ID导入且ID已存在时覆盖原来的预定，否则创建新的预定
+async fn import_row(
+    tx: &mut Transaction<'_, Sqlite>,
+    tenant: &str,
+    mut rsvp: abi::Reservation,
+    upsert: bool,
+    audit: &Audit,
+) -> Result<(abi::Reservation, Option<i64>), Error> {
+    let (id, public_id) = import_ids(&rsvp, upsert);
+    if let Some(public_id) = public_id {
+        let owner: Option<(ReservationId, String)> =
+            sqlx::query_as("SELECT id, tenant_id FROM reservations WHERE public_id = ?1")
+                .bind(public_id.to_string())
+                .fetch_optional(&mut *tx)
+                .await?;
+        match owner {
+            Some((_, owner_tenant)) if owner_tenant != tenant => {
+                return Err(unavailable_error(&rsvp))
+            }
+            Some((owner, _)) if Some(owner) != id => {
+                return Err(Error::InvalidPublicId(rsvp.public_id))
+            }
+            _ => {}
+        }
+    }
+    // 只读取租户内的预定，ID被其它租户使用时与其它租户的预定冲突一样处理，不透露其内容
+    let old = match id {
+        Some(id) => sqlx::query("SELECT * FROM reservations WHERE id = ?1 AND tenant_id = ?2")
+            .bind(id)
+            .bind(tenant)
+            .fetch_optional(&mut *tx)
+            .await?
+            .map(|row| to_reservation(&row))
+            .transpose()?,
+        None => None,
+    };
+    if old.is_none() && id.is_some() {
+        let taken: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM reservations WHERE id = ?1)")
+                .bind(id)
+                .fetch_one(&mut *tx)
+                .await?;
+        if taken {
+            return Err(unavailable_error(&rsvp));
+        }
+    }
+    let Some(old) = old else {
+        let public_id = public_id.unwrap_or_else(Uuid::now_v7);
+        let (rsvp, change_id) = create_row(tx, tenant, rsvp, id, public_id, audit).await?;
+        return Ok((rsvp, Some(change_id)));
+    };
+    rsvp.id = old.id;
+    rsvp.tenant_id = old.tenant_id.clone();
+    rsvp.version = old.version + 1;
+    rsvp.public_id = public_id.map_or(old.public_id.clone(), |p| p.to_string());
+    let status = ReservationStatus::try_from(rsvp.status).unwrap_or(ReservationStatus::Pending);
+    let (start, end) = span(&rsvp);
+    // 原来的时间段不算冲突
+    let ret = sqlx::query(
+        "UPDATE reservations SET user_id = ?2, resource_id = ?3, start_at = ?4, end_at = ?5, note = ?6, status = ?7, public_id = ?8, version = ?9
+        WHERE id = ?1 AND NOT EXISTS (SELECT 1 FROM reservations WHERE tenant_id = ?10 AND resource_id = ?3 AND start_at < ?5 AND end_at > ?4 AND id <> ?1)",
+    )
+    .bind(rsvp.id)
+    .bind(rsvp.user_id.clone())
+    .bind(rsvp.resource_id.clone())
+    .bind(start.timestamp_micros())
+    .bind(end.timestamp_micros())
+    .bind(rsvp.note.clone())
+    .bind(status.to_string())
+    .bind(rsvp.public_id.clone())
+    .bind(rsvp.version)
+    .bind(tenant)
+    .execute(&mut *tx)
+    .await?;
+
+    if ret.rows_affected() == 0 {
+        return Err(find_conflict(tx, &rsvp, rsvp.id).await?);
+    }
+
+    let changed_fields = changed_fields(&old, &rsvp);
+    if changed_fields.is_empty() {
+        return Ok((rsvp, None));
+    }
+    let change = Snapshot::new(Some(&old), Some(&rsvp), &changed_fields);
+    let change_id = record(tx, "update", change, audit).await?;
+    Ok((rsvp, Some(change_id)))
+}
+
+// 在事务中查找与预定时间重叠的其它预定(不包括except)，返回对应的冲突错误
+async fn find_conflict(
+    tx: &mut Transaction<'_, Sqlite>,
+    rsvp: &abi::Reservation,
+    except: ReservationId,
+) -> Result<Error, Error> {
+    let (start, end) = span(rsvp);
+    let row = sqlx::query(
+        "SELECT * FROM reservations WHERE tenant_id = ?4 AND resource_id = ?1 AND start_at < ?3 AND end_at > ?2 AND id <> ?5 LIMIT 1",
+    )
+    .bind(rsvp.resource_id.clone())
+    .bind(start.timestamp_micros())
+    .bind(end.timestamp_micros())
+    .bind(rsvp.tenant_id.clone())
+    .bind(except)
+    .fetch_one(&mut *tx)
+    .await?;
+    Ok(conflict_error(rsvp, &to_reservation(&row)?))
+}
+
+// 在事务中写入变更记录，返回变更记录的ID
+async fn record(
+    tx: &mut Transaction<'_, Sqlite>,
+    op: &str,
+    change: Snapshot,
+    audit: &Audit,
+) -> Result<i64, Error> {
+    let ret = sqlx::query(
+        "INSERT INTO reservation_changes (reservation_id, old, new, op, actor, reason, changed_fields, created_at, tenant_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    )
+    .bind(change.reservation_id)
+    .bind(change.old.map(|v| v.to_string()))
+    .bind(change.new.map(|v| v.to_string()))
+    .bind(op)
+    .bind(audit.actor.to_string())
+    .bind(audit.reason.clone())
+    .bind(change.changed_fields.to_string())
+    .bind(Utc::now().timestamp_micros())
+    .bind(change.tenant_id)
+    .execute(&mut *tx)
+    .await?;
+    Ok(ret.last_insert_rowid())
+}
+
+// 在事务中取出租户内的预定，确认其版本与期望一致
+async fn fetch_checked(
+    tx: &mut Transaction<'_, Sqlite>,
+    tenant: &str,
+    id: ReservationId,
+    version: Option<i64>,
+) -> Result<abi::Reservation, Error> {
+    let row = sqlx::query("SELECT * FROM reservations WHERE id = ?1 AND tenant_id = ?2")
+        .bind(id)
+        .bind(tenant)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+    let rsvp = to_reservation(&row)?;
+    match version {
+        Some(expected) if expected != rsvp.version => Err(Error::VersionMismatch {
+            expected,
+            actual: rsvp.version,
+        }),
+        _ => Ok(rsvp),
+    }
+}
+
+fn to_reservation(row: &SqliteRow) -> Result<abi::Reservation, Error> {
+    let status: String = row.try_get("status")?;
+    let note: Option<String> = row.try_get("note")?;
+    Ok(abi::Reservation {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        status: parse_status(&status) as i32,
+        resource_id: row.try_get("resource_id")?,
+        start: Some(convert_to_timestamp(&from_micros(row.try_get("start_at")?))),
+        end: Some(convert_to_timestamp(&from_micros(row.try_get("end_at")?))),
+        note: note.unwrap_or_default(),
+        version: row.try_get("version")?,
+        public_id: row.try_get("public_id")?,
+        tenant_id: row.try_get("tenant_id")?,
+    })
+}
+
+fn to_reservation_change(row: &SqliteRow) -> Result<abi::ReservationChange, Error> {
+    let op: String = row.try_get("op")?;
+    let actor: Option<String> = row.try_get("actor")?;
+    let reason: Option<String> = row.try_get("reason")?;
+    let old = parse_json(row.try_get("old")?);
+    let new = parse_json(row.try_get("new")?);
+    Ok(abi::ReservationChange {
+        id: row.try_get("id")?,
+        reservation_id: row.try_get("reservation_id")?,
+        op: ReservationUpdateType::from_op(&op) as i32,
+        actor: actor.unwrap_or_default(),
+        reason: reason.unwrap_or_default(),
+        changed_at: Some(convert_to_timestamp(&from_micros(
+            row.try_get("created_at")?,
+        ))),
+        fields: abi::diff_fields(old.as_ref(), new.as_ref()),
+    })
+}
+
+// 读取游标之后租户内的变更记录
+async fn fetch_changes(
+    pool: &SqlitePool,
+    tenant: &str,
+    cursor: i64,
+) -> Result<Vec<(i64, abi::ListenResponse)>, Error> {
+    let rows = sqlx::query(
+        "SELECT * FROM reservation_changes WHERE id > ?1 AND tenant_id = ?2 ORDER BY id",
+    )
+    .bind(cursor)
+    .bind(tenant)
+    .fetch_all(pool)
+    .await?;
+    rows.iter()
+        .map(|row| {
+            let op: String = row.try_get("op")?;
+            let changed_fields: String = row.try_get("changed_fields")?;
+            let change = to_listen_response(
+                &op,
+                parse_json(row.try_get("old")?),
+                parse_json(row.try_get("new")?),
+                serde_json::from_str(&changed_fields).unwrap_or_default(),
+            );
+            Ok((row.try_get("id")?, change))
+        })
+        .collect()
+}
+
+fn parse_json(value: Option<String>) -> Option<Value> {
+    value.and_then(|v| serde_json::from_str(&v).ok())
+}
+
+fn from_micros(micros: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(
+        micros.div_euclid(1_000_000),
+        micros.rem_euclid(1_000_000) as u32 * 1000,
+    )
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{conformance::conformance_tests, ReservationManager, TestSqlite};
+    use std::path::Path;
+
+    async fn setup() -> (TestSqlite, ReservationManager<SqliteStore>) {
+        let tdb = TestSqlite::new(Path::new("../migrations/sqlite"));
+        let manager = ReservationManager::with_store(SqliteStore::new(tdb.get_pool().await));
+        (tdb, manager)
+    }
+
+    conformance_tests!(setup);
+}
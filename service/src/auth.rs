@@ -0,0 +1,267 @@
+use abi::{Actor, AuthConfig, JwtAlgorithm, DEFAULT_TENANT};
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tonic::{service::Interceptor, Request, Status};
+
+// 可以操作任意用户预定的角色
+const ADMIN_ROLE: &str = "admin";
+const SERVICE_ROLE: &str = "service";
+
+// jwt中的声明，校验通过后放入请求的extensions中供处理函数使用
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Claims {
+    // 用户ID或服务名
+    pub sub: String,
+    pub exp: u64,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    // 所属租户，声明了租户的token只能访问该租户
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+}
+
+impl Claims {
+    // 管理员和服务账号可以操作任意用户的预定
+    pub fn is_privileged(&self) -> bool {
+        self.has_role(ADMIN_ROLE) || self.has_role(SERVICE_ROLE)
+    }
+
+    pub fn actor(&self) -> Actor {
+        if self.has_role(SERVICE_ROLE) {
+            Actor::service(&self.sub)
+        } else {
+            Actor::user(&self.sub)
+        }
+    }
+
+    // 检查是否可以操作指定用户的预定
+    pub fn authorize(&self, user_id: &str) -> Result<(), abi::Error> {
+        if self.is_privileged() || self.sub == user_id {
+            return Ok(());
+        }
+        Err(abi::Error::PermissionDenied(format!(
+            "{} 不能操作用户 {} 的预定",
+            self.sub, user_id
+        )))
+    }
+
+    // 确定请求访问的租户: 声明了租户时只能访问该租户，
+    // 否则管理员和服务账号可以访问请求的任意租户，普通用户只能访问默认租户
+    pub fn tenant(&self, requested: Option<&str>) -> Result<String, abi::Error> {
+        let tenant = match (self.tenant.as_deref(), requested) {
+            (Some(own), None) => own,
+            (None, Some(requested)) if self.is_privileged() => requested,
+            (None, None) => DEFAULT_TENANT,
+            (own, Some(requested)) if own.unwrap_or(DEFAULT_TENANT) == requested => requested,
+            (_, Some(requested)) => {
+                return Err(abi::Error::PermissionDenied(format!(
+                    "{} 不能访问租户 {}",
+                    self.sub, requested
+                )))
+            }
+        };
+        Ok(tenant.to_string())
+    }
+
+    fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+}
+
+// 校验请求中的bearer token，未配置认证时直接放行
+#[derive(Clone, Default)]
+pub struct AuthInterceptor {
+    verifier: Option<Arc<(DecodingKey, Validation)>>,
+}
+
+impl AuthInterceptor {
+    pub fn new(config: Option<&AuthConfig>) -> Result<Self, abi::Error> {
+        let Some(config) = config else {
+            return Ok(Self::default());
+        };
+
+        let key = config.load_key()?;
+        let (key, algorithm) = match config.algorithm {
+            JwtAlgorithm::HS256 => (DecodingKey::from_secret(&key), Algorithm::HS256),
+            JwtAlgorithm::RS256 => {
+                let key = DecodingKey::from_rsa_pem(&key).map_err(|e| {
+                    abi::Error::InvalidConfig(format!("server.auth.public_key: {}", e))
+                })?;
+                (key, Algorithm::RS256)
+            }
+        };
+        let mut validation = Validation::new(algorithm);
+        if let Some(issuer) = &config.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &config.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        Ok(Self {
+            verifier: Some(Arc::new((key, validation))),
+        })
+    }
+}
+
+impl Interceptor for AuthInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let Some(verifier) = &self.verifier else {
+            return Ok(request);
+        };
+        let (key, validation) = verifier.as_ref();
+
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .ok_or_else(|| Status::unauthenticated("缺少bearer token"))?;
+        let claims = decode::<Claims>(token, key, validation)
+            .map_err(|e| Status::unauthenticated(format!("非法的token: {}", e)))?
+            .claims;
+
+        request.extensions_mut().insert(claims);
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn claims(sub: &str, roles: &[&str]) -> Claims {
+        let exp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 60;
+        Claims {
+            sub: sub.to_string(),
+            exp,
+            roles: roles.iter().map(|r| r.to_string()).collect(),
+            tenant: None,
+        }
+    }
+
+    fn request_with_token(token: &str) -> Request<()> {
+        let mut request = Request::new(());
+        request.metadata_mut().insert(
+            "authorization",
+            format!("Bearer {}", token).parse().unwrap(),
+        );
+        request
+    }
+
+    fn hs256_config() -> AuthConfig {
+        AuthConfig {
+            secret: Some("secret".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn hs256_token_should_be_accepted() {
+        let mut interceptor = AuthInterceptor::new(Some(&hs256_config())).unwrap();
+        let claims = claims("alice", &[]);
+        let token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+
+        let request = interceptor.call(request_with_token(&token)).unwrap();
+        assert_eq!(request.extensions().get::<Claims>(), Some(&claims));
+    }
+
+    #[test]
+    fn missing_or_invalid_token_should_be_unauthenticated() {
+        let mut interceptor = AuthInterceptor::new(Some(&hs256_config())).unwrap();
+        let status = interceptor.call(Request::new(())).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+        let token = encode(
+            &Header::default(),
+            &claims("alice", &[]),
+            &EncodingKey::from_secret(b"other"),
+        )
+        .unwrap();
+        let status = interceptor.call(request_with_token(&token)).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+    }
+
+    #[test]
+    fn rs256_token_should_be_accepted() {
+        let config = AuthConfig {
+            algorithm: JwtAlgorithm::RS256,
+            public_key: Some("fixtures/jwt_rs256.pub.pem".into()),
+            audience: Some("rsvp".to_string()),
+            ..Default::default()
+        };
+        let mut interceptor = AuthInterceptor::new(Some(&config)).unwrap();
+
+        #[derive(Serialize)]
+        struct WithAudience {
+            #[serde(flatten)]
+            claims: Claims,
+            aud: String,
+        }
+        let claims = claims("billing", &[SERVICE_ROLE]);
+        let key = std::fs::read("fixtures/jwt_rs256.key").unwrap();
+        let token = encode(
+            &Header::new(Algorithm::RS256),
+            &WithAudience {
+                claims: claims.clone(),
+                aud: "rsvp".to_string(),
+            },
+            &EncodingKey::from_rsa_pem(&key).unwrap(),
+        )
+        .unwrap();
+
+        let request = interceptor.call(request_with_token(&token)).unwrap();
+        let got = request.extensions().get::<Claims>().unwrap();
+        assert_eq!(got, &claims);
+        assert_eq!(got.actor(), Actor::service("billing"));
+    }
+
+    #[test]
+    fn disabled_interceptor_should_pass_through() {
+        let mut interceptor = AuthInterceptor::new(None).unwrap();
+        let request = interceptor.call(Request::new(())).unwrap();
+        assert!(request.extensions().get::<Claims>().is_none());
+    }
+
+    #[test]
+    fn users_should_only_act_on_their_own_reservations() {
+        assert!(claims("alice", &[]).authorize("alice").is_ok());
+        assert_eq!(
+            claims("alice", &[]).authorize("bob").unwrap_err(),
+            abi::Error::PermissionDenied(String::new())
+        );
+        assert!(claims("root", &[ADMIN_ROLE]).authorize("bob").is_ok());
+        assert!(claims("billing", &[SERVICE_ROLE]).authorize("bob").is_ok());
+    }
+
+    #[test]
+    fn tenant_claim_should_bind_token_to_tenant() {
+        let alice = Claims {
+            tenant: Some("acme".to_string()),
+            ..claims("alice", &[])
+        };
+        assert_eq!(alice.tenant(None).unwrap(), "acme");
+        assert_eq!(alice.tenant(Some("acme")).unwrap(), "acme");
+        assert!(alice.tenant(Some("globex")).is_err());
+
+        let bob = claims("bob", &[]);
+        assert_eq!(bob.tenant(None).unwrap(), DEFAULT_TENANT);
+        assert!(bob.tenant(Some("globex")).is_err());
+
+        let billing = claims("billing", &[SERVICE_ROLE]);
+        assert_eq!(billing.tenant(Some("globex")).unwrap(), "globex");
+    }
+}
@@ -0,0 +1,145 @@
+use abi::{reservation_service_server::ReservationServiceServer, DbConfig};
+use anyhow::{anyhow, Result};
+use sqlx::PgPool;
+use std::{future::Future, time::Duration};
+use tokio::sync::OnceCell;
+use tonic::server::NamedService;
+use tonic_health::{server::HealthReporter, ServingStatus};
+use tracing::{info, warn};
+
+// 健康检查中预定服务的名字，空字符串表示整个服务
+pub const RESERVATION_SERVICE_NAME: &str =
+    <ReservationServiceServer<crate::RsvpService> as NamedService>::NAME;
+
+// 数据库检查的间隔
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+// 设置整个服务和预定服务的健康状态
+pub async fn set_health(reporter: &mut HealthReporter, status: ServingStatus) {
+    for name in ["", RESERVATION_SERVICE_NAME] {
+        reporter.set_service_status(name, status).await;
+    }
+}
+
+// 定期执行检查并上报健康状态: 检查通过之前为NOT_SERVING，之后检查失败时重新标记为NOT_SERVING
+pub async fn report_health<F, Fut>(mut reporter: HealthReporter, interval: Duration, check: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    set_health(&mut reporter, ServingStatus::NotServing).await;
+    let mut serving = false;
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        match check().await {
+            Ok(()) if !serving => {
+                info!("health check passed, serving");
+                set_health(&mut reporter, ServingStatus::Serving).await;
+                serving = true;
+            }
+            Err(e) if serving => {
+                warn!(error = %e, "health check failed, not serving");
+                set_health(&mut reporter, ServingStatus::NotServing).await;
+                serving = false;
+            }
+            Err(e) => warn!(error = %e, "health check failed"),
+            Ok(()) => {}
+        }
+    }
+}
+
+// 检查数据库连接，首次连接成功时执行迁移(或确认迁移已经执行)并开启行级安全
+pub async fn check_database(
+    pool: &PgPool,
+    config: &DbConfig,
+    prepared: &OnceCell<()>,
+) -> Result<()> {
+    prepared
+        .get_or_try_init(|| prepare_database(pool, config))
+        .await?;
+    sqlx::query("SELECT 1").execute(pool).await?;
+    Ok(())
+}
+
+async fn prepare_database(pool: &PgPool, config: &DbConfig) -> Result<()> {
+    if config.migrate_on_startup {
+        reservation::migrate_up(pool).await?;
+        info!("database migrations applied");
+    } else {
+        let pending = reservation::migration_status(pool)
+            .await?
+            .into_iter()
+            .filter(|m| !m.applied)
+            .map(|m| m.version.to_string())
+            .collect::<Vec<_>>();
+        if !pending.is_empty() {
+            return Err(anyhow!("尚未执行的迁移: {}", pending.join(", ")));
+        }
+    }
+    if config.row_level_security {
+        reservation::set_row_level_security(pool, true).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+    use tonic_health::pb::{
+        health_check_response::ServingStatus as Status, health_client::HealthClient,
+        HealthCheckRequest,
+    };
+
+    #[tokio::test]
+    async fn health_should_follow_check_result() {
+        let (reporter, health) = tonic_health::server::health_reporter();
+        let healthy = Arc::new(AtomicBool::new(false));
+        let check = {
+            let healthy = healthy.clone();
+            move || {
+                let healthy = healthy.load(Ordering::SeqCst);
+                async move {
+                    healthy
+                        .then_some(())
+                        .ok_or_else(|| anyhow!("database unavailable"))
+                }
+            }
+        };
+        tokio::spawn(report_health(reporter, Duration::from_millis(20), check));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(health)
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+        );
+        let channel = tonic::transport::Endpoint::from_shared(format!("http://{}", addr))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let mut client = HealthClient::new(channel);
+        let status = |service: &str| HealthCheckRequest {
+            service: service.to_string(),
+        };
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let resp = client.check(status("")).await.unwrap().into_inner();
+        assert_eq!(resp.status, Status::NotServing as i32);
+
+        healthy.store(true, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let resp = client
+            .check(status(RESERVATION_SERVICE_NAME))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(resp.status, Status::Serving as i32);
+    }
+}
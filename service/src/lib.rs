@@ -0,0 +1,389 @@
+mod auth;
+mod health;
+mod prometheus;
+mod rest;
+mod service;
+mod telemetry;
+mod web;
+
+pub use auth::*;
+pub use health::*;
+pub use prometheus::*;
+pub use rest::*;
+pub use service::*;
+pub use telemetry::*;
+pub use web::*;
+
+use abi::{
+    reservation_service_server::ReservationServiceServer, Config, DbConfig, ServerConfig, TlsConfig,
+};
+use anyhow::Result;
+use reservation::ReservationManager;
+use sqlx::PgPool;
+use tokio::sync::{watch, OnceCell};
+use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
+use tonic_health::ServingStatus;
+use tracing::{error, info};
+
+// 按配置创建数据库连接池
+pub async fn connect(config: &DbConfig) -> Result<PgPool, abi::Error> {
+    let pool = config
+        .pool_options()
+        .connect_with(config.connect_options()?)
+        .await?;
+    Ok(pool)
+}
+
+// 按配置创建连接池，但不立即连接数据库(数据库不可用时服务也可以启动并上报健康状态)
+pub fn connect_lazy(config: &DbConfig) -> Result<PgPool, abi::Error> {
+    Ok(config
+        .pool_options()
+        .connect_lazy_with(config.connect_options()?))
+}
+
+// 启动grpc服务，同时提供健康检查和反射服务，配置了指标端口和REST端口时同时启动指标服务和REST网关
+// 数据库检查通过(配置开启时执行迁移并开启行级安全)之前健康状态为NOT_SERVING
+// 收到SIGTERM或ctrl-c后优雅关闭: 标记为NOT_SERVING，结束响应流，等待进行中的请求完成
+pub async fn start_server(config: &Config) -> Result<()> {
+    let pool = connect_lazy(&config.db)?;
+    let manager = ReservationManager::new(pool.clone());
+    // 指标服务与grpc服务共享预定管理，以便统计订阅者
+    if let Some(metrics) = &config.server.metrics {
+        let addr = metrics.addr().parse()?;
+        let exporter = MetricsExporter::new(install_recorder()?, manager.clone())
+            .with_pool(pool.clone(), config.db.max_connections);
+        info!(%addr, "metrics server listening");
+        tokio::spawn(async move {
+            if let Err(e) = exporter.serve(addr).await {
+                error!(error = %e, "metrics server stopped");
+            }
+        });
+    }
+
+    let (mut reporter, health) = tonic_health::server::health_reporter();
+    let checker = {
+        let (pool, db, reporter) = (pool.clone(), config.db.clone(), reporter.clone());
+        tokio::spawn(async move {
+            let prepared = OnceCell::new();
+            let check = || check_database(&pool, &db, &prepared);
+            report_health(reporter, HEALTH_CHECK_INTERVAL, check).await
+        })
+    };
+    let reflection = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(abi::FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+        .build()?;
+
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let auth = AuthInterceptor::new(config.server.auth.as_ref())?;
+    // REST网关与grpc服务共享预定管理和认证，收到关闭信号后一起关闭
+    let rest = match &config.server.rest {
+        Some(rest) => {
+            let addr = rest.addr().parse()?;
+            let service = RsvpService::new(manager.clone())
+                .with_shutdown(shutdown_rx.clone())
+                .with_actor_metadata(config.server.trust_actor_metadata())
+                .with_tenant_metadata(config.server.trust_tenant_header);
+            let router = rest_router(service, auth.clone());
+            let shutdown = shutdown_requested(shutdown_rx.clone());
+            info!(%addr, "rest gateway listening");
+            Some(tokio::spawn(async move {
+                if let Err(e) = serve_rest(router, addr, shutdown).await {
+                    error!(error = %e, "rest gateway stopped");
+                }
+            }))
+        }
+        None => None,
+    };
+    let service = RsvpService::new(manager)
+        .with_shutdown(shutdown_rx)
+        .with_actor_metadata(config.server.trust_actor_metadata())
+        .with_tenant_metadata(config.server.trust_tenant_header);
+    let addr: std::net::SocketAddr =
+        format!("{}:{}", config.server.host, config.server.port).parse()?;
+    info!(%addr, tls = config.server.tls.is_some(), "grpc server listening");
+    server_builder(&config.server)?
+        .add_service(health)
+        .add_service(reflection)
+        .add_service(ReservationServiceServer::with_interceptor(service, auth))
+        .serve_with_shutdown(addr, async move {
+            shutdown_signal().await;
+            info!("shutting down, draining in-flight requests");
+            checker.abort();
+            set_health(&mut reporter, ServingStatus::NotServing).await;
+            shutdown_tx.send_replace(true);
+        })
+        .await?;
+    if let Some(rest) = rest {
+        let _ = rest.await;
+    }
+
+    // 监听任务随响应流结束，之后关闭连接池中的连接
+    pool.close().await;
+    info!("server stopped");
+    Ok(())
+}
+
+// 等待SIGTERM(kubernetes停止容器)或ctrl-c
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!(error = %e, "failed to listen for ctrl-c");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!(error = %e, "failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+// 按配置创建grpc服务，配置了证书时使用tls，配置了grpc_web时同时接受http/1.1上的grpc-web请求
+pub fn server_builder(config: &ServerConfig) -> Result<GrpcWebServer> {
+    let mut builder = Server::builder().accept_http1(config.grpc_web.is_some());
+    if let Some(tls) = &config.tls {
+        builder = builder.tls_config(tls_config(tls)?)?;
+    }
+    Ok(builder.layer(grpc_web_layer(config.grpc_web.as_ref())))
+}
+
+// 配置了客户端CA时，客户端必须提供由其签发的证书(mTLS)
+fn tls_config(config: &TlsConfig) -> Result<ServerTlsConfig, abi::Error> {
+    let (cert, key) = config.load_identity()?;
+    let mut tls = ServerTlsConfig::new().identity(Identity::from_pem(cert, key));
+    if let Some(ca) = config.load_client_ca()? {
+        tls = tls.client_ca_root(Certificate::from_pem(ca));
+    }
+    Ok(tls)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abi::{
+        convert_to_timestamp, reservation_service_client::ReservationServiceClient, HistoryRequest,
+        Reservation, ReserveRequest,
+    };
+    use rcgen::{BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa};
+    use std::{env, fs, net::SocketAddr, path::PathBuf};
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::{Channel, ClientTlsConfig};
+
+    // 测试时生成的CA，以及由其签发的服务端和客户端证书
+    struct TestCerts {
+        dir: PathBuf,
+        ca: String,
+        client: (String, String),
+    }
+
+    impl TestCerts {
+        fn generate() -> Self {
+            let mut params = CertificateParams::new(vec![]);
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            params
+                .distinguished_name
+                .push(DnType::CommonName, "rsvp test ca");
+            let ca = rcgen::Certificate::from_params(params).unwrap();
+
+            let server =
+                rcgen::Certificate::from_params(CertificateParams::new(vec!["localhost".into()]))
+                    .unwrap();
+
+            let mut params = CertificateParams::new(vec![]);
+            params
+                .distinguished_name
+                .push(DnType::CommonName, "billing");
+            params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+            let client = rcgen::Certificate::from_params(params).unwrap();
+
+            let dir = env::temp_dir().join(format!("rsvp-tls-{}", unique_suffix()));
+            fs::create_dir_all(&dir).unwrap();
+            let ca_pem = ca.serialize_pem().unwrap();
+            fs::write(dir.join("ca.pem"), &ca_pem).unwrap();
+            fs::write(
+                dir.join("server.pem"),
+                server.serialize_pem_with_signer(&ca).unwrap(),
+            )
+            .unwrap();
+            fs::write(dir.join("server.key"), server.serialize_private_key_pem()).unwrap();
+
+            Self {
+                dir,
+                ca: ca_pem,
+                client: (
+                    client.serialize_pem_with_signer(&ca).unwrap(),
+                    client.serialize_private_key_pem(),
+                ),
+            }
+        }
+
+        fn tls_config(&self, mtls: bool) -> TlsConfig {
+            TlsConfig {
+                cert: self.dir.join("server.pem"),
+                key: self.dir.join("server.key"),
+                client_ca: mtls.then(|| self.dir.join("ca.pem")),
+            }
+        }
+    }
+
+    impl Drop for TestCerts {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn unique_suffix() -> u128 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    }
+
+    // 使用内存存储在随机端口上启动服务
+    async fn start_test_server(tls: TlsConfig) -> SocketAddr {
+        let config = ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            tls: Some(tls),
+            auth: None,
+            metrics: None,
+            rest: None,
+            grpc_web: None,
+            trust_tenant_header: false,
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let service = RsvpService::new(ReservationManager::in_memory())
+            .with_actor_metadata(config.trust_actor_metadata());
+        let server = server_builder(&config)
+            .unwrap()
+            .add_service(ReservationServiceServer::new(service));
+        tokio::spawn(server.serve_with_incoming(TcpListenerStream::new(listener)));
+        addr
+    }
+
+    async fn connect_client(
+        addr: SocketAddr,
+        certs: &TestCerts,
+        identity: bool,
+    ) -> Result<ReservationServiceClient<Channel>> {
+        let mut tls = ClientTlsConfig::new()
+            .domain_name("localhost")
+            .ca_certificate(Certificate::from_pem(&certs.ca));
+        if identity {
+            let (cert, key) = &certs.client;
+            tls = tls.identity(Identity::from_pem(cert, key));
+        }
+        let channel = Channel::from_shared(format!("https://{}", addr))?
+            .tls_config(tls)?
+            .connect()
+            .await?;
+        Ok(ReservationServiceClient::new(channel))
+    }
+
+    fn reserve_request(actor: Option<&str>) -> tonic::Request<ReserveRequest> {
+        let start = chrono::DateTime::parse_from_rfc3339("2022-12-25T15:00:00-07:00").unwrap();
+        let end = chrono::DateTime::parse_from_rfc3339("2022-12-28T12:00:00-07:00").unwrap();
+        let mut request = tonic::Request::new(ReserveRequest {
+            reservation: Some(Reservation {
+                user_id: "alice".to_string(),
+                resource_id: "ocean-view-room-713".to_string(),
+                start: Some(convert_to_timestamp(&start.into())),
+                end: Some(convert_to_timestamp(&end.into())),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        if let Some(actor) = actor {
+            request
+                .metadata_mut()
+                .insert(ACTOR_METADATA, actor.parse().unwrap());
+        }
+        request
+    }
+
+    #[tokio::test]
+    async fn mtls_client_identity_should_be_the_actor() {
+        let certs = TestCerts::generate();
+        let addr = start_test_server(certs.tls_config(true)).await;
+        let mut client = connect_client(addr, &certs, true).await.unwrap();
+
+        // 元数据中的操作者不能冒充客户端证书中的身份
+        let status = client
+            .reserve(reserve_request(Some("user:mallory")))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+        let rsvp = client
+            .reserve(reserve_request(None))
+            .await
+            .unwrap()
+            .into_inner()
+            .reservation
+            .unwrap();
+        let changes = client
+            .history(HistoryRequest {
+                id: rsvp.id,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .changes;
+        assert_eq!(changes[0].actor, "service:billing");
+    }
+
+    #[tokio::test]
+    async fn mtls_should_reject_client_without_certificate() {
+        let certs = TestCerts::generate();
+        let addr = start_test_server(certs.tls_config(true)).await;
+        let ret = match connect_client(addr, &certs, false).await {
+            Ok(mut client) => client.reserve(reserve_request(None)).await.map(|_| ()),
+            Err(_) => return,
+        };
+        assert!(ret.is_err());
+    }
+
+    #[tokio::test]
+    async fn tls_without_client_ca_should_not_trust_metadata_actor() {
+        let certs = TestCerts::generate();
+        let addr = start_test_server(certs.tls_config(false)).await;
+        let mut client = connect_client(addr, &certs, false).await.unwrap();
+
+        // 没有可以校验的身份时，元数据中的操作者不会记录到审计信息中
+        let rsvp = client
+            .reserve(reserve_request(Some("user:mallory")))
+            .await
+            .unwrap()
+            .into_inner()
+            .reservation
+            .unwrap();
+        let changes = client
+            .history(HistoryRequest {
+                id: rsvp.id,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .changes;
+        assert_eq!(changes[0].actor, "user:anonymous");
+    }
+}
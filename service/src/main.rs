@@ -1,3 +1,82 @@
-fn main() {
-    println!("Hello, world!");
+use abi::Config;
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use std::{env, path::PathBuf};
+
+#[derive(Debug, Parser)]
+#[command(about = "预定服务")]
+struct Cli {
+    /// 配置文件路径
+    #[arg(short, long, env = "RSVP_CONFIG", default_value = "config.yml")]
+    config: PathBuf,
+
+    /// 覆盖配置项，优先级高于配置文件和环境变量，例如 --set db.host=localhost
+    #[arg(short, long = "set", value_name = "KEY=VALUE", value_parser = parse_override)]
+    set: Vec<(String, String)>,
+
+    // 未指定子命令时启动服务
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// 启动grpc服务
+    Serve,
+    /// 管理数据库迁移
+    #[command(subcommand)]
+    Migrate(MigrateCommand),
+}
+
+#[derive(Debug, Subcommand)]
+enum MigrateCommand {
+    /// 执行所有尚未执行的迁移
+    Up,
+    /// 回滚最近一次迁移，或回滚到指定版本
+    Down {
+        /// 回滚到的版本，之后的迁移都会被回滚
+        #[arg(long)]
+        to: Option<i64>,
+    },
+    /// 列出迁移及其执行状态
+    Status,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let config = Config::load_with(&cli.config, env::vars(), cli.set)?;
+    let _guard = service::init_tracing(&config.log)?;
+
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => service::start_server(&config).await,
+        Command::Migrate(cmd) => migrate(&config, cmd).await,
+    }
+}
+
+async fn migrate(config: &Config, cmd: MigrateCommand) -> Result<()> {
+    let pool = service::connect(&config.db).await?;
+    match cmd {
+        MigrateCommand::Up => {
+            reservation::migrate_up(&pool).await?;
+            println!("数据库迁移完成");
+        }
+        MigrateCommand::Down { to } => match reservation::migrate_down(&pool, to).await? {
+            Some(version) => println!("已回滚到版本 {}", version),
+            None => println!("没有可回滚的迁移"),
+        },
+        MigrateCommand::Status => {
+            for m in reservation::migration_status(&pool).await? {
+                let state = if m.applied { "applied" } else { "pending" };
+                println!("{}\t{}\t{}", m.version, state, m.description);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_override(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+        .ok_or_else(|| format!("{} 不是 KEY=VALUE 格式", s))
 }
@@ -0,0 +1,177 @@
+use anyhow::Result;
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use metrics::{describe_counter, describe_gauge, describe_histogram, gauge, histogram, Unit};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use reservation::{ReservationManager, ReservationStore};
+use sqlx::PgPool;
+use std::{convert::Infallible, net::SocketAddr, sync::Arc, time::Duration};
+use tonic::Code;
+use tracing::warn;
+
+pub const RPC_DURATION: &str = "rsvp_rpc_duration_seconds";
+pub const DB_POOL_CONNECTIONS: &str = "rsvp_db_pool_connections";
+pub const DB_POOL_IDLE_CONNECTIONS: &str = "rsvp_db_pool_idle_connections";
+pub const DB_POOL_MAX_CONNECTIONS: &str = "rsvp_db_pool_max_connections";
+pub const LISTEN_SUBSCRIBERS: &str = "rsvp_listen_subscribers";
+pub const LISTEN_LAG: &str = "rsvp_listen_lag";
+
+// rpc耗时的分桶(秒)
+const RPC_BUCKETS: [f64; 11] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+// 安装全局的prometheus recorder，之后通过metrics门面记录的指标都汇总到返回的handle中
+pub fn install_recorder() -> Result<PrometheusHandle> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Full(RPC_DURATION.to_string()), &RPC_BUCKETS)?
+        .install_recorder()?;
+    describe_metrics();
+    Ok(handle)
+}
+
+fn describe_metrics() {
+    describe_counter!(
+        reservation::RESERVATIONS_CREATED,
+        "按资源类型统计创建的预定"
+    );
+    describe_counter!(
+        reservation::RESERVATIONS_CONFIRMED,
+        "按资源类型统计确认的预定"
+    );
+    describe_counter!(
+        reservation::RESERVATIONS_CANCELLED,
+        "按资源类型统计取消的预定"
+    );
+    describe_counter!(
+        reservation::RESERVATION_CONFLICTS,
+        "按资源类型统计因时间冲突被拒绝的预定"
+    );
+    describe_histogram!(RPC_DURATION, Unit::Seconds, "按方法和状态码统计的rpc耗时");
+    describe_gauge!(DB_POOL_CONNECTIONS, "连接池中已经建立的连接数");
+    describe_gauge!(DB_POOL_IDLE_CONNECTIONS, "连接池中空闲的连接数");
+    describe_gauge!(DB_POOL_MAX_CONNECTIONS, "连接池允许的最大连接数");
+    describe_gauge!(LISTEN_SUBSCRIBERS, "当前的变更订阅者数量");
+    describe_gauge!(LISTEN_LAG, "最新的变更ID与最落后的订阅者游标之差");
+}
+
+// 记录一次rpc的耗时和状态码
+pub fn record_rpc(method: &'static str, code: Code, elapsed: Duration) {
+    histogram!(RPC_DURATION, "method" => method, "code" => format!("{:?}", code))
+        .record(elapsed.as_secs_f64());
+}
+
+// 抓取时才能得到的指标(连接池和订阅者)在每次抓取时更新
+pub struct MetricsExporter<S> {
+    handle: PrometheusHandle,
+    manager: ReservationManager<S>,
+    pool: Option<(PgPool, u32)>,
+}
+
+impl<S: ReservationStore> MetricsExporter<S> {
+    pub fn new(handle: PrometheusHandle, manager: ReservationManager<S>) -> Self {
+        Self {
+            handle,
+            manager,
+            pool: None,
+        }
+    }
+
+    // 同时导出连接池的使用情况
+    pub fn with_pool(mut self, pool: PgPool, max_connections: u32) -> Self {
+        self.pool = Some((pool, max_connections));
+        self
+    }
+
+    // 生成prometheus文本格式的指标
+    pub async fn render(&self) -> String {
+        if let Some((pool, max_connections)) = &self.pool {
+            gauge!(DB_POOL_CONNECTIONS).set(pool.size() as f64);
+            gauge!(DB_POOL_IDLE_CONNECTIONS).set(pool.num_idle() as f64);
+            gauge!(DB_POOL_MAX_CONNECTIONS).set(*max_connections as f64);
+        }
+        gauge!(LISTEN_SUBSCRIBERS).set(self.manager.listen_subscribers() as f64);
+        // 读取最新变更ID失败时保留上一次的值
+        match self.manager.listen_lag().await {
+            Ok(lag) => gauge!(LISTEN_LAG).set(lag as f64),
+            Err(e) => warn!(error = %e, "failed to compute listen lag"),
+        }
+        self.handle.render()
+    }
+
+    // 在addr上提供 GET /metrics，直到服务退出
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        let exporter = Arc::new(self);
+        let make_service = make_service_fn(move |_| {
+            let exporter = exporter.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let exporter = exporter.clone();
+                    async move { Ok::<_, Infallible>(exporter.handle(req).await) }
+                }))
+            }
+        });
+        Server::try_bind(&addr)?.serve(make_service).await?;
+        Ok(())
+    }
+
+    async fn handle(&self, req: Request<Body>) -> Response<Body> {
+        if req.method() != Method::GET || req.uri().path() != "/metrics" {
+            let mut resp = Response::new(Body::empty());
+            *resp.status_mut() = StatusCode::NOT_FOUND;
+            return resp;
+        }
+        let mut resp = Response::new(Body::from(self.render().await));
+        resp.headers_mut().insert(
+            CONTENT_TYPE,
+            "text/plain; version=0.0.4"
+                .parse()
+                .expect("valid header value"),
+        );
+        resp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abi::{Actor, Reservation};
+    use reservation::Rsvp;
+
+    #[tokio::test]
+    async fn metrics_should_be_rendered() {
+        let handle = install_recorder().unwrap();
+        let manager = ReservationManager::in_memory();
+        let exporter = MetricsExporter::new(handle, manager.clone());
+
+        let rsvp = Reservation::new_pending(
+            "alice",
+            "metrics-test-room-1",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        let audit = Actor::user("alice").into();
+        manager.reserve(rsvp.clone(), &audit).await.unwrap();
+        manager.reserve(rsvp, &audit).await.unwrap_err();
+        record_rpc("metrics_test", Code::Ok, Duration::from_millis(3));
+        let _rx = manager.listen(Default::default()).await;
+
+        let text = exporter.render().await;
+        assert!(text
+            .contains(r#"rsvp_reservations_created_total{resource_type="metrics-test-room"} 1"#));
+        assert!(text
+            .contains(r#"rsvp_reservation_conflicts_total{resource_type="metrics-test-room"} 1"#));
+        assert!(text.contains(
+            r#"rsvp_rpc_duration_seconds_bucket{method="metrics_test",code="Ok",le="0.005"} 1"#
+        ));
+        assert!(text.contains("rsvp_listen_subscribers 1"));
+        assert!(text.contains("rsvp_listen_lag 0"));
+
+        let req = Request::get("/other").body(Body::empty()).unwrap();
+        assert_eq!(exporter.handle(req).await.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,1288 @@
+use crate::{AuthInterceptor, ImportRequest, ReserveAllRequest, RsvpService, REQUEST_ID_METADATA};
+use abi::{
+    reservation_service_server::ReservationService, CancelRequest, ConfirmRequest, FilterRequest,
+    GetRequest, ICalEvent, ICalImportOptions, ICalendar, ListenRequest, QueryRequest, Reservation,
+    ReservationFilter, ReservationQuery, ReservationStatus, ReservationUpdateType, ReserveRequest,
+    UpdateRequest,
+};
+use anyhow::Result;
+use axum::{
+    body::{Bytes, StreamBody},
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{
+        header::{AUTHORIZATION, CONTENT_DISPOSITION, CONTENT_TYPE},
+        HeaderMap, HeaderValue, Request as HttpRequest, StatusCode,
+    },
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use futures::{future, stream, Stream, StreamExt, TryStreamExt};
+use reservation::ReservationStore;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{convert::Infallible, future::Future, net::SocketAddr, sync::Arc};
+use tonic::{metadata::MetadataMap, service::Interceptor, Code, Extensions, Request, Status};
+use uuid::Uuid;
+
+// REST/JSON网关，将http请求转换为grpc请求交给同一个服务实现处理，认证、租户、审计和指标与grpc一致
+//   POST   /reservations           创建预定，请求体为ReserveRequest
+//   GET    /reservations           按user_id、resource_id、status、start、end过滤，按ID分页(cursor、page_size、desc)
+//   GET    /reservations/{id}      查看预定，id为预定ID或公开ID
+//   PATCH  /reservations/{id}      修改备注(note)或确认预定(status=confirmed)
+//   DELETE /reservations/{id}      取消预定，expected_version、idempotency_key、reason通过查询参数提供
+//   GET    /changes                变更流(server-sent events)，可用changed_fields=status,note过滤
+//   GET    /calendars/users/{user_id}.ics          用户预定的iCalendar订阅(webcal)
+//   GET    /calendars/resources/{resource_id}.ics  资源预定的iCalendar订阅(webcal)
+//   POST   /calendars/resources/{resource_id}/import  导入iCalendar中的事件为该资源的预定，dry_run时只检查冲突
+//   GET    /admin/reservations/export  按user_id、resource_id、status导出所有预定(format=ndjson|csv)，边查询边输出
+//   POST   /admin/reservations/import  导入export输出的文件，upsert时按ID覆盖已有的预定
+struct Gateway<S> {
+    service: RsvpService<S>,
+    auth: AuthInterceptor,
+}
+
+pub fn rest_router<S: ReservationStore>(service: RsvpService<S>, auth: AuthInterceptor) -> Router {
+    Router::new()
+        .route("/reservations", get(list::<S>).post(reserve::<S>))
+        .route(
+            "/reservations/:id",
+            get(get_one::<S>).patch(patch::<S>).delete(cancel::<S>),
+        )
+        .route("/changes", get(listen::<S>))
+        .route("/calendars/users/:user_id", get(user_calendar::<S>))
+        .route(
+            "/calendars/resources/:resource_id",
+            get(resource_calendar::<S>),
+        )
+        .route(
+            "/calendars/resources/:resource_id/import",
+            post(import_calendar::<S>),
+        )
+        .route("/admin/reservations/export", get(export::<S>))
+        .route(
+            "/admin/reservations/import",
+            post(import::<S>).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)),
+        )
+        .layer(middleware::from_fn(with_request_id))
+        .with_state(Arc::new(Gateway { service, auth }))
+}
+
+// 在addr上提供REST网关，直到shutdown完成
+pub async fn serve_rest(
+    router: Router,
+    addr: SocketAddr,
+    shutdown: impl Future<Output = ()>,
+) -> Result<()> {
+    axum::Server::try_bind(&addr)?
+        .serve(router.into_make_service())
+        .with_graceful_shutdown(shutdown)
+        .await?;
+    Ok(())
+}
+
+// 未提供请求ID时生成一个，grpc处理函数使用同一个ID，网关自身返回的错误也带上它
+async fn with_request_id<B>(mut req: HttpRequest<B>, next: Next<B>) -> Response {
+    let request_id = match req.headers().get(REQUEST_ID_METADATA) {
+        Some(value) if !value.is_empty() => value.clone(),
+        _ => HeaderValue::from_str(&Uuid::new_v4().to_string()).expect("uuid is a valid header"),
+    };
+    req.headers_mut()
+        .insert(REQUEST_ID_METADATA, request_id.clone());
+    let mut resp = next.run(req).await;
+    resp.headers_mut()
+        .entry(REQUEST_ID_METADATA)
+        .or_insert(request_id);
+    resp
+}
+
+impl<S: ReservationStore> Gateway<S> {
+    // 请求头作为grpc元数据(authorization、x-tenant、x-actor、x-request-id)，并经过同样的认证
+    fn request<T>(&self, headers: HeaderMap, message: T) -> Result<Request<T>, RestError> {
+        let request = Request::from_parts(
+            MetadataMap::from_headers(headers),
+            Extensions::default(),
+            (),
+        );
+        let (metadata, extensions, ()) = self.auth.clone().call(request)?.into_parts();
+        Ok(Request::from_parts(metadata, extensions, message))
+    }
+}
+
+// 失败的grpc状态，转换为对应的http状态码和json错误
+pub struct RestError(Box<Status>);
+
+impl From<Status> for RestError {
+    fn from(status: Status) -> Self {
+        Self(Box::new(status))
+    }
+}
+
+impl From<abi::Error> for RestError {
+    fn from(e: abi::Error) -> Self {
+        Status::from(e).into()
+    }
+}
+
+impl IntoResponse for RestError {
+    fn into_response(self) -> Response {
+        let body = json!({
+            "code": format!("{:?}", self.0.code()),
+            "message": self.0.message(),
+        });
+        (http_status(self.0.code()), Json(body)).into_response()
+    }
+}
+
+fn http_status(code: Code) -> StatusCode {
+    match code {
+        Code::Ok => StatusCode::OK,
+        Code::InvalidArgument | Code::OutOfRange => StatusCode::BAD_REQUEST,
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted | Code::FailedPrecondition => StatusCode::CONFLICT,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Cancelled => StatusCode::REQUEST_TIMEOUT,
+        Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        Code::Unknown | Code::Internal | Code::DataLoss => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+fn reply<T, R: Serialize>(
+    resp: tonic::Response<T>,
+    status: StatusCode,
+    f: impl FnOnce(T) -> R,
+) -> Response {
+    (status, Json(f(resp.into_inner()))).into_response()
+}
+
+// 路径中的数字为预定ID，否则为公开ID
+fn id_parts(id: String) -> (i64, String) {
+    match id.parse() {
+        Ok(id) => (id, String::new()),
+        Err(_) => (0, id),
+    }
+}
+
+// 修改类请求的公共参数
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ChangeParams {
+    expected_version: i64,
+    idempotency_key: String,
+    reason: String,
+}
+
+// PATCH的请求体，一次只能修改备注或确认预定
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PatchReservation {
+    note: Option<String>,
+    status: Option<ReservationStatus>,
+    expected_version: i64,
+    idempotency_key: String,
+    reason: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ListParams {
+    user_id: String,
+    resource_id: String,
+    status: Option<ReservationStatus>,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    cursor: i64,
+    page_size: i64,
+    desc: bool,
+}
+
+// 日历订阅的参数，未指定状态时包含待确认和已确认的预定
+// 日历客户端通常无法设置请求头，令牌可以通过access_token提供
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct CalendarParams {
+    status: Option<ReservationStatus>,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    access_token: Option<String>,
+}
+
+// 日历导入的参数，tz为浮动时间和全天事件的时区(默认UTC)，until为重复事件展开的截止时间
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ImportParams {
+    user_id: String,
+    tz: Option<String>,
+    until: Option<DateTime<Utc>>,
+    dry_run: bool,
+    reason: String,
+}
+
+// 日历导入的结果，按日历中事件的顺序给出每个事件(重复事件的每一次)的处理结果
+#[derive(Debug, Default, Serialize)]
+struct ImportReport {
+    dry_run: bool,
+    created: usize,
+    conflicts: usize,
+    invalid: usize,
+    events: Vec<ImportedEvent>,
+}
+
+#[derive(Debug, Serialize)]
+struct ImportedEvent {
+    uid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end: Option<DateTime<Utc>>,
+    result: ImportResult,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reservation: Option<Reservation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+// created: 已创建，ok: 只做检查且可以创建，conflict: 与已有或同批的预定冲突，invalid: 事件或预定不合法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ImportResult {
+    Created,
+    Ok,
+    Conflict,
+    Invalid,
+}
+
+// 批量导入的请求体上限，导出的文件通常远大于axum默认的2MB
+const IMPORT_BODY_LIMIT: usize = 64 * 1024 * 1024;
+
+// 导出的csv列，与Reservation序列化的字段顺序一致
+const EXPORT_FIELDS: [&str; 10] = [
+    "id",
+    "user_id",
+    "status",
+    "resource_id",
+    "start",
+    "end",
+    "note",
+    "version",
+    "public_id",
+    "tenant_id",
+];
+
+// 批量导出和导入的文件格式，ndjson为每行一个json格式的预定
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum BulkFormat {
+    #[default]
+    Ndjson,
+    Csv,
+}
+
+// 批量导出的参数，未指定状态时依次导出待确认、已确认和已阻塞的预定，每种状态内按开始时间排序
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ExportParams {
+    format: BulkFormat,
+    user_id: String,
+    resource_id: String,
+    status: Option<ReservationStatus>,
+    desc: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct BulkImportParams {
+    format: BulkFormat,
+    upsert: bool,
+    reason: String,
+}
+
+// 批量导入的结果，按文件中的顺序给出每一行的处理结果
+#[derive(Debug, Default, Serialize)]
+struct BulkImportReport {
+    upsert: bool,
+    created: usize,
+    updated: usize,
+    conflicts: usize,
+    invalid: usize,
+    rows: Vec<ImportedRow>,
+}
+
+#[derive(Debug, Serialize)]
+struct ImportedRow {
+    // 文件中的行号，从1开始(csv的表头为第1行)
+    line: u64,
+    result: RowResult,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    public_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+// created: 已创建，updated: 覆盖了已有的预定，conflict: 与已有或之前行的预定冲突，invalid: 无法解析或不合法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum RowResult {
+    Created,
+    Updated,
+    Conflict,
+    Invalid,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ListenParams {
+    // 逗号分隔的字段名
+    changed_fields: String,
+}
+
+async fn reserve<S: ReservationStore>(
+    State(gateway): State<Arc<Gateway<S>>>,
+    headers: HeaderMap,
+    Json(mut req): Json<ReserveRequest>,
+) -> Result<Response, RestError> {
+    // 未指定状态时创建pending状态的预定
+    if let Some(rsvp) = req.reservation.as_mut() {
+        if rsvp.status == ReservationStatus::Unknown as i32 {
+            rsvp.status = ReservationStatus::Pending as i32;
+        }
+    }
+    let resp = gateway
+        .service
+        .reserve(gateway.request(headers, req)?)
+        .await?;
+    Ok(reply(resp, StatusCode::CREATED, |r| r.reservation))
+}
+
+async fn get_one<S: ReservationStore>(
+    State(gateway): State<Arc<Gateway<S>>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Response, RestError> {
+    let (id, public_id) = id_parts(id);
+    let req = GetRequest { id, public_id };
+    let resp = gateway.service.get(gateway.request(headers, req)?).await?;
+    Ok(reply(resp, StatusCode::OK, |r| r.reservation))
+}
+
+async fn patch<S: ReservationStore>(
+    State(gateway): State<Arc<Gateway<S>>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(body): Json<PatchReservation>,
+) -> Result<Response, RestError> {
+    let (id, public_id) = id_parts(id);
+    match (body.note, body.status) {
+        (Some(note), None) => {
+            let req = UpdateRequest {
+                id,
+                public_id,
+                note,
+                idempotency_key: body.idempotency_key,
+                expected_version: body.expected_version,
+                reason: body.reason,
+            };
+            let resp = gateway
+                .service
+                .update(gateway.request(headers, req)?)
+                .await?;
+            Ok(reply(resp, StatusCode::OK, |r| r.reservation))
+        }
+        (None, Some(ReservationStatus::Confirmed)) => {
+            let req = ConfirmRequest {
+                id,
+                public_id,
+                idempotency_key: body.idempotency_key,
+                expected_version: body.expected_version,
+                reason: body.reason,
+            };
+            let resp = gateway
+                .service
+                .confirm(gateway.request(headers, req)?)
+                .await?;
+            Ok(reply(resp, StatusCode::OK, |r| r.reservation))
+        }
+        (None, Some(status)) => Err(Status::invalid_argument(format!(
+            "只能将预定确认为confirmed，不能修改为{}",
+            status
+        ))
+        .into()),
+        _ => Err(Status::invalid_argument("note和status必须且只能提供一个").into()),
+    }
+}
+
+async fn cancel<S: ReservationStore>(
+    State(gateway): State<Arc<Gateway<S>>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(params): Query<ChangeParams>,
+) -> Result<Response, RestError> {
+    let (id, public_id) = id_parts(id);
+    let req = CancelRequest {
+        id,
+        public_id,
+        idempotency_key: params.idempotency_key,
+        expected_version: params.expected_version,
+        reason: params.reason,
+    };
+    let resp = gateway
+        .service
+        .cancel(gateway.request(headers, req)?)
+        .await?;
+    Ok(reply(resp, StatusCode::OK, |r| r.reservation))
+}
+
+async fn list<S: ReservationStore>(
+    State(gateway): State<Arc<Gateway<S>>>,
+    headers: HeaderMap,
+    Query(params): Query<ListParams>,
+) -> Result<Response, RestError> {
+    let filter = ReservationFilter {
+        user_id: params.user_id,
+        resource_id: params.resource_id,
+        status: params.status.map(|s| s as i32).unwrap_or_default(),
+        cursor: params.cursor,
+        page_size: params.page_size,
+        desc: params.desc,
+        start: params.start.as_ref().map(abi::convert_to_timestamp),
+        end: params.end.as_ref().map(abi::convert_to_timestamp),
+    };
+    let req = FilterRequest {
+        filter: Some(filter),
+    };
+    let resp = gateway
+        .service
+        .filter(gateway.request(headers, req)?)
+        .await?;
+    Ok(reply(resp, StatusCode::OK, |r| r))
+}
+
+async fn listen<S: ReservationStore>(
+    State(gateway): State<Arc<Gateway<S>>>,
+    headers: HeaderMap,
+    Query(params): Query<ListenParams>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, RestError> {
+    let changed_fields = params
+        .changed_fields
+        .split(',')
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .map(String::from)
+        .collect();
+    let req = ListenRequest { changed_fields };
+    let stream = gateway
+        .service
+        .listen(gateway.request(headers, req)?)
+        .await?
+        .into_inner();
+    // 事件名为变更类型，数据为json格式的ListenResponse
+    let events = stream.map(|ret| {
+        let event = match ret {
+            Ok(change) => {
+                let op = ReservationUpdateType::try_from(change.op)
+                    .unwrap_or(ReservationUpdateType::Unknown)
+                    .as_op();
+                Event::default()
+                    .event(op)
+                    .json_data(&change)
+                    .unwrap_or_else(|e| Event::default().event("error").data(e.to_string()))
+            }
+            Err(status) => Event::default().event("error").data(status.message()),
+        };
+        Ok(event)
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+async fn user_calendar<S: ReservationStore>(
+    State(gateway): State<Arc<Gateway<S>>>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+    Query(params): Query<CalendarParams>,
+) -> Result<Response, RestError> {
+    let user_id = calendar_id(user_id);
+    let query = ReservationQuery {
+        user_id: user_id.clone(),
+        ..Default::default()
+    };
+    calendar(&gateway, headers, query, params, &user_id).await
+}
+
+async fn resource_calendar<S: ReservationStore>(
+    State(gateway): State<Arc<Gateway<S>>>,
+    headers: HeaderMap,
+    Path(resource_id): Path<String>,
+    Query(params): Query<CalendarParams>,
+) -> Result<Response, RestError> {
+    let resource_id = calendar_id(resource_id);
+    let query = ReservationQuery {
+        resource_id: resource_id.clone(),
+        ..Default::default()
+    };
+    calendar(&gateway, headers, query, params, &resource_id).await
+}
+
+// 日历客户端习惯以.ics结尾的地址
+fn calendar_id(id: String) -> String {
+    match id.strip_suffix(".ics") {
+        Some(id) => id.to_string(),
+        None => id,
+    }
+}
+
+// 按状态分别查询时间段内的预定，合并后按开始时间输出为iCalendar
+async fn calendar<S: ReservationStore>(
+    gateway: &Gateway<S>,
+    mut headers: HeaderMap,
+    query: ReservationQuery,
+    params: CalendarParams,
+    name: &str,
+) -> Result<Response, RestError> {
+    if let Some(token) = params.access_token {
+        if !headers.contains_key(AUTHORIZATION) {
+            let value = HeaderValue::from_str(&format!("Bearer {}", token))
+                .map_err(|_| Status::invalid_argument("access_token中包含无效字符"))?;
+            headers.insert(AUTHORIZATION, value);
+        }
+    }
+    let statuses = match params.status {
+        Some(status) => vec![status],
+        None => vec![ReservationStatus::Pending, ReservationStatus::Confirmed],
+    };
+    let mut data = Vec::new();
+    for status in statuses {
+        let query = ReservationQuery {
+            status: status as i32,
+            start: params.start.as_ref().map(abi::convert_to_timestamp),
+            end: params.end.as_ref().map(abi::convert_to_timestamp),
+            ..query.clone()
+        };
+        let req = QueryRequest { query: Some(query) };
+        let resp = gateway
+            .service
+            .query(gateway.request(headers.clone(), req)?)
+            .await?;
+        data.extend(resp.into_inner().try_collect::<Vec<_>>().await?);
+    }
+    data.sort_by_key(|r| (r.start.as_ref().map(|t| (t.seconds, t.nanos)), r.id));
+
+    let mut calendar = ICalendar::new(name, Utc::now());
+    calendar.extend(&data);
+    let headers = [(CONTENT_TYPE, "text/calendar; charset=utf-8")];
+    Ok((StatusCode::OK, headers, calendar.finish()).into_response())
+}
+
+// 解析请求体中的iCalendar，展开后的事件作为同一个用户在该资源上的预定一次性导入
+async fn import_calendar<S: ReservationStore>(
+    State(gateway): State<Arc<Gateway<S>>>,
+    headers: HeaderMap,
+    Path(resource_id): Path<String>,
+    Query(params): Query<ImportParams>,
+    body: String,
+) -> Result<Response, RestError> {
+    let tz = match params.tz.as_deref() {
+        Some(tz) => tz
+            .parse::<Tz>()
+            .map_err(|_| Status::invalid_argument(format!("未知的时区: {}", tz)))?,
+        None => Tz::UTC,
+    };
+    let options = ICalImportOptions {
+        tz,
+        until: params.until,
+        ..Default::default()
+    };
+    let parsed = abi::parse_ical(&body, &options)?;
+    let reservations = parsed
+        .iter()
+        .filter_map(|ret| ret.as_ref().ok())
+        .map(|event| event.to_reservation(&params.user_id, &resource_id))
+        .collect();
+    let req = ReserveAllRequest {
+        reservations,
+        reason: params.reason,
+        dry_run: params.dry_run,
+    };
+    let mut results = gateway
+        .service
+        .reserve_all(gateway.request(headers, req)?)
+        .await?
+        .into_inner()
+        .into_iter();
+
+    let mut report = ImportReport {
+        dry_run: params.dry_run,
+        ..Default::default()
+    };
+    for ret in parsed {
+        let event = match ret {
+            Ok(event) => {
+                let ret = results.next().unwrap_or(Err(abi::Error::Unknown));
+                imported_event(event, ret, params.dry_run)
+            }
+            Err(e) => ImportedEvent {
+                uid: e.uid,
+                start: None,
+                end: None,
+                result: ImportResult::Invalid,
+                reservation: None,
+                error: Some(e.error.to_string()),
+            },
+        };
+        match event.result {
+            ImportResult::Created => report.created += 1,
+            ImportResult::Ok => {}
+            ImportResult::Conflict => report.conflicts += 1,
+            ImportResult::Invalid => report.invalid += 1,
+        }
+        report.events.push(event);
+    }
+    Ok((StatusCode::OK, Json(report)).into_response())
+}
+
+fn imported_event(
+    event: ICalEvent,
+    ret: Result<Reservation, abi::Error>,
+    dry_run: bool,
+) -> ImportedEvent {
+    let (result, reservation, error) = match ret {
+        Ok(rsvp) if dry_run => (ImportResult::Ok, Some(rsvp), None),
+        Ok(rsvp) => (ImportResult::Created, Some(rsvp), None),
+        Err(e @ abi::Error::ConflictReservation(_)) => {
+            (ImportResult::Conflict, None, Some(e.to_string()))
+        }
+        Err(e) => (ImportResult::Invalid, None, Some(e.to_string())),
+    };
+    ImportedEvent {
+        uid: event.uid,
+        start: Some(event.start),
+        end: Some(event.end),
+        result,
+        reservation,
+        error,
+    }
+}
+
+// 导出满足条件的所有预定，逐条编码后输出，不在内存中缓存整个结果
+async fn export<S: ReservationStore>(
+    State(gateway): State<Arc<Gateway<S>>>,
+    headers: HeaderMap,
+    Query(params): Query<ExportParams>,
+) -> Result<Response, RestError> {
+    // 未指定状态时导出所有状态的预定
+    let filter = ReservationFilter {
+        user_id: params.user_id,
+        resource_id: params.resource_id,
+        status: params.status.map(|s| s as i32).unwrap_or_default(),
+        desc: params.desc,
+        ..Default::default()
+    };
+    let rsvps = gateway
+        .service
+        .export(gateway.request(headers, filter)?)
+        .await?
+        .into_inner();
+
+    // 输出过程中出错时中断响应，客户端会收到不完整的文件
+    let (content_type, filename, body) = match params.format {
+        BulkFormat::Ndjson => {
+            let lines = rsvps.map(|ret| ndjson_line(ret.map_err(Box::new)?));
+            ("application/x-ndjson", "reservations.ndjson", lines.boxed())
+        }
+        BulkFormat::Csv => {
+            let header = stream::once(future::ready(csv_line(EXPORT_FIELDS)));
+            let lines = rsvps.map(|ret| csv_line(ret.map_err(Box::new)?));
+            (
+                "text/csv; charset=utf-8",
+                "reservations.csv",
+                header.chain(lines).boxed(),
+            )
+        }
+    };
+    let headers = [
+        (CONTENT_TYPE, content_type.to_string()),
+        (
+            CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", filename),
+        ),
+    ];
+    Ok((StatusCode::OK, headers, StreamBody::new(body)).into_response())
+}
+
+// 将一条记录编码为一行ndjson
+fn ndjson_line<T: Serialize>(record: T) -> Result<Vec<u8>, Box<Status>> {
+    let mut line = serde_json::to_vec(&record).map_err(|e| Status::internal(e.to_string()))?;
+    line.push(b'\n');
+    Ok(line)
+}
+
+// 将一条记录编码为一行csv
+fn csv_line<T: Serialize>(record: T) -> Result<Vec<u8>, Box<Status>> {
+    let mut wtr = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(vec![]);
+    wtr.serialize(record)
+        .map_err(|e| Status::internal(e.to_string()))?;
+    Ok(wtr
+        .into_inner()
+        .map_err(|e| Status::internal(e.to_string()))?)
+}
+
+// 导入export输出的文件，每一行单独解析和校验，无法解析的行不影响其它行
+async fn import<S: ReservationStore>(
+    State(gateway): State<Arc<Gateway<S>>>,
+    headers: HeaderMap,
+    Query(params): Query<BulkImportParams>,
+    body: Bytes,
+) -> Result<Response, RestError> {
+    let parsed = match params.format {
+        BulkFormat::Ndjson => parse_ndjson(&body)?,
+        BulkFormat::Csv => parse_csv(&body)?,
+    };
+    let mut lines = Vec::with_capacity(parsed.len());
+    let mut reservations = Vec::with_capacity(parsed.len());
+    for (line, ret) in parsed {
+        match ret {
+            Ok(rsvp) => {
+                reservations.push(rsvp);
+                lines.push((line, None));
+            }
+            Err(e) => lines.push((line, Some(e))),
+        }
+    }
+    let req = ImportRequest {
+        reservations,
+        upsert: params.upsert,
+        reason: params.reason,
+    };
+    let mut results = gateway
+        .service
+        .import(gateway.request(headers, req)?)
+        .await?
+        .into_inner()
+        .into_iter();
+
+    let mut report = BulkImportReport {
+        upsert: params.upsert,
+        ..Default::default()
+    };
+    for (line, error) in lines {
+        let row = match error {
+            Some(e) => ImportedRow {
+                line,
+                result: RowResult::Invalid,
+                id: None,
+                public_id: None,
+                error: Some(e),
+            },
+            None => imported_row(line, results.next().unwrap_or(Err(abi::Error::Unknown))),
+        };
+        match row.result {
+            RowResult::Created => report.created += 1,
+            RowResult::Updated => report.updated += 1,
+            RowResult::Conflict => report.conflicts += 1,
+            RowResult::Invalid => report.invalid += 1,
+        }
+        report.rows.push(row);
+    }
+    Ok((StatusCode::OK, Json(report)).into_response())
+}
+
+fn imported_row(line: u64, ret: Result<Reservation, abi::Error>) -> ImportedRow {
+    let (result, rsvp, error) = match ret {
+        Ok(rsvp) if rsvp.version > 1 => (RowResult::Updated, Some(rsvp), None),
+        Ok(rsvp) => (RowResult::Created, Some(rsvp), None),
+        Err(e @ abi::Error::ConflictReservation(_)) => {
+            (RowResult::Conflict, None, Some(e.to_string()))
+        }
+        Err(e) => (RowResult::Invalid, None, Some(e.to_string())),
+    };
+    ImportedRow {
+        line,
+        result,
+        id: rsvp.as_ref().map(|r| r.id),
+        public_id: rsvp.map(|r| r.public_id),
+        error,
+    }
+}
+
+// 导入文件中的一行: 行号和解析的结果
+type ParsedRow = (u64, Result<Reservation, String>);
+
+// 解析ndjson，每行一个json格式的预定，忽略空行
+fn parse_ndjson(body: &[u8]) -> Result<Vec<ParsedRow>, RestError> {
+    let body =
+        std::str::from_utf8(body).map_err(|_| Status::invalid_argument("请求体不是有效的UTF-8"))?;
+    Ok(body
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let ret = serde_json::from_str(line).map_err(|e| e.to_string());
+            (i as u64 + 1, ret)
+        })
+        .collect())
+}
+
+// 解析csv，第一行为表头，列名与导出的列一致，缺少的列使用默认值
+fn parse_csv(body: &[u8]) -> Result<Vec<ParsedRow>, RestError> {
+    let mut rdr = csv::Reader::from_reader(body);
+    let headers = rdr
+        .headers()
+        .map_err(|e| Status::invalid_argument(format!("无效的csv表头: {}", e)))?
+        .clone();
+    Ok(rdr
+        .records()
+        .map(|record| match record {
+            Ok(record) => {
+                let line = record.position().map_or(0, |p| p.line());
+                let ret = record
+                    .deserialize(Some(&headers))
+                    .map_err(|e| e.to_string());
+                (line, ret)
+            }
+            Err(e) => (e.position().map_or(0, |p| p.line()), Err(e.to_string())),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abi::Reservation;
+    use axum::body::Body;
+    use hyper::body::HttpBody;
+    use reservation::{MemoryStore, ReservationManager};
+    use serde_json::Value;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn router() -> Router {
+        let service = RsvpService::<MemoryStore>::new(ReservationManager::in_memory());
+        rest_router(service, AuthInterceptor::default())
+    }
+
+    async fn call(router: &Router, method: &str, uri: &str, body: Value) -> (StatusCode, Value) {
+        let req = HttpRequest::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .header("x-actor", "user:alice")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let resp = router.clone().oneshot(req).await.unwrap();
+        let status = resp.status();
+        assert!(resp.headers().contains_key(REQUEST_ID_METADATA));
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn reservation(start: &str, end: &str) -> Value {
+        json!({
+            "reservation": {
+                "user_id": "alice",
+                "resource_id": "room-101",
+                "start": start,
+                "end": end,
+            },
+            "reason": "team sync",
+        })
+    }
+
+    #[tokio::test]
+    async fn rest_should_map_onto_rsvp_service() {
+        let router = router();
+        let (status, created) = call(
+            &router,
+            "POST",
+            "/reservations",
+            reservation("2030-01-05T15:00:00+08:00", "2030-01-05T16:00:00+08:00"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(created["start"], "2030-01-05T07:00:00Z");
+        assert_eq!(created["status"], "pending");
+        let public_id = created["public_id"].as_str().unwrap();
+
+        let (status, conflict) = call(
+            &router,
+            "POST",
+            "/reservations",
+            reservation("2030-01-05T15:30:00+08:00", "2030-01-05T16:30:00+08:00"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(conflict["code"], "FailedPrecondition");
+
+        let uri = format!("/reservations/{}", public_id);
+        let body = json!({ "status": "confirmed", "expected_version": created["version"] });
+        let (status, confirmed) = call(&router, "PATCH", &uri, body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(confirmed["status"], "confirmed");
+
+        let (status, _) = call(&router, "PATCH", &uri, json!({ "status": "blocked" })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (_, updated) = call(&router, "PATCH", &uri, json!({ "note": "bring laptop" })).await;
+        assert_eq!(updated["note"], "bring laptop");
+
+        let (status, got) = call(&router, "GET", &uri, Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(got, updated);
+
+        let (status, _) = call(&router, "DELETE", &uri, Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, missing) = call(&router, "GET", &uri, Value::Null).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(missing["code"], "NotFound");
+    }
+
+    #[tokio::test]
+    async fn list_should_page_by_id_with_time_range() {
+        let router = router();
+        for day in 1..=5 {
+            let start = format!("2030-02-0{}T10:00:00Z", day);
+            let end = format!("2030-02-0{}T11:00:00Z", day);
+            let (status, _) =
+                call(&router, "POST", "/reservations", reservation(&start, &end)).await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+
+        let (_, page) = call(&router, "GET", "/reservations?page_size=2", Value::Null).await;
+        assert_eq!(page["reservations"].as_array().unwrap().len(), 2);
+        assert_eq!(page["pager"]["total"], 5);
+
+        let range = "start=2030-02-02T00:00:00Z&end=2030-02-05T00:00:00Z";
+        let (_, first) = call(
+            &router,
+            "GET",
+            &format!("/reservations?{}&page_size=2", range),
+            Value::Null,
+        )
+        .await;
+        let rsvps: Vec<Reservation> =
+            serde_json::from_value(first["reservations"].clone()).unwrap();
+        assert_eq!(rsvps.len(), 2);
+        assert_eq!(first["pager"]["total"], 3);
+        let next = first["pager"]["next"].as_i64().unwrap();
+        assert_eq!(next, rsvps[1].id);
+
+        let (_, second) = call(
+            &router,
+            "GET",
+            &format!("/reservations?{}&page_size=2&cursor={}", range, next),
+            Value::Null,
+        )
+        .await;
+        assert_eq!(second["reservations"].as_array().unwrap().len(), 1);
+        assert_eq!(second["pager"]["next"], 0);
+    }
+
+    #[tokio::test]
+    async fn changes_should_be_sent_as_events() {
+        let router = router();
+        let req = HttpRequest::get("/changes?changed_fields=status")
+            .body(Body::empty())
+            .unwrap();
+        let resp = router.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.headers()["content-type"], "text/event-stream");
+        let mut body = resp.into_body();
+
+        call(
+            &router,
+            "POST",
+            "/reservations",
+            reservation("2030-03-01T10:00:00Z", "2030-03-01T11:00:00Z"),
+        )
+        .await;
+        let chunk = tokio::time::timeout(Duration::from_secs(1), body.data())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let event = String::from_utf8(chunk.to_vec()).unwrap();
+        assert!(event.starts_with("event:create\ndata:{"));
+        assert!(event.contains(r#""start":"2030-03-01T10:00:00Z""#));
+    }
+
+    #[tokio::test]
+    async fn calendars_should_export_reservations_as_icalendar() {
+        let router = router();
+        let (_, first) = call(
+            &router,
+            "POST",
+            "/reservations",
+            reservation("2030-04-02T09:00:00Z", "2030-04-02T10:00:00Z"),
+        )
+        .await;
+        call(
+            &router,
+            "POST",
+            "/reservations",
+            reservation("2030-04-01T09:00:00Z", "2030-04-01T10:00:00Z"),
+        )
+        .await;
+        let uri = format!("/reservations/{}", first["id"]);
+        call(&router, "PATCH", &uri, json!({"status": "confirmed"})).await;
+
+        let calendar = |uri: &str| {
+            let req = HttpRequest::get(uri).body(Body::empty()).unwrap();
+            let router = router.clone();
+            async move {
+                let resp = router.oneshot(req).await.unwrap();
+                assert_eq!(resp.status(), StatusCode::OK);
+                assert_eq!(
+                    resp.headers()["content-type"],
+                    "text/calendar; charset=utf-8"
+                );
+                let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+                String::from_utf8(body.to_vec()).unwrap()
+            }
+        };
+
+        // 按开始时间排序，包含待确认和已确认的预定
+        let ics = calendar("/calendars/users/alice.ics").await;
+        assert!(ics.contains("X-WR-CALNAME:alice\r\n"));
+        let starts = ics
+            .lines()
+            .filter(|l| l.starts_with("DTSTART:") || l.starts_with("STATUS:"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            starts,
+            [
+                "DTSTART:20300401T090000Z",
+                "STATUS:TENTATIVE",
+                "DTSTART:20300402T090000Z",
+                "STATUS:CONFIRMED"
+            ]
+        );
+
+        let ics = calendar("/calendars/resources/room-101?status=confirmed").await;
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 1);
+        assert!(ics.contains(&format!("UID:reservation-{}@rsvp", first["id"])));
+        let ics = calendar("/calendars/users/bob.ics").await;
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 0);
+    }
+
+    #[tokio::test]
+    async fn calendars_should_be_imported_with_dry_run() {
+        let router = router();
+        call(
+            &router,
+            "POST",
+            "/reservations",
+            reservation("2030-05-14T01:30:00Z", "2030-05-14T02:30:00Z"),
+        )
+        .await;
+
+        // 每周二上午9点(上海)，第二次与已有预定冲突，第三次被单独修改为下午
+        let ics = "BEGIN:VCALENDAR\r\n\
+            VERSION:2.0\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:standup\r\n\
+            SUMMARY:Standup\r\n\
+            DTSTART;TZID=Asia/Shanghai:20300507T090000\r\n\
+            DTEND;TZID=Asia/Shanghai:20300507T100000\r\n\
+            RRULE:FREQ=WEEKLY;COUNT=3\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:standup\r\n\
+            RECURRENCE-ID;TZID=Asia/Shanghai:20300521T090000\r\n\
+            DTSTART;TZID=Asia/Shanghai:20300521T140000\r\n\
+            DTEND;TZID=Asia/Shanghai:20300521T150000\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:broken\r\n\
+            DTSTART;TZID=Mars/Olympus:20300507T090000\r\n\
+            DTEND;TZID=Mars/Olympus:20300507T100000\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+        let import = |query: &str| {
+            let req = HttpRequest::post(format!(
+                "/calendars/resources/room-101/import?user_id=alice&{}",
+                query
+            ))
+            .header("content-type", "text/calendar")
+            .header("x-actor", "user:alice")
+            .body(Body::from(ics))
+            .unwrap();
+            let router = router.clone();
+            async move {
+                let resp = router.oneshot(req).await.unwrap();
+                let status = resp.status();
+                let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+                (status, serde_json::from_slice::<Value>(&body).unwrap())
+            }
+        };
+        let results = |report: &Value| {
+            report["events"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|e| e["result"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let (status, report) = import("dry_run=true").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(results(&report), ["ok", "conflict", "ok", "invalid"]);
+        assert_eq!(report["events"][0]["start"], "2030-05-07T01:00:00Z");
+        assert_eq!(report["events"][2]["start"], "2030-05-21T06:00:00Z");
+        assert_eq!(report["events"][0]["reservation"]["note"], "Standup");
+        assert_eq!(report["created"], 0);
+        let (_, page) = call(&router, "GET", "/reservations", Value::Null).await;
+        assert_eq!(page["pager"]["total"], 1);
+
+        let (_, report) = import("reason=migrate").await;
+        assert_eq!(
+            results(&report),
+            ["created", "conflict", "created", "invalid"]
+        );
+        assert_eq!(
+            (&report["created"], &report["conflicts"], &report["invalid"]),
+            (&json!(2), &json!(1), &json!(1))
+        );
+        let (_, page) = call(&router, "GET", "/reservations", Value::Null).await;
+        assert_eq!(page["pager"]["total"], 3);
+
+        let (status, _) = import("tz=Nowhere").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn reservations_should_round_trip_through_export_and_import() {
+        let router = router();
+        for day in 1..=2 {
+            let start = format!("2030-06-0{}T10:00:00Z", day);
+            let end = format!("2030-06-0{}T11:00:00Z", day);
+            call(&router, "POST", "/reservations", reservation(&start, &end)).await;
+        }
+        call(
+            &router,
+            "PATCH",
+            "/reservations/2",
+            json!({"status": "confirmed"}),
+        )
+        .await;
+
+        let send = |method: &str, uri: &str, body: String| {
+            let req = HttpRequest::builder()
+                .method(method)
+                .uri(uri)
+                .header("x-actor", "service:migrator")
+                .body(Body::from(body))
+                .unwrap();
+            let router = router.clone();
+            async move {
+                let resp = router.oneshot(req).await.unwrap();
+                assert_eq!(resp.status(), StatusCode::OK);
+                let content_type = resp.headers()[CONTENT_TYPE].to_str().unwrap().to_string();
+                let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+                (content_type, String::from_utf8(body.to_vec()).unwrap())
+            }
+        };
+
+        // 默认导出所有状态的预定
+        let (content_type, ndjson) = send("GET", "/admin/reservations/export", String::new()).await;
+        assert_eq!(content_type, "application/x-ndjson");
+        let lines = ndjson.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        let first: Reservation = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first.id, 1);
+
+        let uri = "/admin/reservations/export?format=csv&status=confirmed";
+        let (content_type, csv) = send("GET", uri, String::new()).await;
+        assert_eq!(content_type, "text/csv; charset=utf-8");
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "id,user_id,status,resource_id,start,end,note,version,public_id,tenant_id"
+        );
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("2,alice,confirmed,room-101,2030-06-02T10:00:00Z,"));
+        assert!(lines.next().is_none());
+
+        // 按ID覆盖第2个预定的备注，新增一个预定，其余行冲突或无法解析
+        let mut rdr = csv::Reader::from_reader(csv.as_bytes());
+        let mut exported: Reservation = rdr.deserialize().next().unwrap().unwrap();
+        exported.note = "moved".into();
+        let mut wtr = csv::Writer::from_writer(vec![]);
+        wtr.serialize(&exported).unwrap();
+        wtr.serialize(Reservation {
+            id: 0,
+            public_id: String::new(),
+            start: Some(abi::convert_to_timestamp(
+                &"2030-06-03T10:00:00Z".parse().unwrap(),
+            )),
+            end: Some(abi::convert_to_timestamp(
+                &"2030-06-03T11:00:00Z".parse().unwrap(),
+            )),
+            ..exported.clone()
+        })
+        .unwrap();
+        wtr.serialize(Reservation {
+            id: 0,
+            public_id: String::new(),
+            ..exported.clone()
+        })
+        .unwrap();
+        let mut body = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+        body.push_str("9,alice,pending,room-101,not-a-time,,,1,,default\n");
+
+        let uri = "/admin/reservations/import?format=csv&upsert=true&reason=migrate";
+        let (_, report) = send("POST", uri, body).await;
+        let report: Value = serde_json::from_str(&report).unwrap();
+        let results = report["rows"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| (r["line"].as_u64().unwrap(), r["result"].as_str().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            [
+                (2, "updated"),
+                (3, "created"),
+                (4, "conflict"),
+                (5, "invalid")
+            ]
+        );
+        assert_eq!(
+            (
+                &report["created"],
+                &report["updated"],
+                &report["conflicts"],
+                &report["invalid"]
+            ),
+            (&json!(1), &json!(1), &json!(1), &json!(1))
+        );
+        assert_eq!(report["rows"][0]["public_id"], json!(exported.public_id));
+
+        let (_, got) = call(&router, "GET", "/reservations/2", Value::Null).await;
+        assert_eq!(
+            (&got["note"], &got["version"]),
+            (&json!("moved"), &json!(3))
+        );
+        let (_, page) = call(
+            &router,
+            "GET",
+            "/reservations?status=confirmed",
+            Value::Null,
+        )
+        .await;
+        assert_eq!(page["pager"]["total"], 2);
+    }
+}
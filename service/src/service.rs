@@ -1 +1,918 @@
+use crate::{record_rpc, Claims};
+use abi::{
+    reservation_service_server::ReservationService, Actor, CancelRequest, CancelResponse,
+    ConfirmRequest, ConfirmResponse, FilterRequest, FilterResponse, GetRequest, GetResponse,
+    HistoryRequest, HistoryResponse, ListenRequest, ListenResponse, QueryRequest, Reservation,
+    ReservationFilter, ReserveRequest, ReserveResponse, UpdateRequest, UpdateResponse,
+};
+use futures::{future, Stream, StreamExt};
+use reservation::{IdempotentRsvp, PgStore, ReservationManager, ReservationStore, Rsvp};
+use std::{future::Future, pin::Pin, time::Instant};
+use tokio::sync::{mpsc, watch};
+use tonic::{Code, Request, Response, Status};
+use tracing::{
+    error,
+    field::{debug, Empty},
+    info_span, Instrument, Span,
+};
+use uuid::Uuid;
+use x509_parser::{certificate::X509Certificate, prelude::FromDer};
 
+// 请求元数据中的操作者，格式为 "user:<id>" 或 "service:<name>"
+pub const ACTOR_METADATA: &str = "x-actor";
+
+// 请求元数据中的租户，未提供时为默认租户
+pub const TENANT_METADATA: &str = "x-tenant";
+
+// 请求元数据中的请求ID，未提供时由服务生成，并在响应中返回
+pub const REQUEST_ID_METADATA: &str = "x-request-id";
+
+// 未提供操作者时记录在审计信息中的操作者
+const ANONYMOUS: &str = "anonymous";
+
+pub type ResponseStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+// 批量创建预定的请求(例如从日历导入)，dry_run时只检查冲突，不会创建预定
+#[derive(Debug, Clone, Default)]
+pub struct ReserveAllRequest {
+    pub reservations: Vec<Reservation>,
+    pub reason: String,
+    pub dry_run: bool,
+}
+
+// 批量导入预定的请求(例如在环境之间迁移)，upsert时按ID覆盖已有的预定
+#[derive(Debug, Clone, Default)]
+pub struct ImportRequest {
+    pub reservations: Vec<Reservation>,
+    pub upsert: bool,
+    pub reason: String,
+}
+
+// 定义grpc服务，将请求转发给预定管理(默认使用postgres存储)
+pub struct RsvpService<S = PgStore> {
+    manager: ReservationManager<S>,
+    // 服务关闭时结束所有的响应流(监听和查询)，使优雅关闭不会被长连接阻塞
+    shutdown: Option<watch::Receiver<bool>>,
+    // 是否信任请求元数据中的操作者，配置了认证或tls时元数据中的操作者未经校验
+    trust_actor_metadata: bool,
+    // 未启用认证时是否信任请求元数据中的租户，不信任时所有请求都属于默认租户
+    trust_tenant_metadata: bool,
+}
+
+impl<S: ReservationStore> RsvpService<S> {
+    pub fn new(manager: ReservationManager<S>) -> Self {
+        Self {
+            manager,
+            shutdown: None,
+            trust_actor_metadata: true,
+            trust_tenant_metadata: false,
+        }
+    }
+
+    // 为false时忽略请求元数据中的操作者，只使用jwt或客户端证书中的身份
+    pub fn with_actor_metadata(mut self, trusted: bool) -> Self {
+        self.trust_actor_metadata = trusted;
+        self
+    }
+
+    // 收到true时结束所有进行中的响应流
+    pub fn with_shutdown(mut self, shutdown: watch::Receiver<bool>) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    fn to_stream<T: Send + 'static>(
+        &self,
+        rx: mpsc::Receiver<Result<T, abi::Error>>,
+    ) -> ResponseStream<T> {
+        let stream = to_stream(rx);
+        match self.shutdown.clone() {
+            Some(shutdown) => stream.take_until(shutdown_requested(shutdown)).boxed(),
+            None => stream,
+        }
+    }
+
+    // 为true时未启用认证的请求可以通过元数据选择租户，只应在可信的网关之后开启
+    pub fn with_tenant_metadata(mut self, trusted: bool) -> Self {
+        self.trust_tenant_metadata = trusted;
+        self
+    }
+
+    fn request_actor<T>(&self, request: &Request<T>) -> Result<Actor, abi::Error> {
+        request_actor(request, self.trust_actor_metadata)
+    }
+
+    // 获取限定在请求所属租户内的预定管理
+    fn tenant_manager<T>(&self, request: &Request<T>) -> Result<ReservationManager<S>, abi::Error> {
+        let tenant = request_tenant(request, self.trust_tenant_metadata)?;
+        Span::current().record("tenant", tenant.as_str());
+        self.manager.with_tenant(&tenant)
+    }
+
+    // 批量创建预定，不属于grpc接口，由REST网关的日历导入调用，认证、租户和审计与reserve一致
+    // 返回每条预定的结果，单条预定不合法或冲突不影响其它预定
+    pub async fn reserve_all(
+        &self,
+        request: Request<ReserveAllRequest>,
+    ) -> Result<Response<Vec<Result<Reservation, abi::Error>>>, Status> {
+        traced("reserve_all", request, |request| async move {
+            let actor = self.request_actor(&request)?;
+            let manager = self.tenant_manager(&request)?;
+            let claims = request_claims(&request).cloned();
+            let mut req = request.into_inner();
+            if let Some(claims) = claims {
+                for rsvp in req.reservations.iter_mut() {
+                    restrict_user(&claims, &mut rsvp.user_id)?;
+                }
+            }
+            record_reservation(req.reservations.first());
+            let audit = abi::Audit::new(actor, req.reason);
+            let results = manager
+                .reserve_all(req.reservations, &audit, !req.dry_run)
+                .await?;
+            Ok(Response::new(results))
+        })
+        .await
+    }
+
+    // 导出满足过滤条件的所有预定(忽略游标和页大小)，不属于grpc接口，由REST网关调用
+    // 只允许管理员和服务账号，结果边查询边返回
+    pub async fn export(
+        &self,
+        request: Request<ReservationFilter>,
+    ) -> Result<Response<ResponseStream<Reservation>>, Status> {
+        traced("export", request, |request| async move {
+            let manager = self.tenant_manager(&request)?;
+            require_privileged(request_claims(&request))?;
+            let filter = request.into_inner();
+            record_ids(&filter.user_id, &filter.resource_id);
+            let rx = manager.export(filter).await;
+            Ok(Response::new(self.to_stream(rx)))
+        })
+        .await
+    }
+
+    // 批量导入预定，不属于grpc接口，由REST网关调用，只允许管理员和服务账号
+    // 返回每条预定的结果，单条预定不合法或冲突不影响其它预定
+    pub async fn import(
+        &self,
+        request: Request<ImportRequest>,
+    ) -> Result<Response<Vec<Result<Reservation, abi::Error>>>, Status> {
+        traced("import", request, |request| async move {
+            let actor = self.request_actor(&request)?;
+            let manager = self.tenant_manager(&request)?;
+            require_privileged(request_claims(&request))?;
+            let req = request.into_inner();
+            let audit = abi::Audit::new(actor, req.reason);
+            let results = manager
+                .import_all(req.reservations, req.upsert, &audit)
+                .await?;
+            Ok(Response::new(results))
+        })
+        .await
+    }
+}
+
+// 批量导出和导入只允许管理员和服务账号，未启用认证时不做检查
+fn require_privileged(claims: Option<&Claims>) -> Result<(), abi::Error> {
+    match claims {
+        Some(claims) if !claims.is_privileged() => Err(abi::Error::PermissionDenied(format!(
+            "{} 不能批量导出或导入预定",
+            claims.sub
+        ))),
+        _ => Ok(()),
+    }
+}
+
+// 普通用户只能操作自己的预定，未启用认证时不做检查
+async fn authorize<S: ReservationStore>(
+    manager: &ReservationManager<S>,
+    claims: Option<&Claims>,
+    id: abi::ReservationId,
+    public_id: &str,
+) -> Result<(), abi::Error> {
+    let Some(claims) = claims.filter(|c| !c.is_privileged()) else {
+        return Ok(());
+    };
+    let id = manager.resolve_id(id, public_id).await?;
+    match manager.get(id).await {
+        Ok(rsvp) => claims.authorize(&rsvp.user_id),
+        // 预定已经不存在时交给后续处理，例如幂等重放已经取消的预定
+        Err(abi::Error::NotFound) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+#[tonic::async_trait]
+impl<S: ReservationStore> ReservationService for RsvpService<S> {
+    async fn reserve(
+        &self,
+        request: Request<ReserveRequest>,
+    ) -> Result<Response<ReserveResponse>, Status> {
+        traced("reserve", request, |request| async move {
+            let actor = self.request_actor(&request)?;
+            let manager = self.tenant_manager(&request)?;
+            record_reservation(request.get_ref().reservation.as_ref());
+            if let Some(claims) = request_claims(&request) {
+                let rsvp = request.get_ref().reservation.as_ref();
+                claims.authorize(rsvp.map(|r| r.user_id.as_str()).unwrap_or_default())?;
+            }
+            let resp = manager.reserve_once(request.into_inner(), actor).await?;
+            Ok(Response::new(resp))
+        })
+        .await
+    }
+
+    async fn confirm(
+        &self,
+        request: Request<ConfirmRequest>,
+    ) -> Result<Response<ConfirmResponse>, Status> {
+        traced("confirm", request, |request| async move {
+            let actor = self.request_actor(&request)?;
+            let manager = self.tenant_manager(&request)?;
+            let req = request.get_ref();
+            authorize(&manager, request_claims(&request), req.id, &req.public_id).await?;
+            let resp = manager.confirm_once(request.into_inner(), actor).await?;
+            record_reservation(resp.reservation.as_ref());
+            Ok(Response::new(resp))
+        })
+        .await
+    }
+
+    async fn update(
+        &self,
+        request: Request<UpdateRequest>,
+    ) -> Result<Response<UpdateResponse>, Status> {
+        traced("update", request, |request| async move {
+            let actor = self.request_actor(&request)?;
+            let manager = self.tenant_manager(&request)?;
+            let req = request.get_ref();
+            authorize(&manager, request_claims(&request), req.id, &req.public_id).await?;
+            let resp = manager.update_once(request.into_inner(), actor).await?;
+            record_reservation(resp.reservation.as_ref());
+            Ok(Response::new(resp))
+        })
+        .await
+    }
+
+    async fn cancel(
+        &self,
+        request: Request<CancelRequest>,
+    ) -> Result<Response<CancelResponse>, Status> {
+        traced("cancel", request, |request| async move {
+            let actor = self.request_actor(&request)?;
+            let manager = self.tenant_manager(&request)?;
+            let req = request.get_ref();
+            authorize(&manager, request_claims(&request), req.id, &req.public_id).await?;
+            let resp = manager.cancel_once(request.into_inner(), actor).await?;
+            record_reservation(resp.reservation.as_ref());
+            Ok(Response::new(resp))
+        })
+        .await
+    }
+
+    async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetResponse>, Status> {
+        traced("get", request, |request| async move {
+            let manager = self.tenant_manager(&request)?;
+            let claims = request_claims(&request).cloned();
+            let req = request.into_inner();
+            let id = manager.resolve_id(req.id, &req.public_id).await?;
+            let rsvp = manager.get(id).await?;
+            record_reservation(Some(&rsvp));
+            if let Some(claims) = claims {
+                claims.authorize(&rsvp.user_id)?;
+            }
+            Ok(Response::new(GetResponse {
+                reservation: Some(rsvp),
+            }))
+        })
+        .await
+    }
+
+    type queryStream = ResponseStream<Reservation>;
+
+    async fn query(
+        &self,
+        request: Request<QueryRequest>,
+    ) -> Result<Response<Self::queryStream>, Status> {
+        traced("query", request, |request| async move {
+            let manager = self.tenant_manager(&request)?;
+            let claims = request_claims(&request).cloned();
+            let mut query = request.into_inner().query.unwrap_or_default();
+            if let Some(claims) = claims {
+                restrict_user(&claims, &mut query.user_id)?;
+            }
+            record_ids(&query.user_id, &query.resource_id);
+            let rx = manager.query(query).await;
+            Ok(Response::new(self.to_stream(rx)))
+        })
+        .await
+    }
+
+    async fn filter(
+        &self,
+        request: Request<FilterRequest>,
+    ) -> Result<Response<FilterResponse>, Status> {
+        traced("filter", request, |request| async move {
+            let manager = self.tenant_manager(&request)?;
+            let claims = request_claims(&request).cloned();
+            let mut filter = request.into_inner().filter.unwrap_or_default();
+            if let Some(claims) = claims {
+                restrict_user(&claims, &mut filter.user_id)?;
+            }
+            record_ids(&filter.user_id, &filter.resource_id);
+            let (pager, reservations) = manager.filter(filter).await?;
+            Ok(Response::new(FilterResponse {
+                reservations,
+                pager: Some(pager),
+            }))
+        })
+        .await
+    }
+
+    async fn history(
+        &self,
+        request: Request<HistoryRequest>,
+    ) -> Result<Response<HistoryResponse>, Status> {
+        traced("history", request, |request| async move {
+            let manager = self.tenant_manager(&request)?;
+            let req = request.get_ref();
+            authorize(&manager, request_claims(&request), req.id, &req.public_id).await?;
+            let req = request.into_inner();
+            let id = manager.resolve_id(req.id, &req.public_id).await?;
+            let changes = manager.history(id).await?;
+            Ok(Response::new(HistoryResponse { changes }))
+        })
+        .await
+    }
+
+    type listenStream = ResponseStream<ListenResponse>;
+
+    async fn listen(
+        &self,
+        request: Request<ListenRequest>,
+    ) -> Result<Response<Self::listenStream>, Status> {
+        traced("listen", request, |request| async move {
+            let manager = self.tenant_manager(&request)?;
+            let claims = request_claims(&request).cloned();
+            let rx = manager.listen(request.into_inner()).await;
+            let stream = self.to_stream(rx);
+            // 普通用户只能收到自己预定的变更
+            match claims.filter(|c| !c.is_privileged()) {
+                Some(claims) => Ok(Response::new(
+                    stream
+                        .filter(move |ret| {
+                            let keep = match ret {
+                                Ok(change) => change
+                                    .reservation
+                                    .as_ref()
+                                    .is_some_and(|r| r.user_id == claims.sub),
+                                Err(_) => true,
+                            };
+                            future::ready(keep)
+                        })
+                        .boxed(),
+                )),
+                None => Ok(Response::new(stream)),
+            }
+        })
+        .await
+    }
+}
+
+// 在请求的span中执行处理函数，记录处理耗时和响应的状态码，并在响应中返回请求ID
+async fn traced<Req, Resp, F, Fut>(
+    method: &'static str,
+    request: Request<Req>,
+    f: F,
+) -> Result<Response<Resp>, Status>
+where
+    F: FnOnce(Request<Req>) -> Fut,
+    Fut: Future<Output = Result<Response<Resp>, Status>>,
+{
+    let request_id = request_id(&request);
+    let span = info_span!(
+        "rpc",
+        method,
+        request_id = %request_id,
+        tenant = Empty,
+        user_id = Empty,
+        resource_id = Empty,
+        code = Empty,
+        latency_ms = Empty,
+    );
+    let start = Instant::now();
+    let mut ret = f(request).instrument(span.clone()).await;
+
+    let code = match &ret {
+        Ok(_) => Code::Ok,
+        Err(status) => status.code(),
+    };
+    let elapsed = start.elapsed();
+    record_rpc(method, code, elapsed);
+    span.record("code", debug(code));
+    span.record("latency_ms", elapsed.as_secs_f64() * 1000.0);
+    if let Err(status) = &ret {
+        if matches!(code, Code::Internal | Code::Unknown | Code::Unavailable) {
+            span.in_scope(|| error!(error = status.message(), "rpc failed"));
+        }
+    }
+
+    let metadata = match &mut ret {
+        Ok(resp) => resp.metadata_mut(),
+        Err(status) => status.metadata_mut(),
+    };
+    if let Ok(value) = request_id.parse() {
+        metadata.insert(REQUEST_ID_METADATA, value);
+    }
+    ret
+}
+
+// 获取请求元数据中的请求ID，未提供或不合法时生成一个新的
+fn request_id<T>(request: &Request<T>) -> String {
+    request
+        .metadata()
+        .get(REQUEST_ID_METADATA)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+// 在请求的span中记录预定所属的用户和资源
+fn record_reservation(rsvp: Option<&Reservation>) {
+    if let Some(rsvp) = rsvp {
+        record_ids(&rsvp.user_id, &rsvp.resource_id);
+    }
+}
+
+fn record_ids(user_id: &str, resource_id: &str) {
+    let span = Span::current();
+    if !user_id.is_empty() {
+        span.record("user_id", user_id);
+    }
+    if !resource_id.is_empty() {
+        span.record("resource_id", resource_id);
+    }
+}
+
+// 获取认证拦截器放入请求中的jwt声明
+fn request_claims<T>(request: &Request<T>) -> Option<&Claims> {
+    request.extensions().get::<Claims>()
+}
+
+// 获取请求所属的租户: 启用认证时由jwt声明决定，否则只在trusted时使用请求元数据中的租户，
+// 不信任时忽略元数据，使用默认租户
+fn request_tenant<T>(request: &Request<T>, trusted: bool) -> Result<String, abi::Error> {
+    let requested = match request.metadata().get(TENANT_METADATA) {
+        Some(value) => Some(
+            value
+                .to_str()
+                .map_err(|_| abi::Error::InvalidTenant(format!("{:?}", value)))?,
+        ),
+        None => None,
+    };
+    match request_claims(request) {
+        Some(claims) => claims.tenant(requested),
+        None if trusted => Ok(requested.unwrap_or(abi::DEFAULT_TENANT).to_string()),
+        None => Ok(abi::DEFAULT_TENANT.to_string()),
+    }
+}
+
+// 普通用户查询时只能查询自己的预定，未指定用户时默认为自己
+fn restrict_user(claims: &Claims, user_id: &mut String) -> Result<(), abi::Error> {
+    if user_id.is_empty() && !claims.is_privileged() {
+        *user_id = claims.sub.clone();
+    }
+    claims.authorize(user_id)
+}
+
+// 获取请求的操作者: 优先使用jwt中的身份，其次是客户端证书，都没有时视为匿名用户。
+// 元数据中的操作者只在trusted时使用，与已校验的身份不一致时拒绝请求，避免伪造审计记录
+fn request_actor<T>(request: &Request<T>, trusted: bool) -> Result<Actor, abi::Error> {
+    let claimed = match request.metadata().get(ACTOR_METADATA) {
+        Some(value) => Some(
+            value
+                .to_str()
+                .map_err(|_| abi::Error::InvalidActor(format!("{:?}", value)))?
+                .parse::<Actor>()?,
+        ),
+        None => None,
+    };
+    let verified = request_claims(request)
+        .map(Claims::actor)
+        .or_else(|| peer_actor(request));
+    match (verified, claimed) {
+        (Some(actor), Some(claimed)) if actor != claimed => Err(abi::Error::PermissionDenied(
+            format!("{} 不能以 {} 的身份操作", actor, claimed),
+        )),
+        (Some(actor), _) => Ok(actor),
+        (None, Some(claimed)) if trusted => Ok(claimed),
+        (None, _) => Ok(Actor::user(ANONYMOUS)),
+    }
+}
+
+// 使用mTLS时，以客户端证书的CN作为服务主体
+fn peer_actor<T>(request: &Request<T>) -> Option<Actor> {
+    let certs = request.peer_certs()?;
+    let (_, cert) = X509Certificate::from_der(certs.first()?.as_ref()).ok()?;
+    let cn = cert.subject().iter_common_name().next()?.as_str().ok()?;
+    Some(Actor::service(cn))
+}
+
+// 等待关闭信号，发送端已经释放时不会再有关闭信号
+pub(crate) async fn shutdown_requested(mut shutdown: watch::Receiver<bool>) {
+    if shutdown.wait_for(|closed| *closed).await.is_err() {
+        future::pending::<()>().await;
+    }
+}
+
+// 将管理层返回的channel转换为grpc响应流
+fn to_stream<T: Send + 'static>(rx: mpsc::Receiver<Result<T, abi::Error>>) -> ResponseStream<T> {
+    let stream = futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|item| (item.map_err(Status::from), rx))
+    });
+    stream.boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abi::convert_to_timestamp;
+    use chrono::{TimeZone, Utc};
+    use futures::TryStreamExt;
+
+    fn service() -> RsvpService<reservation::MemoryStore> {
+        RsvpService::new(ReservationManager::in_memory())
+    }
+
+    fn reserve_request(user_id: &str) -> ReserveRequest {
+        let start = Utc.with_ymd_and_hms(2022, 12, 25, 15, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2022, 12, 28, 12, 0, 0).unwrap();
+        ReserveRequest {
+            reservation: Some(Reservation {
+                user_id: user_id.to_string(),
+                status: abi::ReservationStatus::Pending as i32,
+                resource_id: "ocean-view-room-713".to_string(),
+                start: Some(convert_to_timestamp(&start)),
+                end: Some(convert_to_timestamp(&end)),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn request_actor_should_parse_metadata() {
+        let mut request = Request::new(());
+        assert_eq!(
+            request_actor(&request, true).unwrap(),
+            Actor::user(ANONYMOUS)
+        );
+
+        request
+            .metadata_mut()
+            .insert(ACTOR_METADATA, "service:billing".parse().unwrap());
+        assert_eq!(
+            request_actor(&request, true).unwrap(),
+            Actor::service("billing")
+        );
+
+        request
+            .metadata_mut()
+            .insert(ACTOR_METADATA, "billing".parse().unwrap());
+        let status = Status::from(request_actor(&request, true).unwrap_err());
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn request_actor_should_not_trust_unverified_metadata() {
+        let mut request = Request::new(());
+        request
+            .metadata_mut()
+            .insert(ACTOR_METADATA, "user:mallory".parse().unwrap());
+        assert_eq!(
+            request_actor(&request, false).unwrap(),
+            Actor::user(ANONYMOUS)
+        );
+
+        // 与jwt中的身份不一致时拒绝请求，一致时允许
+        let mut request = with_claims((), "alice", &[]);
+        request
+            .metadata_mut()
+            .insert(ACTOR_METADATA, "user:mallory".parse().unwrap());
+        let status = Status::from(request_actor(&request, true).unwrap_err());
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        request
+            .metadata_mut()
+            .insert(ACTOR_METADATA, "user:alice".parse().unwrap());
+        assert_eq!(
+            request_actor(&request, false).unwrap(),
+            Actor::user("alice")
+        );
+    }
+
+    #[tokio::test]
+    async fn reserve_then_get_should_work() {
+        let service = service();
+        let rsvp = service
+            .reserve(Request::new(reserve_request("alice")))
+            .await
+            .unwrap()
+            .into_inner()
+            .reservation
+            .unwrap();
+
+        let req = GetRequest {
+            public_id: rsvp.public_id.clone(),
+            ..Default::default()
+        };
+        let got = service.get(Request::new(req)).await.unwrap().into_inner();
+        assert_eq!(got.reservation, Some(rsvp));
+    }
+
+    #[tokio::test]
+    async fn conflicting_reserve_should_fail_with_failed_precondition() {
+        let service = service();
+        service
+            .reserve(Request::new(reserve_request("alice")))
+            .await
+            .unwrap();
+        let status = service
+            .reserve(Request::new(reserve_request("bob")))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+    }
+
+    #[tokio::test]
+    async fn out_of_range_time_should_fail_with_invalid_argument() {
+        let service = service();
+        let mut req = reserve_request("alice");
+        let rsvp = req.reservation.as_mut().unwrap();
+        rsvp.start.as_mut().unwrap().nanos = -1;
+        let status = service.reserve(Request::new(req)).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        let mut end = convert_to_timestamp(&Utc::now());
+        end.seconds = i64::MAX;
+        let query = abi::ReservationQuery {
+            end: Some(end),
+            ..Default::default()
+        };
+        let mut stream = service
+            .query(Request::new(QueryRequest { query: Some(query) }))
+            .await
+            .unwrap()
+            .into_inner();
+        let status = stream.next().await.unwrap().unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    fn with_claims<T>(req: T, sub: &str, roles: &[&str]) -> Request<T> {
+        let mut request = Request::new(req);
+        request.extensions_mut().insert(Claims {
+            sub: sub.to_string(),
+            exp: 0,
+            roles: roles.iter().map(|r| r.to_string()).collect(),
+            tenant: None,
+        });
+        request
+    }
+
+    #[tokio::test]
+    async fn users_should_not_reserve_for_others() {
+        let service = service();
+        let status = service
+            .reserve(with_claims(reserve_request("alice"), "bob", &[]))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+        let rsvp = service
+            .reserve(with_claims(reserve_request("alice"), "alice", &[]))
+            .await
+            .unwrap()
+            .into_inner()
+            .reservation
+            .unwrap();
+        let changes = service
+            .history(with_claims(
+                HistoryRequest {
+                    id: rsvp.id,
+                    ..Default::default()
+                },
+                "alice",
+                &[],
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .changes;
+        assert_eq!(changes[0].actor, "user:alice");
+    }
+
+    #[tokio::test]
+    async fn users_should_only_cancel_their_own_reservations() {
+        let service = service();
+        let rsvp = service
+            .reserve(Request::new(reserve_request("alice")))
+            .await
+            .unwrap()
+            .into_inner()
+            .reservation
+            .unwrap();
+        let cancel = CancelRequest {
+            id: rsvp.id,
+            ..Default::default()
+        };
+
+        let status = service
+            .cancel(with_claims(cancel.clone(), "bob", &[]))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        let status = service
+            .get(with_claims(
+                GetRequest {
+                    id: rsvp.id,
+                    ..Default::default()
+                },
+                "bob",
+                &[],
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+        // 管理员可以取消任意用户的预定
+        service
+            .cancel(with_claims(cancel, "root", &["admin"]))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn users_should_only_filter_their_own_reservations() {
+        let service = service();
+        service
+            .reserve(Request::new(reserve_request("alice")))
+            .await
+            .unwrap();
+
+        let filter = |user_id: &str| FilterRequest {
+            filter: Some(abi::ReservationFilter {
+                user_id: user_id.to_string(),
+                status: abi::ReservationStatus::Pending as i32,
+                page_size: 10,
+                ..Default::default()
+            }),
+        };
+        let status = service
+            .filter(with_claims(filter("alice"), "bob", &[]))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+        // 未指定用户时只返回自己的预定
+        let resp = service
+            .filter(with_claims(filter(""), "bob", &[]))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(resp.reservations.is_empty());
+        let resp = service
+            .filter(with_claims(filter(""), "billing", &["service"]))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(resp.reservations.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn bulk_export_and_import_should_require_admin() {
+        let service = service();
+        let import = ImportRequest {
+            reservations: vec![reserve_request("alice").reservation.unwrap()],
+            ..Default::default()
+        };
+        let status = service
+            .import(with_claims(import.clone(), "alice", &[]))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        let status = service
+            .export(with_claims(ReservationFilter::default(), "alice", &[]))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+        let results = service
+            .import(with_claims(import, "root", &["admin"]))
+            .await
+            .unwrap()
+            .into_inner();
+        let rsvp = results[0].as_ref().unwrap();
+        let exported = service
+            .export(with_claims(
+                ReservationFilter::default(),
+                "root",
+                &["admin"],
+            ))
+            .await
+            .unwrap()
+            .into_inner()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(exported, vec![rsvp.clone()]);
+    }
+
+    fn with_tenant<T>(req: T, tenant: &str) -> Request<T> {
+        let mut request = Request::new(req);
+        request
+            .metadata_mut()
+            .insert(TENANT_METADATA, tenant.parse().unwrap());
+        request
+    }
+
+    #[tokio::test]
+    async fn tenants_should_book_same_resource_independently() {
+        let service = service().with_tenant_metadata(true);
+        let rsvp = service
+            .reserve(with_tenant(reserve_request("alice"), "acme"))
+            .await
+            .unwrap()
+            .into_inner()
+            .reservation
+            .unwrap();
+        assert_eq!(rsvp.tenant_id, "acme");
+        service
+            .reserve(with_tenant(reserve_request("bob"), "globex"))
+            .await
+            .unwrap();
+
+        let get = GetRequest {
+            id: rsvp.id,
+            ..Default::default()
+        };
+        let status = service
+            .get(with_tenant(get.clone(), "globex"))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+        let status = service
+            .get(with_tenant(get, "acme corp"))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn untrusted_tenant_metadata_should_use_default_tenant() {
+        let service = service();
+        let rsvp = service
+            .reserve(with_tenant(reserve_request("alice"), "acme"))
+            .await
+            .unwrap()
+            .into_inner()
+            .reservation
+            .unwrap();
+        assert_eq!(rsvp.tenant_id, abi::DEFAULT_TENANT);
+    }
+
+    #[tokio::test]
+    async fn request_id_should_be_returned_in_response() {
+        let service = service();
+        let mut request = Request::new(reserve_request("alice"));
+        request
+            .metadata_mut()
+            .insert(REQUEST_ID_METADATA, "req-42".parse().unwrap());
+        let resp = service.reserve(request).await.unwrap();
+        assert_eq!(resp.metadata().get(REQUEST_ID_METADATA).unwrap(), "req-42");
+
+        // 未提供时生成请求ID，失败的响应中同样返回
+        let status = service
+            .reserve(Request::new(reserve_request("alice")))
+            .await
+            .unwrap_err();
+        let request_id = status.metadata().get(REQUEST_ID_METADATA).unwrap();
+        assert!(request_id.to_str().unwrap().parse::<Uuid>().is_ok());
+    }
+
+    #[tokio::test]
+    async fn listen_stream_should_end_on_shutdown() {
+        let (tx, rx) = watch::channel(false);
+        let service = service().with_shutdown(rx);
+        let mut stream = service
+            .listen(Request::new(ListenRequest::default()))
+            .await
+            .unwrap()
+            .into_inner();
+
+        tx.send_replace(true);
+        let next = tokio::time::timeout(std::time::Duration::from_secs(1), stream.next()).await;
+        assert!(matches!(next, Ok(None)));
+    }
+}
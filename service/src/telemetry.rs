@@ -0,0 +1,75 @@
+use abi::{LogConfig, LogFormat};
+use anyhow::Result;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime, trace, Resource};
+use tracing_subscriber::{
+    fmt::format::FmtSpan, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer, Registry,
+};
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+// 析构时关闭trace导出，发送尚未导出的span
+pub struct TracingGuard {
+    otlp: bool,
+}
+
+// 按配置初始化日志，配置了otlp_endpoint时同时将trace导出到collector
+// span结束时输出其字段和耗时，需要在进程退出前持有返回的guard
+pub fn init_tracing(config: &LogConfig) -> Result<TracingGuard> {
+    let filter = env_filter(&config.level)?;
+    let fmt = tracing_subscriber::fmt::layer().with_span_events(FmtSpan::CLOSE);
+    let mut layers: Vec<BoxedLayer> = vec![match config.format {
+        LogFormat::Pretty => fmt.pretty().boxed(),
+        LogFormat::Json => fmt.json().boxed(),
+    }];
+    if let Some(endpoint) = &config.otlp_endpoint {
+        layers.push(otlp_layer(endpoint, &config.service_name)?);
+    }
+
+    tracing_subscriber::registry()
+        .with(layers)
+        .with(filter)
+        .try_init()?;
+    Ok(TracingGuard {
+        otlp: config.otlp_endpoint.is_some(),
+    })
+}
+
+fn env_filter(level: &str) -> Result<EnvFilter, abi::Error> {
+    EnvFilter::try_new(level).map_err(|e| abi::Error::InvalidConfig(format!("log.level: {}", e)))
+}
+
+// 通过grpc将span批量导出到OpenTelemetry collector
+fn otlp_layer(endpoint: &str, service_name: &str) -> Result<BoxedLayer> {
+    let exporter = opentelemetry_otlp::new_exporter()
+        .tonic()
+        .with_endpoint(endpoint);
+    let resource = Resource::new([KeyValue::new("service.name", service_name.to_string())]);
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(exporter)
+        .with_trace_config(trace::config().with_resource(resource))
+        .install_batch(runtime::Tokio)?;
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer).boxed())
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        if self.otlp {
+            opentelemetry::global::shutdown_tracer_provider();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_level_should_be_rejected() {
+        assert!(env_filter("info,reservation=debug").is_ok());
+        let err = env_filter("reservation=loud").unwrap_err();
+        assert!(err.to_string().contains("log.level"));
+    }
+}
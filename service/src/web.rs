@@ -0,0 +1,217 @@
+use crate::{ACTOR_METADATA, REQUEST_ID_METADATA, TENANT_METADATA};
+use abi::GrpcWebConfig;
+use axum::http::{HeaderName, Method};
+use std::time::Duration;
+use tonic::transport::Server;
+use tonic_web::GrpcWebLayer;
+use tower::{
+    layer::util::{Identity, Stack},
+    util::Either,
+};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+// 浏览器缓存预检结果的时间
+const CORS_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+// grpc-web客户端需要发送和读取的头，以及服务使用的元数据
+const ALLOW_HEADERS: [&str; 8] = [
+    "x-grpc-web",
+    "content-type",
+    "x-user-agent",
+    "grpc-timeout",
+    "authorization",
+    TENANT_METADATA,
+    ACTOR_METADATA,
+    REQUEST_ID_METADATA,
+];
+const EXPOSE_HEADERS: [&str; 4] = [
+    "grpc-status",
+    "grpc-message",
+    "grpc-status-details-bin",
+    REQUEST_ID_METADATA,
+];
+
+pub type GrpcWebLayers = Either<Stack<GrpcWebLayer, Stack<CorsLayer, Identity>>, Identity>;
+pub type GrpcWebServer = Server<Stack<GrpcWebLayers, Identity>>;
+
+// 配置了grpc_web时，将grpc-web请求(http/1.1)转换为grpc请求，普通grpc请求不受影响
+// 跨域请求只允许配置中的来源，流式响应(query、listen)同样可用
+pub fn grpc_web_layer(config: Option<&GrpcWebConfig>) -> GrpcWebLayers {
+    match config {
+        Some(config) => Either::A(
+            tower::ServiceBuilder::new()
+                .layer(cors_layer(&config.cors_origins))
+                .layer(GrpcWebLayer::new())
+                .into_inner(),
+        ),
+        None => Either::B(Identity::new()),
+    }
+}
+
+// 来源已在加载配置时校验过，无法解析的来源会被忽略
+fn cors_layer(origins: &[String]) -> CorsLayer {
+    let origins = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(origins.iter().filter_map(|o| o.parse().ok()))
+    };
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods([Method::POST])
+        .allow_headers(ALLOW_HEADERS.map(HeaderName::from_static))
+        .expose_headers(EXPOSE_HEADERS.map(HeaderName::from_static))
+        .max_age(CORS_MAX_AGE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{server_builder, RsvpService};
+    use abi::{
+        convert_to_timestamp, reservation_service_client::ReservationServiceClient,
+        reservation_service_server::ReservationServiceServer, ListenRequest, QueryRequest,
+        Reservation, ReservationQuery, ReservationStatus, ReservationUpdateType, ReserveRequest,
+        ServerConfig,
+    };
+    use axum::http::{header, Request, StatusCode};
+    use hyper::{client::HttpConnector, Body, Client};
+    use reservation::ReservationManager;
+    use std::net::SocketAddr;
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::body::BoxBody;
+    use tonic_web::{GrpcWebCall, GrpcWebClientLayer, GrpcWebClientService};
+
+    const ORIGIN: &str = "https://app.example.com";
+
+    // 使用内存存储在随机端口上启动接受grpc-web请求的服务
+    async fn start_server() -> SocketAddr {
+        let config = ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            tls: None,
+            auth: None,
+            metrics: None,
+            rest: None,
+            grpc_web: Some(GrpcWebConfig {
+                cors_origins: vec![ORIGIN.to_string()],
+            }),
+            trust_tenant_header: false,
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let service = RsvpService::new(ReservationManager::in_memory());
+        let server = server_builder(&config)
+            .unwrap()
+            .add_service(ReservationServiceServer::new(service));
+        tokio::spawn(server.serve_with_incoming(TcpListenerStream::new(listener)));
+        addr
+    }
+
+    // 与浏览器一样通过http/1.1发送grpc-web请求
+    fn grpc_web_client(
+        addr: SocketAddr,
+    ) -> ReservationServiceClient<GrpcWebClientService<Client<HttpConnector, GrpcWebCall<BoxBody>>>>
+    {
+        let client = tower::ServiceBuilder::new()
+            .layer(GrpcWebClientLayer::new())
+            .service(Client::builder().build_http());
+        let origin = format!("http://{}", addr).parse().unwrap();
+        ReservationServiceClient::with_origin(client, origin)
+    }
+
+    fn reservation(resource_id: &str) -> Reservation {
+        let start = "2024-01-05T15:00:00Z".parse().unwrap();
+        let end = "2024-01-06T12:00:00Z".parse().unwrap();
+        Reservation {
+            user_id: "alice".to_string(),
+            resource_id: resource_id.to_string(),
+            status: ReservationStatus::Pending as i32,
+            start: Some(convert_to_timestamp(&start)),
+            end: Some(convert_to_timestamp(&end)),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn grpc_web_client_should_call_unary_and_streaming_methods() {
+        let addr = start_server().await;
+        let mut client = grpc_web_client(addr);
+        let mut changes = client
+            .listen(ListenRequest::default())
+            .await
+            .unwrap()
+            .into_inner();
+
+        let rsvp = client
+            .reserve(ReserveRequest {
+                reservation: Some(reservation("room-101")),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .reservation
+            .unwrap();
+        assert!(rsvp.id > 0);
+
+        let query = ReservationQuery {
+            resource_id: "room-101".to_string(),
+            start: rsvp.start.clone(),
+            end: rsvp.end.clone(),
+            ..Default::default()
+        };
+        let mut stream = client
+            .query(QueryRequest { query: Some(query) })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(stream.message().await.unwrap().unwrap().id, rsvp.id);
+        assert!(stream.message().await.unwrap().is_none());
+
+        let event = tokio::time::timeout(std::time::Duration::from_secs(1), changes.message())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.op, ReservationUpdateType::Create as i32);
+        assert_eq!(event.reservation.unwrap().id, rsvp.id);
+    }
+
+    #[tokio::test]
+    async fn cors_preflight_should_only_allow_configured_origins() {
+        let addr = start_server().await;
+        let preflight = |origin: &'static str| {
+            Request::options(format!(
+                "http://{}/reservation.ReservationService/Reserve",
+                addr
+            ))
+            .header(header::ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .header(
+                header::ACCESS_CONTROL_REQUEST_HEADERS,
+                "content-type,x-grpc-web,authorization,x-tenant",
+            )
+            .body(Body::empty())
+            .unwrap()
+        };
+        let client = Client::new();
+
+        let resp = client.request(preflight(ORIGIN)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let headers = resp.headers();
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], ORIGIN);
+        let allowed = headers[header::ACCESS_CONTROL_ALLOW_HEADERS]
+            .to_str()
+            .unwrap();
+        assert!(allowed.contains("authorization") && allowed.contains("x-tenant"));
+
+        let resp = client
+            .request(preflight("https://evil.example.com"))
+            .await
+            .unwrap();
+        assert!(!resp
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+}